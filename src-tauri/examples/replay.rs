@@ -0,0 +1,42 @@
+// 无界面回放按键轨迹，走完整的 捕获 → 组合键 → 数据库 流程
+// 用法：cargo run --example replay -- <轨迹文件> [输出目录]
+use keyboard_statistics_lib::input::ReplaySource;
+use keyboard_statistics_lib::keyboard::KeyboardMonitor;
+use keyboard_statistics_lib::database;
+use keyboard_statistics_lib::store::{SqliteStore, Store};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+fn main() -> Result<(), String> {
+    let mut args = std::env::args().skip(1);
+    let trace_path = PathBuf::from(args.next().ok_or("缺少轨迹文件参数")?);
+    let out_dir = args.next()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("keyboard-statistics-replay"));
+    std::fs::create_dir_all(&out_dir)
+        .map_err(|e| format!("无法创建输出目录: {}", e))?;
+
+    let db_path = out_dir.join("keyboard_events.db");
+    let store: Arc<dyn Store> = Arc::new(SqliteStore::open(&db_path)
+        .map_err(|e| format!("数据库连接失败: {}", e))?);
+
+    let source = ReplaySource::from_file(&trace_path)?;
+    let mut monitor = KeyboardMonitor::new(Ok(store.clone()));
+    monitor.start_with_source(Box::new(source))?;
+    monitor.join();
+    monitor.flush();
+
+    let conn = store.read()
+        .map_err(|e| format!("数据库连接失败: {}", e))?;
+    let total = database::get_total_key_count(&conn)
+        .map_err(|e| format!("查询总按键数失败: {}", e))?;
+    let top_keys = database::get_top_keys(&conn, 20)
+        .map_err(|e| format!("查询常用按键失败: {}", e))?;
+
+    println!("数据库: {}", db_path.display());
+    println!("总按键次数: {}", total);
+    for (key, count) in top_keys {
+        println!("{}\t{}", key, count);
+    }
+    Ok(())
+}
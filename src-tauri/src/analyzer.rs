@@ -1,6 +1,9 @@
-use crate::database::KeyboardEventRecord;
-use chrono::{DateTime, Local, Duration, NaiveDateTime, TimeZone, Datelike, Weekday};
+use crate::database::{ActivityState, EVENTS_WITH_NAMES, MASKED_KEY_CODE, MAX_FLIGHT_MS};
+use crate::apps::{AppCatalog, AppRule};
+use crate::rollup::{source_sql, Granularity};
+use chrono::{DateTime, Local, Duration, TimeZone};
 use rusqlite::Connection;
+use std::cmp::Reverse;
 use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 use rusqlite::params;
@@ -19,7 +22,8 @@ pub struct KeyStats {
     pub backspace_ratio: f64,
     pub most_used_keys: Vec<(String, u64)>,
     pub key_categories: HashMap<String, u64>,
-    pub app_usage: HashMap<String, u64>,
+    pub app_usage: HashMap<String, u64>,      // 按应用显示名称合并的按键数
+    pub app_categories: HashMap<String, u64>, // 按应用分类的按键数
     pub time_distribution: HashMap<String, u64>,
     pub prev_total_presses: u64,
     pub prev_avg_kpm: f64,
@@ -27,6 +31,12 @@ pub struct KeyStats {
     pub activity_heatmap: HashMap<String, u64>,
     pub key_combos: Vec<KeyCombo>,
     pub app_time_distribution: Vec<AppTimeData>,
+    pub key_hold_times: Vec<HoldTimeStats>,
+    pub app_hold_times: Vec<HoldTimeStats>,
+    pub held_repeats: Vec<KeyRepeatStats>,
+    pub active_minutes: f64, // 有输入的时间（分钟），由空闲检测记录
+    pub idle_minutes: f64,   // 离开的时间（分钟）
+    pub idle_ratio: f64,     // 离开时间占比（百分比）
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -35,13 +45,140 @@ pub struct AppTimeData {
     pub data: Vec<u64>,
 }
 
-pub struct DataAnalyzer {
-    conn: Connection,
+// 按住不放触发系统自动重复的统计，例如按住↓代替PageDown
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyRepeatStats {
+    pub key: String,
+    pub held_presses: u64,  // 触发了自动重复的按下次数
+    pub total_repeats: u64, // 自动重复总次数
+    pub max_repeats: u64,   // 单次按住的最多重复次数
+}
+
+// 按键统计视图：逻辑视图按显示名称合并（左右Shift都记为Shift），物理视图按实际按键区分
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyView {
+    Logical,
+    Physical,
+}
+
+impl KeyView {
+    pub fn parse(view: &str) -> Option<Self> {
+        match view {
+            "logical" => Some(KeyView::Logical),
+            "physical" => Some(KeyView::Physical),
+            _ => None,
+        }
+    }
+
+    // 分组使用的列，旧数据没有物理按键标识时退回显示名称
+    fn column(&self) -> &'static str {
+        match self {
+            KeyView::Logical => "key_code",
+            KeyView::Physical => "COALESCE(raw_key, key_code)",
+        }
+    }
+}
+
+// 按键按住时长统计（毫秒）
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HoldTimeStats {
+    pub label: String,
+    pub count: u64,
+    pub avg_ms: f64,
+    pub p50_ms: i64,
+    pub p90_ms: i64,
+}
+
+// 按键间隔直方图的分桶上界（毫秒），超过最后一个上界的样本计入最后一个桶
+pub const FLIGHT_HISTOGRAM_BOUNDS_MS: [i64; 10] = [50, 100, 150, 200, 300, 500, 1000, 2000, 5000, 30000];
+
+// 按键间隔（上一次按下到本次按下）分布统计
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FlightTimeStats {
+    pub label: String,
+    pub count: u64,
+    pub median_ms: i64,
+    pub p90_ms: i64,
+    pub histogram: Vec<u64>, // 长度为分桶上界数量+1
+}
+
+impl FlightTimeStats {
+    // samples 需已升序排列
+    fn from_samples(label: String, samples: Vec<i64>) -> Self {
+        let mut histogram = vec![0u64; FLIGHT_HISTOGRAM_BOUNDS_MS.len() + 1];
+        for value in &samples {
+            let bucket = FLIGHT_HISTOGRAM_BOUNDS_MS.iter()
+                .position(|bound| value <= bound)
+                .unwrap_or(FLIGHT_HISTOGRAM_BOUNDS_MS.len());
+            histogram[bucket] += 1;
+        }
+        FlightTimeStats {
+            label,
+            count: samples.len() as u64,
+            median_ms: crate::database::percentile(&samples, 50.0),
+            p90_ms: crate::database::percentile(&samples, 90.0),
+            histogram,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FlightTimeReport {
+    pub histogram_bounds_ms: Vec<i64>,
+    pub overall: FlightTimeStats,
+    pub by_app: Vec<FlightTimeStats>,
+    pub by_hour: Vec<FlightTimeStats>,
+    pub by_day: Vec<FlightTimeStats>,
 }
 
-impl DataAnalyzer {
-    pub fn new(conn: Connection) -> Self {
-        DataAnalyzer { conn }
+// 鼠标统计摘要
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MouseStats {
+    pub total_clicks: u64,
+    pub clicks_by_button: HashMap<String, u64>,
+    pub move_distance: f64,  // 指针移动距离（像素）
+    pub scroll_distance: i64, // 滚动格数（水平+垂直）
+    pub app_clicks: Vec<(String, u64)>,
+    pub app_move_distance: Vec<(String, f64)>,
+    pub click_time_distribution: HashMap<String, u64>, // 按小时的点击次数
+}
+
+// 单个键盘设备的统计
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeviceStats {
+    pub device_id: String,
+    pub name: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub total_presses: u64,
+    pub backspace_ratio: f64,
+    pub most_used_keys: Vec<(String, u64)>,
+}
+
+// 单个上下文标签（项目、分支、任务等）的统计
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ContextStats {
+    pub tag: String,
+    pub total_presses: u64,
+    pub typing_minutes: u64, // 有按键的分钟数，可粗略估算投入的时间
+    pub backspace_ratio: f64,
+}
+
+pub struct DataAnalyzer<'a> {
+    conn: &'a Connection,
+    apps: AppCatalog, // 应用显示名称和分类规则
+}
+
+impl<'a> DataAnalyzer<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        DataAnalyzer { conn, apps: AppCatalog::default() }
+    }
+
+    // 使用用户自定义的应用规则，优先于内置规则
+    pub fn with_app_rules(mut self, rules: &[AppRule]) -> Self {
+        self.apps = AppCatalog::new(rules);
+        self
     }
 
     pub fn get_stats(&self, time_range: &str) -> Result<KeyStats, rusqlite::Error> {
@@ -54,10 +191,20 @@ impl DataAnalyzer {
         let most_used_keys = self.get_most_used_keys(&start_time, &end_time, 10)?;
         let key_categories = self.get_key_categories(&start_time, &end_time)?;
         let app_usage = self.get_app_usage(&start_time, &end_time)?;
+        let app_categories = self.get_app_categories(&start_time, &end_time)?;
         let time_distribution = self.get_time_distribution(&start_time, &end_time)?;
         let activity_heatmap = self.get_activity_heatmap(time_range)?;
         let key_combos = self.get_key_combos(&start_time, &end_time, 10)?;
         let app_time_distribution = self.get_app_time_distribution(&start_time, &end_time)?;
+        let key_hold_times = self.get_key_hold_times(&start_time, &end_time, 10)?;
+        let app_hold_times = self.get_app_hold_times(&start_time, &end_time, 10)?;
+        let held_repeats = self.get_held_repeats(&start_time, &end_time, 10)?;
+        let (active_minutes, idle_minutes) = self.get_activity_minutes(&start_time, &end_time)?;
+        let idle_ratio = if active_minutes + idle_minutes > 0.0 {
+            idle_minutes / (active_minutes + idle_minutes) * 100.0
+        } else {
+            0.0
+        };
         
         // 获取前一周期的统计数据
         let (prev_start_time, prev_end_time) = self.get_previous_time_range(time_range)?;
@@ -73,6 +220,7 @@ impl DataAnalyzer {
             most_used_keys,
             key_categories,
             app_usage,
+            app_categories,
             time_distribution,
             prev_total_presses,
             prev_avg_kpm,
@@ -80,6 +228,12 @@ impl DataAnalyzer {
             activity_heatmap,
             key_combos,
             app_time_distribution,
+            key_hold_times,
+            app_hold_times,
+            held_repeats,
+            active_minutes,
+            idle_minutes,
+            idle_ratio,
         })
     }
 
@@ -87,7 +241,7 @@ impl DataAnalyzer {
         let now = Local::now();
         
         // 获取最早的记录时间
-        let first_event_time = match crate::database::get_first_event_time(&self.conn)? {
+        let first_event_time = match crate::database::get_first_event_time(self.conn)? {
             Some(time) => time,
             None => now - Duration::days(1)  // 如果没有记录，默认为昨天
         };
@@ -130,11 +284,9 @@ impl DataAnalyzer {
     }
 
     fn get_total_presses(&self, start_time: &DateTime<Local>, end_time: &DateTime<Local>) -> Result<u64, rusqlite::Error> {
-        let mut stmt = self.conn.prepare(
-            "SELECT COUNT(*) FROM keyboard_events WHERE timestamp BETWEEN ?1 AND ?2"
-        )?;
-        let count: u64 = stmt.query_row(
-            params![start_time.to_rfc3339(), end_time.to_rfc3339()],
+        let count: u64 = self.conn.query_row(
+            &format!("SELECT COALESCE(SUM(count), 0) FROM ({})", source_sql(self.conn, start_time, end_time, Granularity::Day)?),
+            [],
             |row| row.get(0)
         )?;
         Ok(count)
@@ -145,33 +297,44 @@ impl DataAnalyzer {
         let one_minute_ago = now - Duration::seconds(60);
         
         let mut stmt = self.conn.prepare(
-            "SELECT COUNT(*) FROM keyboard_events WHERE timestamp BETWEEN ?1 AND ?2"
+            "SELECT COUNT(*) FROM keyboard_events WHERE ts BETWEEN ?1 AND ?2"
         )?;
         
         let count: u64 = stmt.query_row(
-            params![one_minute_ago.to_rfc3339(), now.to_rfc3339()],
+            params![one_minute_ago.timestamp_millis(), now.timestamp_millis()],
             |row| row.get(0)
         )?;
         
         Ok(count as f64)
     }
 
+    // 按活跃时间计算平均KPM，没有活跃时间记录的旧数据按整个时间范围计算
     fn calculate_average_kpm(&self, start_time: &DateTime<Local>, end_time: &DateTime<Local>) -> Result<f64, rusqlite::Error> {
-        let duration = (*end_time - *start_time).num_minutes() as f64;
-        if duration <= 0.0 {
-            return Ok(0.0);
+        crate::database::calculate_average_kpm(self.conn, *start_time, *end_time)
+    }
+
+    // 时间范围内的活跃和空闲分钟数
+    fn get_activity_minutes(&self, start_time: &DateTime<Local>, end_time: &DateTime<Local>) -> Result<(f64, f64), rusqlite::Error> {
+        let intervals = crate::database::query_activity_intervals(self.conn, *start_time, *end_time)?;
+        let mut active_ms = 0;
+        let mut idle_ms = 0;
+        for interval in intervals {
+            let ms = interval.end_time.signed_duration_since(interval.start_time).num_milliseconds().max(0);
+            match interval.state {
+                ActivityState::Active => active_ms += ms,
+                ActivityState::Idle => idle_ms += ms,
+            }
         }
-        let total_presses = self.get_total_presses(start_time, end_time)?;
-        Ok(total_presses as f64 / duration)
+        Ok((active_ms as f64 / 60_000.0, idle_ms as f64 / 60_000.0))
     }
 
     fn calculate_backspace_ratio(&self, start_time: &DateTime<Local>, end_time: &DateTime<Local>) -> Result<f64, rusqlite::Error> {
-        let mut stmt = self.conn.prepare(
-            "SELECT COUNT(*) FROM keyboard_events WHERE key_code = 'Backspace' AND timestamp BETWEEN ?1 AND ?2"
-        )?;
-        
-        let backspace_count: u64 = stmt.query_row(
-            params![start_time.to_rfc3339(), end_time.to_rfc3339()],
+        let backspace_count: u64 = self.conn.query_row(
+            &format!(
+                "SELECT COALESCE(SUM(count), 0) FROM ({}) WHERE key_code = 'Backspace'",
+                source_sql(self.conn, start_time, end_time, Granularity::Day)?
+            ),
+            [],
             |row| row.get(0)
         )?;
         
@@ -186,16 +349,70 @@ impl DataAnalyzer {
 
     fn get_most_used_keys(&self, start_time: &DateTime<Local>, end_time: &DateTime<Local>, limit: usize) 
         -> Result<Vec<(String, u64)>, rusqlite::Error> {
-        let mut stmt = self.conn.prepare(
-            "SELECT key_code, COUNT(*) as count 
-             FROM keyboard_events 
-             WHERE timestamp BETWEEN ?1 AND ?2 
-             GROUP BY key_code 
-             ORDER BY count DESC 
-             LIMIT ?3"
+        self.get_most_used_keys_in_view(start_time, end_time, limit, KeyView::Logical)
+    }
+
+    // 获取按住触发自动重复最多的按键
+    fn get_held_repeats(&self, start_time: &DateTime<Local>, end_time: &DateTime<Local>, limit: usize)
+        -> Result<Vec<KeyRepeatStats>, rusqlite::Error> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT key_code, COUNT(*), SUM(repeat_count) as total, MAX(repeat_count) 
+             FROM {} 
+             WHERE ts BETWEEN ?1 AND ?2 
+             AND repeat_count > 0 
+             GROUP BY key_id 
+             ORDER BY total DESC 
+             LIMIT ?3",
+            EVENTS_WITH_NAMES
+        ))?;
+        let rows = stmt.query_map(
+            params![start_time.timestamp_millis(), end_time.timestamp_millis(), limit as i64],
+            |row| {
+                Ok(KeyRepeatStats {
+                    key: row.get(0)?,
+                    held_presses: row.get(1)?,
+                    total_repeats: row.get(2)?,
+                    max_repeats: row.get(3)?,
+                })
+            }
         )?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row?);
+        }
+        Ok(result)
+    }
+
+    // 按指定视图获取最常用的按键
+    pub fn get_key_usage(&self, time_range: &str, view: KeyView, limit: usize)
+        -> Result<Vec<(String, u64)>, rusqlite::Error> {
+        let (start_time, end_time) = self.get_time_range(time_range)?;
+        self.get_most_used_keys_in_view(&start_time, &end_time, limit, view)
+    }
+
+    fn get_most_used_keys_in_view(&self, start_time: &DateTime<Local>, end_time: &DateTime<Local>, limit: usize, view: KeyView)
+        -> Result<Vec<(String, u64)>, rusqlite::Error> {
+        // 汇总表只按 key_code 统计，物理按键视图读原始记录
+        let source = match view {
+            KeyView::Logical => source_sql(self.conn, start_time, end_time, Granularity::Day)?,
+            KeyView::Physical => format!(
+                "SELECT key_code, raw_key, 1 AS count FROM {} WHERE ts BETWEEN {} AND {}",
+                EVENTS_WITH_NAMES, start_time.timestamp_millis(), end_time.timestamp_millis()
+            ),
+        };
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} as key_name, SUM(count) as count 
+             FROM ({}) 
+             WHERE key_code != ?2 
+             GROUP BY key_name 
+             ORDER BY count DESC 
+             LIMIT ?1",
+            view.column(), source
+        ))?;
+        // 按应用策略脱敏的按键只计入总数，不参与按键排行
         let rows = stmt.query_map(
-            params![start_time.to_rfc3339(), end_time.to_rfc3339(), limit],
+            params![limit, MASKED_KEY_CODE],
             |row| {
                 Ok((
                     row.get::<_, String>(0)?,
@@ -213,14 +430,14 @@ impl DataAnalyzer {
 
     fn get_key_categories(&self, start_time: &DateTime<Local>, end_time: &DateTime<Local>) 
         -> Result<HashMap<String, u64>, rusqlite::Error> {
-        let mut stmt = self.conn.prepare(
-            "SELECT key_code, COUNT(*) as count 
-             FROM keyboard_events 
-             WHERE timestamp BETWEEN ?1 AND ?2 
-             GROUP BY key_code"
-        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT key_code, SUM(count) as count 
+             FROM ({}) 
+             GROUP BY key_id",
+            source_sql(self.conn, start_time, end_time, Granularity::Day)?
+        ))?;
         let rows = stmt.query_map(
-            params![start_time.to_rfc3339(), end_time.to_rfc3339()],
+            [],
             |row| {
                 Ok((
                     row.get::<_, String>(0)?,
@@ -238,16 +455,36 @@ impl DataAnalyzer {
         Ok(categories)
     }
 
+    // 按应用显示名称合并，同一应用在不同平台或版本下的进程名计为一个应用
     fn get_app_usage(&self, start_time: &DateTime<Local>, end_time: &DateTime<Local>) 
         -> Result<HashMap<String, u64>, rusqlite::Error> {
-        let mut stmt = self.conn.prepare(
-            "SELECT app_name, COUNT(*) as count 
-             FROM keyboard_events 
-             WHERE timestamp BETWEEN ?1 AND ?2 
-             GROUP BY app_name"
-        )?;
+        let mut app_usage = HashMap::new();
+        for (app, count) in self.get_raw_app_usage(start_time, end_time)? {
+            *app_usage.entry(self.apps.identify(&app).display_name).or_insert(0) += count;
+        }
+        Ok(app_usage)
+    }
+
+    fn get_app_categories(&self, start_time: &DateTime<Local>, end_time: &DateTime<Local>) 
+        -> Result<HashMap<String, u64>, rusqlite::Error> {
+        let mut categories = HashMap::new();
+        for (app, count) in self.get_raw_app_usage(start_time, end_time)? {
+            *categories.entry(self.apps.identify(&app).category).or_insert(0) += count;
+        }
+        Ok(categories)
+    }
+
+    // 按原始进程名统计
+    fn get_raw_app_usage(&self, start_time: &DateTime<Local>, end_time: &DateTime<Local>) 
+        -> Result<HashMap<String, u64>, rusqlite::Error> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT app_name, SUM(count) as count 
+             FROM ({}) 
+             GROUP BY app_id",
+            source_sql(self.conn, start_time, end_time, Granularity::Day)?
+        ))?;
         let rows = stmt.query_map(
-            params![start_time.to_rfc3339(), end_time.to_rfc3339()],
+            [],
             |row| {
                 Ok((
                     row.get::<_, String>(0)?,
@@ -266,14 +503,14 @@ impl DataAnalyzer {
 
     fn get_time_distribution(&self, start_time: &DateTime<Local>, end_time: &DateTime<Local>) 
         -> Result<HashMap<String, u64>, rusqlite::Error> {
-        let mut stmt = self.conn.prepare(
-            "SELECT strftime('%H', datetime(timestamp, 'localtime')) as hour, COUNT(*) as count 
-             FROM keyboard_events 
-             WHERE timestamp BETWEEN ?1 AND ?2 
-             GROUP BY hour"
-        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT strftime('%H', ts / 1000 + utc_offset, 'unixepoch') as hour, SUM(count) as count 
+             FROM ({}) 
+             GROUP BY hour",
+            source_sql(self.conn, start_time, end_time, Granularity::Hour)?
+        ))?;
         let rows = stmt.query_map(
-            params![start_time.to_rfc3339(), end_time.to_rfc3339()],
+            [],
             |row| {
                 Ok((
                     row.get::<_, String>(0)?,
@@ -311,7 +548,6 @@ impl DataAnalyzer {
     }
 
     fn get_previous_time_range(&self, time_range: &str) -> Result<(DateTime<Local>, DateTime<Local>), rusqlite::Error> {
-        let now = Local::now();
         let (current_start, current_end) = self.get_time_range(time_range)?;
         
         let duration = current_end.signed_duration_since(current_start);
@@ -360,15 +596,15 @@ impl DataAnalyzer {
     fn get_today_heatmap(&self) -> Result<HashMap<String, u64>, rusqlite::Error> {
         let (start_time, end_time) = self.get_time_range("today")?;
         
-        let mut stmt = self.conn.prepare(
-            "SELECT strftime('%H', datetime(timestamp, 'localtime')) as hour, COUNT(*) as count 
-             FROM keyboard_events 
-             WHERE timestamp BETWEEN ?1 AND ?2 
-             GROUP BY hour"
-        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT strftime('%H', ts / 1000 + utc_offset, 'unixepoch') as hour, SUM(count) as count 
+             FROM ({}) 
+             GROUP BY hour",
+            source_sql(self.conn, &start_time, &end_time, Granularity::Hour)?
+        ))?;
         
         let rows = stmt.query_map(
-            params![start_time.to_rfc3339(), end_time.to_rfc3339()],
+            [],
             |row| {
                 Ok((
                     row.get::<_, String>(0)?,
@@ -393,18 +629,18 @@ impl DataAnalyzer {
     fn get_week_heatmap(&self) -> Result<HashMap<String, u64>, rusqlite::Error> {
         let (start_time, end_time) = self.get_time_range("week")?;
         
-        let mut stmt = self.conn.prepare(
+        let mut stmt = self.conn.prepare(&format!(
             "SELECT 
-                strftime('%w', datetime(timestamp, 'localtime')) as day_of_week,
-                strftime('%H', datetime(timestamp, 'localtime')) as hour, 
-                COUNT(*) as count 
-             FROM keyboard_events 
-             WHERE timestamp BETWEEN ?1 AND ?2 
-             GROUP BY day_of_week, hour"
-        )?;
+                strftime('%w', ts / 1000 + utc_offset, 'unixepoch') as day_of_week,
+                strftime('%H', ts / 1000 + utc_offset, 'unixepoch') as hour, 
+                SUM(count) as count 
+             FROM ({}) 
+             GROUP BY day_of_week, hour",
+            source_sql(self.conn, &start_time, &end_time, Granularity::Hour)?
+        ))?;
         
         let rows = stmt.query_map(
-            params![start_time.to_rfc3339(), end_time.to_rfc3339()],
+            [],
             |row| {
                 Ok((
                     row.get::<_, String>(0)?,
@@ -431,18 +667,18 @@ impl DataAnalyzer {
     fn get_month_heatmap(&self) -> Result<HashMap<String, u64>, rusqlite::Error> {
         let (start_time, end_time) = self.get_time_range("month")?;
         
-        let mut stmt = self.conn.prepare(
+        let mut stmt = self.conn.prepare(&format!(
             "SELECT 
-                strftime('%d', datetime(timestamp, 'localtime')) as day,
-                strftime('%H', datetime(timestamp, 'localtime')) as hour, 
-                COUNT(*) as count 
-             FROM keyboard_events 
-             WHERE timestamp BETWEEN ?1 AND ?2 
-             GROUP BY day, hour"
-        )?;
+                strftime('%d', ts / 1000 + utc_offset, 'unixepoch') as day,
+                strftime('%H', ts / 1000 + utc_offset, 'unixepoch') as hour, 
+                SUM(count) as count 
+             FROM ({}) 
+             GROUP BY day, hour",
+            source_sql(self.conn, &start_time, &end_time, Granularity::Hour)?
+        ))?;
         
         let rows = stmt.query_map(
-            params![start_time.to_rfc3339(), end_time.to_rfc3339()],
+            [],
             |row| {
                 Ok((
                     row.get::<_, String>(0)?,
@@ -467,13 +703,13 @@ impl DataAnalyzer {
 
     // 获取所有时间活动热力图（按星期和小时的汇总）
     fn get_all_time_heatmap(&self) -> Result<HashMap<String, u64>, rusqlite::Error> {
-        // 对于全部数据，我们按星期几和小时汇总
+        // 对于全部数据，我们按星期几和小时汇总，直接读小时汇总表
         let mut stmt = self.conn.prepare(
             "SELECT 
-                strftime('%w', datetime(timestamp, 'localtime')) as day_of_week,
-                strftime('%H', datetime(timestamp, 'localtime')) as hour, 
-                COUNT(*) as count 
-             FROM keyboard_events 
+                strftime('%w', bucket_ts / 1000 + utc_offset, 'unixepoch') as day_of_week,
+                strftime('%H', bucket_ts / 1000 + utc_offset, 'unixepoch') as hour, 
+                SUM(count) as count 
+             FROM rollup_hour 
              GROUP BY day_of_week, hour"
         )?;
         
@@ -505,18 +741,18 @@ impl DataAnalyzer {
     fn get_key_combos(&self, start_time: &DateTime<Local>, end_time: &DateTime<Local>, limit: usize) 
         -> Result<Vec<KeyCombo>, rusqlite::Error> {
         // 查询组合键使用情况
-        let mut stmt = self.conn.prepare(
-            "SELECT key_code, COUNT(*) as count 
-             FROM keyboard_events 
-             WHERE timestamp BETWEEN ?1 AND ?2 
-             AND key_code LIKE '%+%' 
-             GROUP BY key_code 
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT key_code, SUM(count) as count 
+             FROM ({}) 
+             WHERE key_code LIKE '%+%' 
+             GROUP BY key_id 
              ORDER BY count DESC 
-             LIMIT ?3"
-        )?;
+             LIMIT ?1",
+            source_sql(self.conn, start_time, end_time, Granularity::Day)?
+        ))?;
         
         let rows = stmt.query_map(
-            params![start_time.to_rfc3339(), end_time.to_rfc3339(), limit as i64],
+            params![limit as i64],
             |row| {
                 Ok(KeyCombo {
                     combo: row.get::<_, String>(0)?,
@@ -537,67 +773,401 @@ impl DataAnalyzer {
     fn get_app_time_distribution(&self, start_time: &DateTime<Local>, end_time: &DateTime<Local>) 
         -> Result<Vec<AppTimeData>, rusqlite::Error> {
         // 获取前5个最常用的应用
-        let mut app_usage = self.get_app_usage(start_time, end_time)?;
+        let app_usage = self.get_app_usage(start_time, end_time)?;
         let mut top_apps: Vec<_> = app_usage.iter()
             .map(|(app, count)| (app.clone(), *count))
             .collect();
             
         // 按使用量降序排序
-        top_apps.sort_by(|a, b| b.1.cmp(&a.1));
-        
-        // 获取前5个应用
-        let top_apps = if top_apps.len() > 5 {
-            top_apps[0..5].to_vec()
-        } else {
-            top_apps
-        };
+        top_apps.sort_by_key(|app| Reverse(app.1));
+        top_apps.truncate(5);
         
-        // 准备返回结果
-        let mut result = Vec::new();
+        // 按进程名查询24小时的使用分布，再合并到显示名称
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT app_name, strftime('%H', ts / 1000 + utc_offset, 'unixepoch') as hour, SUM(count) as count 
+             FROM ({}) 
+             GROUP BY app_id, hour",
+            source_sql(self.conn, start_time, end_time, Granularity::Hour)?
+        ))?;
+        let rows = stmt.query_map(
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, u64>(2)?
+                ))
+            }
+        )?;
         
-        // 对于每个应用，获取其24小时的使用分布
-        for (app_name, _) in &top_apps {
-            // 查询该应用在24小时内的使用分布
-            let mut stmt = self.conn.prepare(
-                "SELECT strftime('%H', datetime(timestamp, 'localtime')) as hour, COUNT(*) as count 
-                 FROM keyboard_events 
-                 WHERE timestamp BETWEEN ?1 AND ?2 
-                 AND app_name = ?3
-                 GROUP BY hour
-                 ORDER BY hour"
-            )?;
-            
-            let hour_rows = stmt.query_map(
-                params![start_time.to_rfc3339(), end_time.to_rfc3339(), app_name],
-                |row| {
-                    Ok((
-                        row.get::<_, String>(0)?,
-                        row.get::<_, u64>(1)?
-                    ))
-                }
-            )?;
-            
-            // 创建24小时的数据数组，初始化为0
-            let mut hourly_data = vec![0u64; 24];
-            
-            // 填充实际数据
-            for hour_row in hour_rows {
-                if let Ok((hour_str, count)) = hour_row {
-                    if let Ok(hour) = hour_str.parse::<usize>() {
-                        if hour < 24 {
-                            hourly_data[hour] = count;
-                        }
-                    }
+        // 创建24小时的数据数组，初始化为0
+        let mut hourly: HashMap<String, Vec<u64>> = top_apps.iter()
+            .map(|(app_name, _)| (app_name.clone(), vec![0u64; 24]))
+            .collect();
+        for row in rows {
+            let (app, hour_str, count) = row?;
+            let Some(hourly_data) = hourly.get_mut(&self.apps.identify(&app).display_name) else {
+                continue;
+            };
+            if let Ok(hour) = hour_str.parse::<usize>() {
+                if hour < 24 {
+                    hourly_data[hour] += count;
                 }
             }
-            
-            // 添加到结果
-            result.push(AppTimeData {
-                label: app_name.clone(),
-                data: hourly_data,
-            });
         }
         
+        let result = top_apps.into_iter()
+            .map(|(app_name, _)| AppTimeData {
+                data: hourly.remove(&app_name).unwrap_or_default(),
+                label: app_name,
+            })
+            .collect();
+        Ok(result)
+    }
+
+    // 获取各按键的按住时长统计
+    pub fn get_key_hold_times(&self, start_time: &DateTime<Local>, end_time: &DateTime<Local>, limit: usize)
+        -> Result<Vec<HoldTimeStats>, rusqlite::Error> {
+        self.get_hold_times_by("key_code", start_time, end_time, limit)
+    }
+
+    // 获取各应用的按住时长统计
+    pub fn get_app_hold_times(&self, start_time: &DateTime<Local>, end_time: &DateTime<Local>, limit: usize)
+        -> Result<Vec<HoldTimeStats>, rusqlite::Error> {
+        self.get_hold_times_by("app_name", start_time, end_time, limit)
+    }
+
+    // 按指定列分组计算按住时长的平均值和百分位数，按样本数降序返回
+    fn get_hold_times_by(&self, column: &str, start_time: &DateTime<Local>, end_time: &DateTime<Local>, limit: usize)
+        -> Result<Vec<HoldTimeStats>, rusqlite::Error> {
+        let groups = self.get_samples_by(column, "hold_ms", start_time, end_time)?;
+
+        let mut result: Vec<HoldTimeStats> = groups.into_iter().map(|(label, holds)| {
+            HoldTimeStats {
+                label,
+                count: holds.len() as u64,
+                avg_ms: holds.iter().sum::<i64>() as f64 / holds.len() as f64,
+                p50_ms: crate::database::percentile(&holds, 50.0),
+                p90_ms: crate::database::percentile(&holds, 90.0),
+            }
+        }).collect();
+
+        result.sort_by_key(|stats| Reverse(stats.count));
+        result.truncate(limit);
         Ok(result)
     }
-} 
\ No newline at end of file
+
+    // 获取鼠标统计摘要
+    pub fn get_mouse_stats(&self, time_range: &str) -> Result<MouseStats, rusqlite::Error> {
+        let (start_time, end_time) = self.get_time_range(time_range)?;
+        let range = params![start_time.timestamp_millis(), end_time.timestamp_millis()];
+
+        let mut clicks_by_button = HashMap::new();
+        let mut stmt = self.conn.prepare(
+            "SELECT button, COUNT(*) FROM mouse_clicks 
+             WHERE ts BETWEEN ?1 AND ?2 
+             GROUP BY button"
+        )?;
+        let rows = stmt.query_map(range, |row| Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?)))?;
+        for row in rows {
+            let (button, count) = row?;
+            clicks_by_button.insert(button, count);
+        }
+        let total_clicks = clicks_by_button.values().sum();
+
+        let mut stmt = self.conn.prepare(
+            "SELECT app_name, COUNT(*) as count FROM mouse_clicks 
+             WHERE ts BETWEEN ?1 AND ?2 
+             GROUP BY app_name 
+             ORDER BY count DESC 
+             LIMIT 10"
+        )?;
+        let app_clicks = stmt.query_map(range, |row| Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut click_time_distribution = HashMap::new();
+        let mut stmt = self.conn.prepare(
+            "SELECT strftime('%H', ts / 1000 + utc_offset, 'unixepoch') as hour, COUNT(*) FROM mouse_clicks 
+             WHERE ts BETWEEN ?1 AND ?2 
+             GROUP BY hour"
+        )?;
+        let rows = stmt.query_map(range, |row| Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?)))?;
+        for row in rows {
+            let (hour, count) = row?;
+            click_time_distribution.insert(hour, count);
+        }
+
+        let (move_distance, scroll_distance): (f64, i64) = self.conn.query_row(
+            "SELECT COALESCE(SUM(move_distance), 0), COALESCE(SUM(scroll_x + scroll_y), 0) FROM mouse_activity 
+             WHERE minute_ts BETWEEN ?1 AND ?2",
+            range,
+            |row| Ok((row.get(0)?, row.get(1)?))
+        )?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT app_name, SUM(move_distance) as distance FROM mouse_activity 
+             WHERE minute_ts BETWEEN ?1 AND ?2 
+             GROUP BY app_name 
+             ORDER BY distance DESC 
+             LIMIT 10"
+        )?;
+        let app_move_distance = stmt.query_map(range, |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(MouseStats {
+            total_clicks,
+            clicks_by_button,
+            move_distance,
+            scroll_distance,
+            app_clicks,
+            app_move_distance,
+            click_time_distribution,
+        })
+    }
+
+    // 按键盘设备分别统计，只包含能区分设备的输入源（evdev）记录的按键
+    pub fn get_device_stats(&self, time_range: &str) -> Result<Vec<DeviceStats>, rusqlite::Error> {
+        let (start_time, end_time) = self.get_time_range(time_range)?;
+        let mut stmt = self.conn.prepare(
+            "SELECT e.device_id, COALESCE(d.name, e.device_id), COALESCE(d.vendor_id, 0), COALESCE(d.product_id, 0), 
+                    COUNT(*) as count, SUM(k.key_code = 'Backspace') 
+             FROM keyboard_events e 
+             JOIN keys k ON k.key_id = e.key_id 
+             LEFT JOIN input_devices d ON d.device_id = e.device_id 
+             WHERE e.ts BETWEEN ?1 AND ?2 AND e.device_id IS NOT NULL 
+             GROUP BY e.device_id 
+             ORDER BY count DESC"
+        )?;
+        let rows = stmt.query_map(
+            params![start_time.timestamp_millis(), end_time.timestamp_millis()],
+            |row| {
+                let total_presses: u64 = row.get(4)?;
+                let backspace_count: u64 = row.get(5)?;
+                Ok(DeviceStats {
+                    device_id: row.get(0)?,
+                    name: row.get(1)?,
+                    vendor_id: row.get(2)?,
+                    product_id: row.get(3)?,
+                    total_presses,
+                    backspace_ratio: backspace_count as f64 / total_presses as f64 * 100.0,
+                    most_used_keys: Vec::new(),
+                })
+            }
+        )?;
+        let mut devices = rows.collect::<Result<Vec<_>, _>>()?;
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT key_code, COUNT(*) as count 
+             FROM {} 
+             WHERE ts BETWEEN ?1 AND ?2 AND device_id = ?3 AND key_code != ?4 
+             GROUP BY key_id 
+             ORDER BY count DESC 
+             LIMIT 10",
+            EVENTS_WITH_NAMES
+        ))?;
+        for device in devices.iter_mut() {
+            device.most_used_keys = stmt.query_map(
+                params![start_time.timestamp_millis(), end_time.timestamp_millis(), device.device_id, MASKED_KEY_CODE],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?))
+            )?.collect::<Result<Vec<_>, _>>()?;
+        }
+        Ok(devices)
+    }
+
+    // 按上下文标签分别统计，只包含外部工具告知过标签时记录的按键
+    pub fn get_context_stats(&self, time_range: &str) -> Result<Vec<ContextStats>, rusqlite::Error> {
+        let (start_time, end_time) = self.get_time_range(time_range)?;
+        // 毫秒时间戳整除 60000 即所在分钟，按不同分钟数估算打字时间
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT context_tag, COUNT(*) as count, COUNT(DISTINCT ts / 60000), SUM(key_code = 'Backspace') 
+             FROM {} 
+             WHERE ts BETWEEN ?1 AND ?2 AND context_tag IS NOT NULL 
+             GROUP BY context_tag 
+             ORDER BY count DESC",
+            EVENTS_WITH_NAMES
+        ))?;
+        let rows = stmt.query_map(
+            params![start_time.timestamp_millis(), end_time.timestamp_millis()],
+            |row| {
+                let total_presses: u64 = row.get(1)?;
+                let backspace_count: u64 = row.get(3)?;
+                Ok(ContextStats {
+                    tag: row.get(0)?,
+                    total_presses,
+                    typing_minutes: row.get(2)?,
+                    backspace_ratio: backspace_count as f64 / total_presses as f64 * 100.0,
+                })
+            }
+        )?;
+        rows.collect()
+    }
+
+    // 获取按键间隔分布报告：整体、按应用、按小时和按日期
+    pub fn get_flight_time_report(&self, time_range: &str) -> Result<FlightTimeReport, rusqlite::Error> {
+        let (start_time, end_time) = self.get_time_range(time_range)?;
+
+        let overall = self.get_flight_times_by("'all'", &start_time, &end_time)?
+            .pop()
+            .unwrap_or_else(|| FlightTimeStats::from_samples("all".to_string(), Vec::new()));
+
+        let mut by_app = self.get_flight_times_by("app_name", &start_time, &end_time)?;
+        by_app.sort_by_key(|stats| Reverse(stats.count));
+
+        let mut by_hour = self.get_flight_times_by(
+            "strftime('%H', ts / 1000 + utc_offset, 'unixepoch')", &start_time, &end_time)?;
+        by_hour.sort_by(|a, b| a.label.cmp(&b.label));
+
+        let mut by_day = self.get_flight_times_by(
+            "strftime('%Y-%m-%d', ts / 1000 + utc_offset, 'unixepoch')", &start_time, &end_time)?;
+        by_day.sort_by(|a, b| a.label.cmp(&b.label));
+
+        Ok(FlightTimeReport {
+            histogram_bounds_ms: FLIGHT_HISTOGRAM_BOUNDS_MS.to_vec(),
+            overall,
+            by_app,
+            by_hour,
+            by_day,
+        })
+    }
+
+    fn get_flight_times_by(&self, label_expr: &str, start_time: &DateTime<Local>, end_time: &DateTime<Local>)
+        -> Result<Vec<FlightTimeStats>, rusqlite::Error> {
+        let groups = self.get_samples_by(label_expr, "flight_ms", start_time, end_time)?;
+        // 较早版本记录了跨越停顿的间隔，统计时一并排除
+        Ok(groups.into_iter()
+            .map(|(label, mut samples)| {
+                samples.retain(|ms| *ms <= MAX_FLIGHT_MS);
+                FlightTimeStats::from_samples(label, samples)
+            })
+            .filter(|stats| stats.count > 0)
+            .collect())
+    }
+
+    // 按分组表达式收集指定列的非空样本，每组样本升序排列
+    fn get_samples_by(&self, label_expr: &str, value_column: &str, start_time: &DateTime<Local>, end_time: &DateTime<Local>)
+        -> Result<HashMap<String, Vec<i64>>, rusqlite::Error> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}, {} 
+             FROM {} 
+             WHERE ts BETWEEN ?1 AND ?2 
+             AND {} IS NOT NULL",
+            label_expr, value_column, EVENTS_WITH_NAMES, value_column
+        ))?;
+
+        let rows = stmt.query_map(
+            params![start_time.timestamp_millis(), end_time.timestamp_millis()],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?
+                ))
+            }
+        )?;
+
+        let mut groups: HashMap<String, Vec<i64>> = HashMap::new();
+        for row in rows {
+            let (label, value) = row?;
+            groups.entry(label).or_default().push(value);
+        }
+        for samples in groups.values_mut() {
+            samples.sort_unstable();
+        }
+        Ok(groups)
+    }
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{insert_event, KeyboardEventRecord};
+    use crate::store::{MemoryStore, Store};
+
+    // 最近半小时内的按键，其中一部分按应用策略脱敏
+    fn sample_store() -> MemoryStore {
+        let store = MemoryStore::new().unwrap();
+        let conn = store.write().unwrap();
+        let now = Local::now();
+        let keys = ["A", "Backspace", "Ctrl+C", "A", MASKED_KEY_CODE];
+        for i in 0..100i64 {
+            insert_event(&conn, &KeyboardEventRecord {
+                timestamp: now - Duration::seconds(i * 17),
+                key_code: keys[i as usize % keys.len()].to_string(),
+                app_name: if i % 4 == 0 { "chrome.exe" } else { "Code.exe" }.to_string(),
+                hold_ms: Some(60 + i),
+                flight_ms: if i == 0 { None } else { Some(100 + i) },
+                raw_key: None,
+                scan_code: None,
+                repeat_count: if i == 1 { 3 } else { 0 },
+                device_id: None,
+                context_tag: if i < 10 { Some("project:crate".to_string()) } else { None },
+            }).unwrap();
+        }
+        drop(conn);
+        store
+    }
+
+    #[test]
+    fn summarizes_recent_keys() {
+        let store = sample_store();
+        let conn = store.read().unwrap();
+        let stats = DataAnalyzer::new(&conn).get_stats("week").unwrap();
+        assert_eq!(stats.total_presses, 100);
+        assert_eq!(stats.backspace_ratio, 20.0);
+        assert_eq!(stats.app_usage.values().sum::<u64>(), 100);
+        // 脱敏的按键只计入总数
+        assert_eq!(stats.most_used_keys[0], ("A".to_string(), 40));
+        assert!(stats.most_used_keys.iter().all(|(key, _)| key != MASKED_KEY_CODE));
+        assert_eq!(stats.key_combos.len(), 1);
+        assert_eq!(stats.key_combos[0].count, 20);
+        assert_eq!(stats.held_repeats[0].key, "Backspace");
+        assert_eq!(stats.key_hold_times.iter().map(|hold| hold.count).sum::<u64>(), 100);
+    }
+
+    #[test]
+    fn averages_kpm_over_recorded_active_time() {
+        let store = sample_store();
+        let conn = store.write().unwrap();
+        let now = Local::now();
+        let end = now + Duration::minutes(1);
+        // 旧数据没有活跃时间记录，无法计算
+        let analyzer = DataAnalyzer::new(&conn);
+        let start = now - Duration::minutes(99);
+        assert_eq!(analyzer.calculate_average_kpm(&start, &end).unwrap(), 0.0);
+
+        // 最近 5 分钟活跃，其间有 18 次按键；之前的按键没有对应的活跃时间，不计入
+        crate::database::insert_activity_interval(&conn, &crate::database::ActivityInterval {
+            start_time: now - Duration::minutes(5),
+            end_time: now,
+            state: ActivityState::Active,
+        }).unwrap();
+        assert_eq!(analyzer.calculate_average_kpm(&start, &end).unwrap(), 18.0 / 5.0);
+    }
+
+    #[test]
+    fn groups_raw_records_by_name() {
+        let store = sample_store();
+        // 跨越停顿的间隔不计入
+        insert_event(&store.write().unwrap(), &KeyboardEventRecord {
+            timestamp: Local::now(),
+            key_code: "A".to_string(),
+            app_name: "Code.exe".to_string(),
+            hold_ms: None,
+            flight_ms: Some(8 * 60 * 60 * 1000),
+            raw_key: None,
+            scan_code: None,
+            repeat_count: 0,
+            device_id: None,
+            context_tag: None,
+        }).unwrap();
+        let conn = store.read().unwrap();
+        let analyzer = DataAnalyzer::new(&conn);
+        let report = analyzer.get_flight_time_report("week").unwrap();
+        let mut apps: Vec<_> = report.by_app.iter().map(|app| (app.label.as_str(), app.count)).collect();
+        apps.sort();
+        assert_eq!(apps, [("Code.exe", 75), ("chrome.exe", 24)]);
+
+        let contexts = analyzer.get_context_stats("week").unwrap();
+        assert_eq!(contexts.len(), 1);
+        assert_eq!(contexts[0].total_presses, 10);
+        assert_eq!(contexts[0].backspace_ratio, 20.0);
+    }
+}
@@ -0,0 +1,249 @@
+// 应用识别：把进程名（如 Code.exe、code、WINWORD.EXE）归并为统一的显示名称和分类，
+// 只在查询统计时使用，数据库中保留原始进程名
+use crate::policy::matches_app_pattern;
+use serde::{Serialize, Deserialize};
+
+// 没有匹配到分类的应用
+pub const UNCATEGORIZED: &str = "其他";
+
+// 内置规则：进程名（支持 * 和 ? 通配符）、显示名称、分类
+const BUILTIN_RULES: &[(&str, &str, &str)] = &[
+    // 开发
+    ("code.exe", "VS Code", "开发"),
+    ("code", "VS Code", "开发"),
+    ("code - insiders.exe", "VS Code", "开发"),
+    ("code-insiders", "VS Code", "开发"),
+    ("cursor.exe", "Cursor", "开发"),
+    ("cursor", "Cursor", "开发"),
+    ("devenv.exe", "Visual Studio", "开发"),
+    ("idea64.exe", "IntelliJ IDEA", "开发"),
+    ("idea", "IntelliJ IDEA", "开发"),
+    ("pycharm64.exe", "PyCharm", "开发"),
+    ("pycharm", "PyCharm", "开发"),
+    ("webstorm64.exe", "WebStorm", "开发"),
+    ("goland64.exe", "GoLand", "开发"),
+    ("clion64.exe", "CLion", "开发"),
+    ("rustrover64.exe", "RustRover", "开发"),
+    ("studio64.exe", "Android Studio", "开发"),
+    ("sublime_text*", "Sublime Text", "开发"),
+    ("notepad++.exe", "Notepad++", "开发"),
+    ("vim", "Vim", "开发"),
+    ("nvim", "Neovim", "开发"),
+    ("emacs*", "Emacs", "开发"),
+    ("windowsterminal.exe", "终端", "开发"),
+    ("cmd.exe", "终端", "开发"),
+    ("powershell.exe", "终端", "开发"),
+    ("pwsh.exe", "终端", "开发"),
+    ("gnome-terminal*", "终端", "开发"),
+    ("konsole", "终端", "开发"),
+    ("alacritty*", "终端", "开发"),
+    ("kitty", "终端", "开发"),
+    ("wezterm*", "终端", "开发"),
+    ("xterm", "终端", "开发"),
+    // 沟通
+    ("wechat.exe", "微信", "沟通"),
+    ("weixin.exe", "微信", "沟通"),
+    ("wechat", "微信", "沟通"),
+    ("qq.exe", "QQ", "沟通"),
+    ("qq", "QQ", "沟通"),
+    ("dingtalk.exe", "钉钉", "沟通"),
+    ("feishu.exe", "飞书", "沟通"),
+    ("lark.exe", "飞书", "沟通"),
+    ("feishu", "飞书", "沟通"),
+    ("wxwork.exe", "企业微信", "沟通"),
+    ("slack.exe", "Slack", "沟通"),
+    ("slack", "Slack", "沟通"),
+    ("teams.exe", "Teams", "沟通"),
+    ("ms-teams.exe", "Teams", "沟通"),
+    ("discord.exe", "Discord", "沟通"),
+    ("discord", "Discord", "沟通"),
+    ("telegram.exe", "Telegram", "沟通"),
+    ("telegram-desktop", "Telegram", "沟通"),
+    ("outlook.exe", "Outlook", "沟通"),
+    ("thunderbird*", "Thunderbird", "沟通"),
+    // 写作
+    ("winword.exe", "Word", "写作"),
+    ("wps.exe", "WPS", "写作"),
+    ("obsidian.exe", "Obsidian", "写作"),
+    ("obsidian", "Obsidian", "写作"),
+    ("typora.exe", "Typora", "写作"),
+    ("typora", "Typora", "写作"),
+    ("notion.exe", "Notion", "写作"),
+    ("notepad.exe", "记事本", "写作"),
+    ("gedit", "gedit", "写作"),
+    // 办公
+    ("excel.exe", "Excel", "办公"),
+    ("powerpnt.exe", "PowerPoint", "办公"),
+    ("onenote.exe", "OneNote", "办公"),
+    ("et.exe", "WPS 表格", "办公"),
+    ("soffice*", "LibreOffice", "办公"),
+    ("libreoffice*", "LibreOffice", "办公"),
+    // 浏览
+    ("chrome.exe", "Chrome", "浏览"),
+    ("chrome", "Chrome", "浏览"),
+    ("google-chrome*", "Chrome", "浏览"),
+    ("chromium*", "Chromium", "浏览"),
+    ("msedge.exe", "Edge", "浏览"),
+    ("msedge", "Edge", "浏览"),
+    ("firefox.exe", "Firefox", "浏览"),
+    ("firefox*", "Firefox", "浏览"),
+    ("brave.exe", "Brave", "浏览"),
+    ("opera.exe", "Opera", "浏览"),
+    // 设计
+    ("figma.exe", "Figma", "设计"),
+    ("photoshop.exe", "Photoshop", "设计"),
+    ("illustrator.exe", "Illustrator", "设计"),
+    ("blender*", "Blender", "设计"),
+    ("gimp*", "GIMP", "设计"),
+    ("inkscape*", "Inkscape", "设计"),
+    // 游戏
+    ("steam.exe", "Steam", "游戏"),
+    ("steam", "Steam", "游戏"),
+    ("steamwebhelper*", "Steam", "游戏"),
+    ("epicgameslauncher.exe", "Epic Games", "游戏"),
+    ("leagueclient*.exe", "英雄联盟", "游戏"),
+    ("league of legends.exe", "英雄联盟", "游戏"),
+    ("cs2.exe", "Counter-Strike 2", "游戏"),
+    ("genshinimpact.exe", "原神", "游戏"),
+];
+
+// 一条应用识别规则，显示名称和分类为空时沿用后面的规则或默认值
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppRule {
+    pub pattern: String, // 进程名，支持 * 和 ? 通配符，不区分大小写
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+}
+
+impl AppRule {
+    pub fn matches(&self, app_name: &str) -> bool {
+        matches_app_pattern(&self.pattern, app_name)
+    }
+}
+
+// 识别结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppIdentity {
+    pub display_name: String,
+    pub category: String,
+}
+
+pub fn builtin_rules() -> Vec<AppRule> {
+    BUILTIN_RULES.iter()
+        .map(|(pattern, display_name, category)| AppRule {
+            pattern: pattern.to_string(),
+            display_name: Some(display_name.to_string()),
+            category: Some(category.to_string()),
+        })
+        .collect()
+}
+
+// 用户规则在前、内置规则在后，按顺序匹配
+#[derive(Debug, Clone)]
+pub struct AppCatalog {
+    rules: Vec<AppRule>,
+}
+
+impl AppCatalog {
+    pub fn new(user_rules: &[AppRule]) -> Self {
+        let mut rules = user_rules.to_vec();
+        rules.extend(builtin_rules());
+        AppCatalog { rules }
+    }
+
+    // 显示名称和分类分别取第一条提供该项的匹配规则，
+    // 因此用户规则可以只修改分类而保留内置的显示名称
+    pub fn identify(&self, app_name: &str) -> AppIdentity {
+        let matched: Vec<&AppRule> = self.rules.iter().filter(|rule| rule.matches(app_name)).collect();
+        let display_name = matched.iter()
+            .find_map(|rule| rule.display_name.clone())
+            .unwrap_or_else(|| default_display_name(app_name));
+        let category = matched.iter()
+            .find_map(|rule| rule.category.clone())
+            .unwrap_or_else(|| UNCATEGORIZED.to_string());
+        AppIdentity { display_name, category }
+    }
+}
+
+impl Default for AppCatalog {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+// 没有规则时去掉 Windows 可执行文件的扩展名
+fn default_display_name(app_name: &str) -> String {
+    let stem_len = app_name.len().saturating_sub(4);
+    match app_name.get(stem_len..) {
+        Some(ext) if stem_len > 0 && ext.eq_ignore_ascii_case(".exe") => app_name[..stem_len].to_string(),
+        _ => app_name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, display_name: Option<&str>, category: Option<&str>) -> AppRule {
+        AppRule {
+            pattern: pattern.to_string(),
+            display_name: display_name.map(str::to_string),
+            category: category.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn identifies_apps_by_rule_precedence() {
+        let catalog = AppCatalog::new(&[
+            // 只修改分类，保留内置的显示名称
+            rule("code.exe", None, Some("工作")),
+            // 只修改显示名称，保留内置的分类
+            rule("chrome*", Some("谷歌浏览器"), None),
+            // 用户规则覆盖内置规则
+            rule("wechat.exe", Some("WeChat"), Some("社交")),
+            // 用户规则之间按顺序匹配
+            rule("my-*", Some("内部工具"), Some("工作")),
+            rule("my-app", Some("不会用到"), Some("不会用到")),
+        ]);
+        let cases = [
+            ("Code.exe", "VS Code", "工作"),
+            ("code", "VS Code", "开发"),
+            ("chrome.exe", "谷歌浏览器", "浏览"),
+            ("WeChat.exe", "WeChat", "社交"),
+            ("my-app", "内部工具", "工作"),
+            ("WINWORD.EXE", "Word", "写作"),
+            ("sublime_text.exe", "Sublime Text", "开发"),
+            ("unknown.exe", "unknown", UNCATEGORIZED),
+            ("Unknown.EXE", "Unknown", UNCATEGORIZED),
+            ("unknown", "unknown", UNCATEGORIZED),
+        ];
+        for (app_name, display_name, category) in cases {
+            assert_eq!(
+                catalog.identify(app_name),
+                AppIdentity { display_name: display_name.to_string(), category: category.to_string() },
+                "{}",
+                app_name
+            );
+        }
+    }
+
+    #[test]
+    fn strips_exe_from_default_names() {
+        let cases = [
+            ("notes.exe", "notes"),
+            ("NOTES.EXE", "NOTES"),
+            ("my app.Exe", "my app"),
+            (".exe", ".exe"),
+            ("exe", "exe"),
+            ("notes.exe.bak", "notes.exe.bak"),
+            ("notes", "notes"),
+            ("微信.exe", "微信"),
+            ("", ""),
+        ];
+        for (app_name, expected) in cases {
+            assert_eq!(default_display_name(app_name), expected, "{:?}", app_name);
+        }
+    }
+}
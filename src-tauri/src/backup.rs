@@ -0,0 +1,417 @@
+// 数据库备份：使用 SQLite 的在线备份接口从只读连接复制，备份期间按键照常写入。
+// 备份文件保存在数据目录的 backups 子目录中，文件名包含备份方式和时间，
+// 例如 keyboard_events-auto-20240301-040000.db
+use crate::migrations::{migrate, schema_version, SCHEMA_VERSION};
+use crate::store::Store;
+use chrono::{DateTime, Duration, Local, NaiveDateTime, TimeZone};
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::{Connection, OpenFlags};
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// 后台检查是否需要自动备份的间隔
+pub const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+const FILE_PREFIX: &str = "keyboard_events-";
+const TIME_FORMAT: &str = "%Y%m%d-%H%M%S";
+// 数据库被占用时重试的次数和间隔
+const BUSY_RETRIES: u32 = 100;
+const BUSY_PAUSE: std::time::Duration = std::time::Duration::from_millis(50);
+// 从该版本起有按键汇总表，包含按保留策略清理了原始记录的时间段
+const ROLLUP_VERSION: u32 = 11;
+
+// 自动备份设置，保存在配置文件中
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupSettings {
+    pub enabled: bool,       // 是否定期自动备份
+    pub interval_hours: u32, // 自动备份的间隔（小时）
+    pub keep: u32,           // 保留最近几份自动备份，更早的自动删除
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        BackupSettings {
+            enabled: false,
+            interval_hours: 24,
+            keep: 7,
+        }
+    }
+}
+
+impl BackupSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_hours == 0 {
+            return Err("自动备份的间隔至少为 1 小时".to_string());
+        }
+        if self.keep == 0 {
+            return Err("至少保留 1 份自动备份".to_string());
+        }
+        Ok(())
+    }
+}
+
+// 备份方式，自动备份按设置的份数轮换，其余的需要手动删除
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupKind {
+    Auto,          // 定期自动备份
+    Manual,        // 手动备份
+    BeforeRestore, // 恢复备份前自动保存的当前数据
+}
+
+impl BackupKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            BackupKind::Auto => "auto",
+            BackupKind::Manual => "manual",
+            BackupKind::BeforeRestore => "before-restore",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        [BackupKind::Auto, BackupKind::Manual, BackupKind::BeforeRestore]
+            .into_iter()
+            .find(|kind| kind.as_str() == value)
+    }
+}
+
+// 备份文件的信息，无法读取的备份版本和按键数为空
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    pub file_name: String,
+    pub path: String,
+    pub kind: BackupKind,
+    pub created_at: DateTime<Local>,
+    pub size_bytes: u64,
+    pub schema_version: Option<u32>,
+    pub event_count: Option<i64>, // 按键总数（包括已按保留策略清理的原始记录）
+}
+
+fn file_name(kind: BackupKind, time: DateTime<Local>) -> String {
+    format!("{}{}-{}.db", FILE_PREFIX, kind.as_str(), time.format(TIME_FORMAT))
+}
+
+// 从文件名解析备份方式和时间，不是本程序创建的备份时返回 None
+fn parse_file_name(name: &str) -> Option<(BackupKind, DateTime<Local>)> {
+    let stem = name.strip_prefix(FILE_PREFIX)?.strip_suffix(".db")?;
+    let mut parts = stem.rsplitn(3, '-');
+    let time = parts.next()?;
+    let date = parts.next()?;
+    let kind = BackupKind::parse(parts.next()?)?;
+    let time = NaiveDateTime::parse_from_str(&format!("{}-{}", date, time), TIME_FORMAT).ok()?;
+    Some((kind, Local.from_local_datetime(&time).earliest()?))
+}
+
+// 把 from 的全部内容复制到 to。一次复制所有页面，整个过程在同一个读事务中完成，
+// 得到的是某一时刻的完整数据；WAL 模式下其间写连接仍可提交，不会使备份重新开始
+fn copy_database(from: &Connection, to: &mut Connection) -> rusqlite::Result<()> {
+    let backup = Backup::new(from, to)?;
+    let mut retries = 0;
+    loop {
+        match backup.step(-1)? {
+            StepResult::Done => return Ok(()),
+            StepResult::Busy | StepResult::Locked if retries < BUSY_RETRIES => {
+                retries += 1;
+                std::thread::sleep(BUSY_PAUSE);
+            }
+            StepResult::Busy | StepResult::Locked => {
+                return Err(rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+                    Some("数据库被占用".to_string()),
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn open_read_only(path: &Path) -> rusqlite::Result<Connection> {
+    Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+}
+
+// 读取备份的版本和按键总数。按键总数按天汇总统计，没有汇总表的早期版本按原始记录统计
+fn inspect(path: &Path) -> rusqlite::Result<(u32, i64)> {
+    let conn = open_read_only(path)?;
+    let version = schema_version(&conn)?;
+    let sql = if version >= ROLLUP_VERSION {
+        "SELECT COALESCE(SUM(count), 0) FROM rollup_day"
+    } else {
+        "SELECT COUNT(*) FROM keyboard_events"
+    };
+    let count = conn.query_row(sql, [], |row| row.get(0))?;
+    Ok((version, count))
+}
+
+// 把当前数据库备份到 dir，先写入临时文件，完成后再改名，避免留下不完整的备份
+pub fn create_backup(store: &dyn Store, dir: &Path, kind: BackupKind, now: DateTime<Local>) -> Result<BackupInfo, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("创建备份目录失败: {}", e))?;
+    let name = file_name(kind, now);
+    let path = dir.join(&name);
+    if path.exists() {
+        return Err(format!("备份文件 {} 已存在，请稍后再试", name));
+    }
+    let temp_path = dir.join(format!("{}.tmp", name));
+    let _ = fs::remove_file(&temp_path);
+
+    let result = (|| -> rusqlite::Result<()> {
+        let mut dest = Connection::open(&temp_path)?;
+        {
+            let source = store.read()?;
+            copy_database(&source, &mut dest)?;
+        }
+        // 备份文件复制了数据库的 WAL 标记，改回普通模式，使其成为独立的单个文件
+        dest.pragma_update(None, "journal_mode", "DELETE")?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(format!("备份数据库失败: {}", e));
+    }
+    fs::rename(&temp_path, &path).map_err(|e| format!("保存备份文件失败: {}", e))?;
+    println!("已备份数据库: {}", path.display());
+    backup_info(&path, kind, now)
+}
+
+fn backup_info(path: &Path, kind: BackupKind, created_at: DateTime<Local>) -> Result<BackupInfo, String> {
+    let metadata = fs::metadata(path).map_err(|e| format!("读取备份文件失败: {}", e))?;
+    let inspected = inspect(path).ok();
+    Ok(BackupInfo {
+        file_name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+        path: path.to_string_lossy().to_string(),
+        kind,
+        created_at,
+        size_bytes: metadata.len(),
+        schema_version: inspected.map(|(version, _)| version),
+        event_count: inspected.map(|(_, count)| count),
+    })
+}
+
+// 列出 dir 中的备份，最新的在前；目录不存在时返回空列表
+pub fn list_backups(dir: &Path) -> Result<Vec<BackupInfo>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let entries = fs::read_dir(dir).map_err(|e| format!("读取备份目录失败: {}", e))?;
+    let mut backups = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("读取备份目录失败: {}", e))?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some((kind, created_at)) = parse_file_name(&name) else {
+            continue;
+        };
+        backups.push(backup_info(&entry.path(), kind, created_at)?);
+    }
+    backups.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| b.file_name.cmp(&a.file_name)));
+    Ok(backups)
+}
+
+// 检查备份是否完整，并且是本程序可以读取的版本
+fn validate_backup(conn: &Connection) -> Result<u32, String> {
+    let check: String = conn.query_row("PRAGMA quick_check", [], |row| row.get(0))
+        .map_err(|e| format!("备份文件无法读取: {}", e))?;
+    if check != "ok" {
+        return Err(format!("备份文件已损坏: {}", check));
+    }
+    let version = schema_version(conn).map_err(|e| format!("读取备份版本失败: {}", e))?;
+    let has_events: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'keyboard_events')",
+        [],
+        |row| row.get(0),
+    ).map_err(|e| format!("读取备份失败: {}", e))?;
+    if version == 0 || !has_events {
+        return Err("不是按键统计的数据库备份".to_string());
+    }
+    if version > SCHEMA_VERSION {
+        return Err(format!("备份的数据库版本 {} 高于程序支持的版本 {}，请升级程序", version, SCHEMA_VERSION));
+    }
+    Ok(version)
+}
+
+// 用 dir 中名为 file_name 的备份替换当前数据，较早版本的备份恢复后升级到当前版本。
+// 恢复前先备份当前数据，返回这份备份的信息
+pub fn restore_backup(store: &dyn Store, dir: &Path, file_name: &str, now: DateTime<Local>) -> Result<BackupInfo, String> {
+    // 只接受备份目录中的文件名，不能指向其他位置
+    if parse_file_name(file_name).is_none() || Path::new(file_name).file_name() != Some(file_name.as_ref()) {
+        return Err("无效的备份文件名".to_string());
+    }
+    let path = dir.join(file_name);
+    if !path.is_file() {
+        return Err(format!("备份文件 {} 不存在", file_name));
+    }
+    let source = open_read_only(&path).map_err(|e| format!("打开备份文件失败: {}", e))?;
+    let version = validate_backup(&source)?;
+
+    let current = create_backup(store, dir, BackupKind::BeforeRestore, now)?;
+    let mut conn = store.write().map_err(|e| format!("数据库连接失败: {}", e))?;
+    copy_database(&source, &mut conn).map_err(|e| format!("恢复备份失败: {}", e))?;
+    if version < SCHEMA_VERSION {
+        migrate(&mut conn, None).map_err(|e| format!("升级恢复的数据库失败: {}", e))?;
+    }
+    println!("已从备份恢复数据库: {}", path.display());
+    Ok(current)
+}
+
+// 删除超出保留份数的自动备份，返回删除的数量
+pub fn rotate_backups(dir: &Path, keep: u32) -> Result<usize, String> {
+    let expired: Vec<_> = list_backups(dir)?
+        .into_iter()
+        .filter(|backup| backup.kind == BackupKind::Auto)
+        .skip(keep as usize)
+        .collect();
+    for backup in &expired {
+        fs::remove_file(&backup.path).map_err(|e| format!("删除旧备份 {} 失败: {}", backup.file_name, e))?;
+    }
+    Ok(expired.len())
+}
+
+// 距上次自动备份超过设置的间隔时备份一次并轮换，未到时间或未启用时返回 None
+pub fn run_scheduled_backup(store: &dyn Store, dir: &Path, settings: &BackupSettings, now: DateTime<Local>) -> Result<Option<BackupInfo>, String> {
+    if !settings.enabled {
+        return Ok(None);
+    }
+    let last = list_backups(dir)?
+        .into_iter()
+        .find(|backup| backup.kind == BackupKind::Auto)
+        .map(|backup| backup.created_at);
+    // 系统时间被调回时上次备份晚于当前时间，同样视为到期
+    if let Some(last) = last {
+        let elapsed = now - last;
+        if elapsed >= Duration::zero() && elapsed < Duration::hours(settings.interval_hours as i64) {
+            return Ok(None);
+        }
+    }
+    let backup = create_backup(store, dir, BackupKind::Auto, now)?;
+    rotate_backups(dir, settings.keep)?;
+    Ok(Some(backup))
+}
+
+// 备份文件所在的目录
+pub fn backup_dir(app_dir: &Path) -> PathBuf {
+    app_dir.join("backups")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{get_total_key_count, insert_event};
+    use crate::store::{MemoryStore, SqliteStore};
+    use crate::test_support::event;
+
+    // 与写入线程一样，每条记录和它的统计在同一个事务中写入
+    fn insert(store: &dyn Store, count: usize) {
+        let mut conn = store.write().unwrap();
+        for _ in 0..count {
+            let tx = conn.transaction().unwrap();
+            insert_event(&tx, &event(Local::now(), "A", "Code.exe")).unwrap();
+            tx.commit().unwrap();
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("keyboard-statistics-backup-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn time(hour: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 3, 1, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_backup_file_names() {
+        let name = file_name(BackupKind::BeforeRestore, time(4));
+        assert_eq!(name, "keyboard_events-before-restore-20240301-040000.db");
+        assert_eq!(parse_file_name(&name), Some((BackupKind::BeforeRestore, time(4))));
+        assert_eq!(parse_file_name("keyboard_events.db.v3.bak"), None);
+        assert_eq!(parse_file_name("keyboard_events-auto-20240301-040000.db.tmp"), None);
+    }
+
+    #[test]
+    fn backs_up_while_recording_and_restores() {
+        let dir = temp_dir("restore");
+        let store = SqliteStore::open(&dir.join("keyboard_events.db")).unwrap();
+        insert(&store, 3);
+
+        // 备份期间写入线程持续写入
+        let backup = std::thread::scope(|scope| {
+            let writer = scope.spawn(|| insert(&store, 200));
+            let backup = create_backup(&store, &dir.join("backups"), BackupKind::Manual, time(1)).unwrap();
+            writer.join().unwrap();
+            backup
+        });
+        let backed_up = backup.event_count.unwrap();
+        assert!((3..=203).contains(&backed_up));
+        assert_eq!(backup.schema_version, Some(SCHEMA_VERSION));
+        assert!(!dir.join("backups").join(format!("{}.tmp", backup.file_name)).exists());
+
+        let current = restore_backup(&store, &dir.join("backups"), &backup.file_name, time(2)).unwrap();
+        assert_eq!(current.kind, BackupKind::BeforeRestore);
+        assert_eq!(current.event_count, Some(203));
+        assert_eq!(get_total_key_count(&store.read().unwrap()).unwrap(), backed_up);
+        let mode: String = store.read().unwrap().query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+        assert_eq!(mode, "wal");
+        // 恢复后继续记录
+        insert(&store, 1);
+        assert_eq!(get_total_key_count(&store.read().unwrap()).unwrap(), backed_up + 1);
+
+        let backups = list_backups(&dir.join("backups")).unwrap();
+        assert_eq!(backups.iter().map(|b| b.kind).collect::<Vec<_>>(), [BackupKind::BeforeRestore, BackupKind::Manual]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_invalid_backups() {
+        let dir = temp_dir("invalid");
+        let store = MemoryStore::new().unwrap();
+        insert(&store, 1);
+
+        assert!(restore_backup(&store, &dir, "../keyboard_events.db", time(1)).is_err());
+        assert!(restore_backup(&store, &dir, "keyboard_events-manual-20240301-000000.db", time(1)).is_err());
+
+        // 不是数据库的文件
+        let name = file_name(BackupKind::Manual, time(2));
+        fs::write(dir.join(&name), "not a database").unwrap();
+        assert!(restore_backup(&store, &dir, &name, time(3)).is_err());
+
+        // 更新版本程序创建的备份
+        let newer = create_backup(&store, &dir, BackupKind::Manual, time(4)).unwrap();
+        Connection::open(&newer.path).unwrap().pragma_update(None, "user_version", SCHEMA_VERSION + 1).unwrap();
+        let err = restore_backup(&store, &dir, &newer.file_name, time(5)).unwrap_err();
+        assert!(err.contains("高于程序支持的版本"));
+
+        // 校验失败时不修改当前数据，也不创建恢复前的备份
+        assert_eq!(get_total_key_count(&store.read().unwrap()).unwrap(), 1);
+        assert!(list_backups(&dir).unwrap().iter().all(|b| b.kind == BackupKind::Manual));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rotates_scheduled_backups() {
+        let dir = temp_dir("rotate");
+        let store = MemoryStore::new().unwrap();
+        let settings = BackupSettings { enabled: true, interval_hours: 6, keep: 2 };
+        create_backup(&store, &dir, BackupKind::Manual, time(0)).unwrap();
+
+        let mut created = Vec::new();
+        for hour in 0..24 {
+            if let Some(backup) = run_scheduled_backup(&store, &dir, &settings, time(hour)).unwrap() {
+                created.push(backup.created_at);
+            }
+        }
+        assert_eq!(created, [time(0), time(6), time(12), time(18)]);
+
+        // 只轮换自动备份，手动备份保留
+        let backups = list_backups(&dir).unwrap();
+        let kept: Vec<_> = backups.iter().map(|b| (b.kind, b.created_at)).collect();
+        assert_eq!(kept, [
+            (BackupKind::Auto, time(18)),
+            (BackupKind::Auto, time(12)),
+            (BackupKind::Manual, time(0)),
+        ]);
+
+        let disabled = BackupSettings { enabled: false, ..settings };
+        assert!(run_scheduled_backup(&store, &dir, &disabled, time(23) + Duration::days(1)).unwrap().is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,353 @@
+use crate::database::{InputDeviceRecord, KeyboardEventRecord, MASKED_KEY_CODE};
+use crate::keyboard::KeyboardEvent;
+use crate::writer::{EventWriter, WriteRecord};
+use crate::mouse::MouseEvent;
+use crate::privacy::{is_private_key, CharacterBucket};
+use chrono::{DateTime, Duration, Local};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+use tauri::{AppHandle, Emitter};
+
+pub const DATABASE_SUBSCRIBER: &str = "database";
+pub const KEY_POPUP_SUBSCRIBER: &str = "key_popup";
+pub const KPM_SUBSCRIBER: &str = "kpm";
+pub const IDLE_SUBSCRIBER: &str = "idle";
+pub const MOUSE_SUBSCRIBER: &str = "mouse";
+
+// 总线线程发布 Tick 的间隔
+const TICK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+// 总线上的事件
+#[derive(Debug, Clone)]
+pub enum BusEvent {
+    // 按键按下时发布，用于实时展示
+    KeyPressed(KeyboardEvent),
+    // 按键松开（或判定丢失松开事件）后发布，记录已包含按住时长等信息
+    RecordReady(KeyboardEventRecord),
+    // 鼠标点击、移动或滚动，仅在开启鼠标统计时发布
+    Mouse(MouseEvent),
+    // 本次监听中首次收到某个键盘设备的按键
+    DeviceSeen(InputDeviceRecord),
+    // 要求带缓冲的消费者立即交出数据，在退出或等待落盘前发布
+    Flush,
+    // 总线线程定时发布，没有输入时带缓冲的消费者也能按时间交出数据
+    Tick(DateTime<Local>),
+}
+
+// 订阅者在总线线程中按发布顺序依次调用，不会阻塞键盘钩子
+pub trait Subscriber: Send {
+    fn handle(&mut self, event: &BusEvent);
+}
+
+struct SubscriberEntry {
+    name: String,
+    enabled: bool,
+    subscriber: Box<dyn Subscriber>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriberInfo {
+    pub name: String,
+    pub enabled: bool,
+}
+
+enum BusMessage {
+    Event(BusEvent),
+    // 之前发布的事件都处理完后通知
+    Sync(Sender<()>),
+}
+
+// 进程内事件总线：键盘钩子只负责把事件放入队列，由总线线程分发给各消费者，
+// 消费者独立订阅并可单独启用/停用
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<SubscriberEntry>>>,
+    tx: Sender<BusMessage>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    // 启动总线线程，EventBus 被释放后线程处理完剩余事件并退出
+    pub fn new() -> Self {
+        let subscribers: Arc<Mutex<Vec<SubscriberEntry>>> = Arc::new(Mutex::new(Vec::new()));
+        let (tx, rx) = channel();
+        let thread_subscribers = subscribers.clone();
+        thread::spawn(move || {
+            let mut last_tick = Instant::now();
+            loop {
+                match rx.recv_timeout(TICK_INTERVAL.saturating_sub(last_tick.elapsed())) {
+                    Ok(BusMessage::Event(event)) => dispatch(&thread_subscribers, &event),
+                    Ok(BusMessage::Sync(done)) => {
+                        let _ = done.send(());
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+                if last_tick.elapsed() >= TICK_INTERVAL {
+                    dispatch(&thread_subscribers, &BusEvent::Tick(Local::now()));
+                    last_tick = Instant::now();
+                }
+            }
+        });
+        EventBus { subscribers, tx }
+    }
+
+    // 订阅事件，同名订阅者会被替换
+    pub fn subscribe(&self, name: &str, subscriber: Box<dyn Subscriber>) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|entry| entry.name != name);
+        subscribers.push(SubscriberEntry {
+            name: name.to_string(),
+            enabled: true,
+            subscriber,
+        });
+    }
+
+    // 启用或停用订阅者，订阅者不存在时返回 false
+    pub fn set_enabled(&self, name: &str, enabled: bool) -> bool {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        match subscribers.iter_mut().find(|entry| entry.name == name) {
+            Some(entry) => {
+                entry.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn subscribers(&self) -> Vec<SubscriberInfo> {
+        self.subscribers.lock().unwrap().iter()
+            .map(|entry| SubscriberInfo {
+                name: entry.name.clone(),
+                enabled: entry.enabled,
+            })
+            .collect()
+    }
+
+    // 放入队列后立即返回，订阅者稍后在总线线程中处理
+    pub fn publish(&self, event: BusEvent) {
+        let _ = self.tx.send(BusMessage::Event(event));
+    }
+
+    // 发布 Flush 并等待包括它在内的所有已发布事件处理完成
+    pub fn flush(&self) {
+        self.publish(BusEvent::Flush);
+        let (done_tx, done_rx) = channel();
+        if self.tx.send(BusMessage::Sync(done_tx)).is_ok() {
+            let _ = done_rx.recv();
+        }
+    }
+}
+
+fn dispatch(subscribers: &Mutex<Vec<SubscriberEntry>>, event: &BusEvent) {
+    let mut subscribers = subscribers.lock().unwrap();
+    for entry in subscribers.iter_mut().filter(|entry| entry.enabled) {
+        entry.subscriber.handle(event);
+    }
+}
+
+// 数据库订阅者：把完成的记录交给写入线程，隐私模式下字符键等按键先按分钟缓冲。
+// 只记录次数的应用中的按键始终按分钟保存，不论是否开启隐私模式
+pub struct DatabaseSubscriber {
+    writer: EventWriter,
+    privacy_mode: Arc<AtomicBool>,
+    bucket: CharacterBucket,
+    masked: CharacterBucket,
+}
+
+impl DatabaseSubscriber {
+    pub fn new(writer: EventWriter, privacy_mode: Arc<AtomicBool>) -> Self {
+        DatabaseSubscriber {
+            writer,
+            privacy_mode,
+            bucket: CharacterBucket::new(),
+            masked: CharacterBucket::new(),
+        }
+    }
+
+    fn submit_all(&self, records: Vec<KeyboardEventRecord>) {
+        for record in records {
+            self.writer.submit(record);
+        }
+    }
+}
+
+impl Subscriber for DatabaseSubscriber {
+    fn handle(&mut self, event: &BusEvent) {
+        match event {
+            // 进入新的一分钟后释放上一分钟的记录，隐私模式关闭后立即全部释放
+            BusEvent::Tick(now) => {
+                let released = if self.privacy_mode.load(Ordering::Relaxed) {
+                    self.bucket.release_before(*now)
+                } else {
+                    self.bucket.drain()
+                };
+                self.submit_all(released);
+                let released = self.masked.release_before(*now);
+                self.submit_all(released);
+            }
+            BusEvent::RecordReady(record) => {
+                if record.key_code == MASKED_KEY_CODE {
+                    let released = self.masked.add(record.clone());
+                    self.submit_all(released);
+                } else if self.privacy_mode.load(Ordering::Relaxed) && is_private_key(&record.key_code) {
+                    let released = self.bucket.add(record.clone());
+                    self.submit_all(released);
+                } else {
+                    self.writer.submit(record.clone());
+                }
+            }
+            BusEvent::Flush => {
+                let released = self.bucket.drain();
+                self.submit_all(released);
+                let released = self.masked.drain();
+                self.submit_all(released);
+            }
+            BusEvent::DeviceSeen(device) => {
+                self.writer.submit_record(WriteRecord::Device(device.clone()));
+            }
+            BusEvent::KeyPressed(_) | BusEvent::Mouse(_) => {}
+        }
+    }
+}
+
+// 用于发送到前端的事件载荷
+#[derive(Clone, Serialize)]
+struct KeyPressedPayload {
+    key_code: String,
+    chord: crate::combo::Chord,
+}
+
+// 按键弹窗订阅者：按下时通知 key_popup 窗口
+pub struct KeyPopupSubscriber {
+    app_handle: AppHandle,
+}
+
+impl KeyPopupSubscriber {
+    pub fn new(app_handle: AppHandle) -> Self {
+        KeyPopupSubscriber { app_handle }
+    }
+}
+
+impl Subscriber for KeyPopupSubscriber {
+    fn handle(&mut self, event: &BusEvent) {
+        if let BusEvent::KeyPressed(event) = event {
+            // 脱敏的按键不在屏幕上显示
+            if event.key_code == MASKED_KEY_CODE {
+                return;
+            }
+            let _ = self.app_handle.emit_to("key_popup", "key-pressed", KeyPressedPayload {
+                key_code: event.key_code.clone(),
+                chord: event.chord.clone(),
+            });
+        }
+    }
+}
+
+// 实时KPM：统计最近60秒内的按键次数，无需查询数据库
+#[derive(Clone, Default)]
+pub struct KpmTracker {
+    presses: Arc<Mutex<VecDeque<DateTime<Local>>>>,
+}
+
+impl KpmTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn current_kpm(&self) -> f64 {
+        let mut presses = self.presses.lock().unwrap();
+        Self::prune(&mut presses, Local::now());
+        presses.len() as f64
+    }
+
+    fn prune(presses: &mut VecDeque<DateTime<Local>>, now: DateTime<Local>) {
+        let window_start = now - Duration::seconds(60);
+        while presses.front().is_some_and(|t| *t < window_start) {
+            presses.pop_front();
+        }
+    }
+}
+
+impl Subscriber for KpmTracker {
+    fn handle(&mut self, event: &BusEvent) {
+        if let BusEvent::KeyPressed(event) = event {
+            let mut presses = self.presses.lock().unwrap();
+            presses.push_back(event.timestamp);
+            Self::prune(&mut presses, event.timestamp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::query_events_by_time_range;
+    use crate::store::{MemoryStore, Store};
+    use crate::test_support::event;
+    use chrono::{Duration, TimeZone};
+
+    // 记录收到事件时所在的线程
+    struct Recorder {
+        threads: Arc<Mutex<Vec<thread::ThreadId>>>,
+    }
+
+    impl Subscriber for Recorder {
+        fn handle(&mut self, event: &BusEvent) {
+            if let BusEvent::Flush = event {
+                self.threads.lock().unwrap().push(thread::current().id());
+            }
+        }
+    }
+
+    #[test]
+    fn dispatches_events_on_the_bus_thread() {
+        let bus = EventBus::new();
+        let threads = Arc::new(Mutex::new(Vec::new()));
+        bus.subscribe("recorder", Box::new(Recorder { threads: threads.clone() }));
+
+        bus.publish(BusEvent::Flush);
+        bus.publish(BusEvent::Flush);
+        // flush 返回时之前发布的事件都已处理
+        bus.flush();
+
+        let threads = threads.lock().unwrap();
+        assert_eq!(threads.len(), 3);
+        assert!(threads.iter().all(|id| *id != thread::current().id()));
+    }
+
+    #[test]
+    fn stores_masked_keys_per_minute() {
+        let store = Arc::new(MemoryStore::new().unwrap());
+        let writer = EventWriter::spawn(store.clone());
+        let mut subscriber = DatabaseSubscriber::new(writer.clone(), Arc::new(AtomicBool::new(false)));
+        let minute = Local.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+        let written = || {
+            writer.flush();
+            query_events_by_time_range(&store.read().unwrap(), minute, minute + Duration::minutes(2)).unwrap()
+        };
+
+        let mut masked = event(minute + Duration::milliseconds(5_123), MASKED_KEY_CODE, "keepass.exe");
+        masked.hold_ms = Some(80);
+        subscriber.handle(&BusEvent::RecordReady(masked));
+        subscriber.handle(&BusEvent::RecordReady(event(minute + Duration::seconds(40), MASKED_KEY_CODE, "keepass.exe")));
+        subscriber.handle(&BusEvent::RecordReady(event(minute + Duration::seconds(41), "Ctrl+C", "Code.exe")));
+        // 其他按键直接写入，脱敏的按键等到这一分钟结束
+        subscriber.handle(&BusEvent::Tick(minute + Duration::seconds(50)));
+        assert_eq!(written().len(), 1);
+
+        subscriber.handle(&BusEvent::Tick(minute + Duration::minutes(1)));
+        let masked: Vec<_> = written().into_iter().filter(|record| record.key_code == MASKED_KEY_CODE).collect();
+        assert_eq!(masked.len(), 2);
+        assert!(masked.iter().all(|record| record.timestamp == minute && record.hold_ms.is_none()));
+    }
+}
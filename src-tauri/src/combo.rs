@@ -0,0 +1,248 @@
+use rdev::Key;
+use chrono::{DateTime, Local};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+// 超过该时长仍处于按下状态的按键视为丢失了松开事件（秒）
+const STALE_KEY_SECS: i64 = 30;
+
+// 按下的物理按键：产生按键的设备（输入源无法区分设备时为空）和按键，
+// 多个键盘同时按下同一个键时分别跟踪
+pub type DeviceKey = (Option<String>, Key);
+
+// 修饰键，声明顺序即组合键中的规范顺序：Ctrl+Alt+Shift+Win
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Modifier {
+    Ctrl,
+    Alt,
+    Shift,
+    Win,
+}
+
+impl Modifier {
+    pub fn from_key(key: &Key) -> Option<Self> {
+        match key {
+            Key::ControlLeft | Key::ControlRight => Some(Modifier::Ctrl),
+            Key::Alt | Key::AltGr => Some(Modifier::Alt),
+            Key::ShiftLeft | Key::ShiftRight => Some(Modifier::Shift),
+            Key::MetaLeft | Key::MetaRight => Some(Modifier::Win),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Modifier::Ctrl => "Ctrl",
+            Modifier::Alt => "Alt",
+            Modifier::Shift => "Shift",
+            Modifier::Win => "Win",
+        }
+    }
+}
+
+// 一次按键产生的组合：按规范顺序排列的修饰键 + 基础键
+// 单独按下修饰键时 key 为空，例如先按 Ctrl 再按 Shift 得到 modifiers=[Ctrl, Shift]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chord {
+    pub modifiers: Vec<Modifier>,
+    pub key: Option<String>,
+}
+
+impl Chord {
+    // 组合键的显示名称，例如 "Ctrl+Shift+A"
+    pub fn label(&self) -> String {
+        let mut parts: Vec<&str> = self.modifiers.iter().map(|m| m.label()).collect();
+        if let Some(key) = &self.key {
+            parts.push(key);
+        }
+        parts.join("+")
+    }
+
+    pub fn is_combo(&self) -> bool {
+        self.modifiers.len() + usize::from(self.key.is_some()) > 1
+    }
+}
+
+// 组合键检测器：跟踪当前按下的物理按键，生成规范化的组合键；
+// 任一键盘上按住的修饰键都对所有键盘生效
+#[derive(Default)]
+pub struct ComboDetector {
+    held_modifiers: HashMap<DeviceKey, (Modifier, DateTime<Local>)>,
+    held_keys: HashMap<DeviceKey, DateTime<Local>>,
+}
+
+impl ComboDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 处理按下事件；系统自动重复的按下返回 None
+    pub fn press(&mut self, key: DeviceKey, label: &str, at: DateTime<Local>) -> Option<Chord> {
+        self.expire_stale(at);
+
+        if let Some(modifier) = Modifier::from_key(&key.1) {
+            if self.held_modifiers.contains_key(&key) {
+                return None;
+            }
+            self.held_modifiers.insert(key, (modifier, at));
+            return Some(Chord {
+                modifiers: self.active_modifiers(),
+                key: None,
+            });
+        }
+
+        if self.held_keys.contains_key(&key) {
+            return None;
+        }
+        self.held_keys.insert(key, at);
+        Some(Chord {
+            modifiers: self.active_modifiers(),
+            key: Some(label.to_string()),
+        })
+    }
+
+    // 处理松开事件；未记录按下的按键直接忽略
+    pub fn release(&mut self, key: &DeviceKey) {
+        self.held_modifiers.remove(key);
+        self.held_keys.remove(key);
+    }
+
+    // 清空按键状态，在监听重启或恢复录制时调用，避免沿用失效的按下状态
+    pub fn reset(&mut self) {
+        self.held_modifiers.clear();
+        self.held_keys.clear();
+    }
+
+    fn active_modifiers(&self) -> Vec<Modifier> {
+        let mut modifiers: Vec<Modifier> = self.held_modifiers.values().map(|(m, _)| *m).collect();
+        modifiers.sort();
+        modifiers.dedup();
+        modifiers
+    }
+
+    // 丢弃长时间未松开的按键（如焦点切换到安全桌面后松开事件丢失）
+    fn expire_stale(&mut self, now: DateTime<Local>) {
+        let is_fresh = |pressed_at: &DateTime<Local>| {
+            now.signed_duration_since(*pressed_at).num_seconds() < STALE_KEY_SECS
+        };
+        self.held_modifiers.retain(|_, (_, pressed_at)| is_fresh(pressed_at));
+        self.held_keys.retain(|_, pressed_at| is_fresh(pressed_at));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    enum Step {
+        Press(Key),
+        Release(Key),
+        PressOn(&'static str, Key), // 指定设备
+        ReleaseOn(&'static str, Key),
+        Wait(i64), // 秒
+        Reset,
+    }
+    use Step::*;
+
+    // (用例名称, 操作步骤, 期望的组合键序列)
+    type Case = (&'static str, Vec<Step>, Vec<Option<String>>);
+
+    fn label_of(key: &Key) -> String {
+        format!("{:?}", key).trim_start_matches("Key").to_string()
+    }
+
+    // 依次执行步骤，返回每次按下产生的组合键名称（自动重复记为 None）
+    fn run(steps: &[Step]) -> Vec<Option<String>> {
+        let mut detector = ComboDetector::new();
+        let mut now = Local::now();
+        let mut labels = Vec::new();
+        for step in steps {
+            now += Duration::milliseconds(10);
+            match step {
+                Press(key) => labels.push(detector.press((None, *key), &label_of(key), now).map(|c| c.label())),
+                Release(key) => detector.release(&(None, *key)),
+                PressOn(device, key) => {
+                    let device_key = (Some(device.to_string()), *key);
+                    labels.push(detector.press(device_key, &label_of(key), now).map(|c| c.label()));
+                }
+                ReleaseOn(device, key) => detector.release(&(Some(device.to_string()), *key)),
+                Wait(secs) => now += Duration::seconds(*secs),
+                Reset => detector.reset(),
+            }
+        }
+        labels
+    }
+
+    #[test]
+    fn detects_combos_for_tricky_sequences() {
+        let s = |label: &str| Some(label.to_string());
+        let cases: Vec<Case> = vec![
+            ("单键", vec![Press(Key::KeyA), Release(Key::KeyA)], vec![s("A")]),
+            ("Ctrl+C",
+                vec![Press(Key::ControlLeft), Press(Key::KeyC), Release(Key::KeyC), Release(Key::ControlLeft)],
+                vec![s("Ctrl"), s("Ctrl+C")]),
+            ("先Shift后A与先A后Shift不同",
+                vec![Press(Key::KeyA), Press(Key::ShiftLeft), Release(Key::KeyA), Release(Key::ShiftLeft)],
+                vec![s("A"), s("Shift")]),
+            ("修饰键按规范顺序排列",
+                vec![Press(Key::ShiftLeft), Press(Key::Alt), Press(Key::ControlRight), Press(Key::KeyT)],
+                vec![s("Shift"), s("Alt+Shift"), s("Ctrl+Alt+Shift"), s("Ctrl+Alt+Shift+T")]),
+            ("Tab不是修饰键",
+                vec![Press(Key::Tab), Press(Key::KeyA), Release(Key::KeyA), Release(Key::Tab)],
+                vec![s("Tab"), s("A")]),
+            ("Alt+Tab连续切换",
+                vec![Press(Key::Alt), Press(Key::Tab), Release(Key::Tab), Press(Key::Tab), Release(Key::Tab), Release(Key::Alt)],
+                vec![s("Alt"), s("Alt+Tab"), s("Alt+Tab")]),
+            ("自动重复不产生新事件",
+                vec![Press(Key::Backspace), Press(Key::Backspace), Press(Key::Backspace), Release(Key::Backspace), Press(Key::Backspace)],
+                vec![s("Backspace"), None, None, s("Backspace")]),
+            ("左右Shift同时按下只算一个修饰键",
+                vec![Press(Key::ShiftLeft), Press(Key::ShiftRight), Press(Key::KeyA), Release(Key::ShiftLeft), Press(Key::KeyB)],
+                vec![s("Shift"), s("Shift"), s("Shift+A"), s("Shift+B")]),
+            ("按住普通键时按下其他键不组合",
+                vec![Press(Key::KeyA), Press(Key::KeyB)],
+                vec![s("A"), s("B")]),
+            ("修饰键松开后不再组合",
+                vec![Press(Key::ControlLeft), Release(Key::ControlLeft), Press(Key::KeyV)],
+                vec![s("Ctrl"), s("V")]),
+            ("未记录按下的松开事件被忽略",
+                vec![Release(Key::ControlLeft), Press(Key::KeyV)],
+                vec![s("V")]),
+            ("丢失松开事件的修饰键超时失效",
+                vec![Press(Key::MetaLeft), Press(Key::KeyL), Wait(STALE_KEY_SECS), Press(Key::KeyA)],
+                vec![s("Win"), s("Win+L"), s("A")]),
+            ("重置后清除残留状态",
+                vec![Press(Key::ControlLeft), Reset, Press(Key::KeyS), Press(Key::ControlLeft)],
+                vec![s("Ctrl"), s("S"), s("Ctrl")]),
+            ("两个键盘按下同一个键不算自动重复",
+                vec![PressOn("kb1", Key::KeyA), PressOn("kb2", Key::KeyA), PressOn("kb1", Key::KeyA)],
+                vec![s("A"), s("A"), None]),
+            ("松开一个键盘的Shift不影响另一个键盘",
+                vec![PressOn("kb1", Key::ShiftLeft), PressOn("kb2", Key::ShiftLeft), ReleaseOn("kb1", Key::ShiftLeft), PressOn("kb2", Key::KeyA)],
+                vec![s("Shift"), s("Shift"), s("Shift+A")]),
+            ("修饰键对所有键盘生效",
+                vec![PressOn("kb1", Key::ControlLeft), PressOn("kb2", Key::KeyC), ReleaseOn("kb2", Key::ControlLeft), PressOn("kb2", Key::KeyV)],
+                vec![s("Ctrl"), s("Ctrl+C"), s("Ctrl+V")]),
+        ];
+
+        for (name, steps, expected) in cases {
+            assert_eq!(run(&steps), expected, "用例: {}", name);
+        }
+    }
+
+    #[test]
+    fn chord_exposes_modifiers_and_base_key() {
+        let mut detector = ComboDetector::new();
+        let now = Local::now();
+        detector.press((None, Key::ShiftLeft), "Shift", now);
+        let chord = detector.press((None, Key::KeyZ), "Z", now).unwrap();
+        assert_eq!(chord.modifiers, vec![Modifier::Shift]);
+        assert_eq!(chord.key.as_deref(), Some("Z"));
+        assert!(chord.is_combo());
+
+        let chord = detector.press((None, Key::ControlLeft), "Ctrl", now).unwrap();
+        assert_eq!(chord.modifiers, vec![Modifier::Ctrl, Modifier::Shift]);
+        assert_eq!(chord.key, None);
+    }
+}
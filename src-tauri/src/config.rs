@@ -3,6 +3,13 @@ use std::fs::File;
 use std::io::{Read, Write};
 use std::path::PathBuf;
 use std::sync::Mutex;
+use crate::policy::AppPolicy;
+use keyboard_statistics_lib::apps::AppRule;
+use crate::idle::DEFAULT_IDLE_TIMEOUT_SECS;
+use crate::input::DEFAULT_INPUT_BACKEND;
+use crate::hotkey::HotkeySettings;
+use crate::retention::RetentionSettings;
+use crate::backup::BackupSettings;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AppConfig {
@@ -11,6 +18,32 @@ pub struct AppConfig {
     pub recording_enabled: bool,
     pub autostart_enabled: bool,
     pub popup_position: PopupPosition,
+    #[serde(default)]
+    pub app_policies: Vec<AppPolicy>, // 按应用的录制策略，按顺序匹配
+    #[serde(default)]
+    pub app_rules: Vec<AppRule>, // 用户自定义的应用显示名称和分类，优先于内置规则
+    #[serde(default)]
+    pub privacy_mode: bool, // 隐私模式：字符键按分钟保存，无法还原输入内容
+    #[serde(default = "default_idle_timeout_secs")]
+    pub idle_timeout_secs: u64, // 无输入超过该时长视为离开（秒）
+    #[serde(default)]
+    pub mouse_enabled: bool, // 是否统计鼠标，默认关闭
+    #[serde(default = "default_input_backend")]
+    pub input_backend: String, // 输入源：auto、rdev 或 evdev（仅 Linux）
+    #[serde(default)]
+    pub hotkeys: HotkeySettings, // 暂停/恢复和临时暂停的全局快捷键
+    #[serde(default)]
+    pub retention: RetentionSettings, // 数据保留策略，默认永久保留
+    #[serde(default)]
+    pub backup: BackupSettings, // 自动备份设置，默认不启用
+}
+
+fn default_idle_timeout_secs() -> u64 {
+    DEFAULT_IDLE_TIMEOUT_SECS
+}
+
+fn default_input_backend() -> String {
+    DEFAULT_INPUT_BACKEND.to_string()
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -38,6 +71,15 @@ impl Default for AppConfig {
             recording_enabled: true,
             autostart_enabled: false,
             popup_position: PopupPosition::default(),
+            app_policies: Vec::new(),
+            app_rules: Vec::new(),
+            privacy_mode: false,
+            idle_timeout_secs: DEFAULT_IDLE_TIMEOUT_SECS,
+            mouse_enabled: false,
+            input_backend: DEFAULT_INPUT_BACKEND.to_string(),
+            hotkeys: HotkeySettings::default(),
+            retention: RetentionSettings::default(),
+            backup: BackupSettings::default(),
         }
     }
 }
@@ -83,7 +125,7 @@ impl ConfigManager {
             .map_err(|e| format!("写入配置文件失败: {}", e))
     }
 
-    pub fn get_config(&self) -> std::sync::MutexGuard<AppConfig> {
+    pub fn get_config(&self) -> std::sync::MutexGuard<'_, AppConfig> {
         self.config.lock().unwrap()
     }
 }
\ No newline at end of file
@@ -0,0 +1,318 @@
+// 外部上下文标签：编辑器插件、shell 钩子等通过本地套接字（Windows 上为命名管道）告知当前的项目、
+// 分支或任务，之后记录的按键都带上该标签，用于按项目统计按键
+//
+// 协议：每行一条消息，内容即标签，例如 "project:crate"；空行清除标签。
+// 标签超过 CONTEXT_TTL 未再次告知即失效，避免编辑器关闭后一直沿用旧标签
+use serde::{Serialize, Deserialize};
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Unix 套接字所在目录（位于应用数据目录下，仅当前用户可访问）及文件名
+#[cfg(unix)]
+pub const CONTEXT_SOCKET_DIR: &str = "context";
+#[cfg(unix)]
+pub const CONTEXT_SOCKET_NAME: &str = "context.sock";
+#[cfg(windows)]
+pub const CONTEXT_PIPE_NAME: &str = r"\\.\pipe\keyboard-statistics-context";
+
+pub const CONTEXT_TTL: Duration = Duration::from_secs(30 * 60);
+// 标签最大长度（字符），超出部分截断
+const MAX_TAG_CHARS: usize = 128;
+// 单条消息的最大字节数，超长的连接直接断开
+const MAX_LINE_BYTES: u64 = 4096;
+// 同时处理的连接数上限，超出的连接直接关闭
+const MAX_CLIENTS: usize = 8;
+
+struct ActiveContext {
+    tag: String,
+    expires_at: Instant,
+}
+
+// 当前上下文标签，由套接字线程写入、监听线程读取
+#[derive(Clone, Default)]
+pub struct ContextTracker {
+    current: Arc<Mutex<Option<ActiveContext>>>,
+}
+
+impl ContextTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 设置标签，空标签清除
+    pub fn set(&self, tag: &str) {
+        self.set_at(tag, Instant::now());
+    }
+
+    // 当前有效的标签，已过期时返回空
+    pub fn current(&self) -> Option<String> {
+        self.current_at(Instant::now())
+    }
+
+    fn set_at(&self, tag: &str, now: Instant) {
+        let tag: String = tag.trim().chars().take(MAX_TAG_CHARS).collect();
+        let mut current = self.current.lock().unwrap();
+        if tag.is_empty() {
+            *current = None;
+        } else {
+            *current = Some(ActiveContext { tag, expires_at: now + CONTEXT_TTL });
+        }
+    }
+
+    fn current_at(&self, now: Instant) -> Option<String> {
+        let mut current = self.current.lock().unwrap();
+        match current.as_ref() {
+            Some(context) if context.expires_at > now => Some(context.tag.clone()),
+            Some(_) => {
+                *current = None;
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+// 上下文接口的状态，供界面显示接入方式
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextStatus {
+    pub endpoint: String,    // 套接字路径或命名管道名
+    pub tag: Option<String>, // 当前有效的标签
+}
+
+// 逐行读取一个连接发来的消息，连接可以保持打开持续发送
+fn handle_client<R: Read>(stream: R, tracker: &ContextTracker) {
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut line = String::new();
+        match (&mut reader).take(MAX_LINE_BYTES).read_line(&mut line) {
+            Ok(0) => return,
+            Ok(_) if !line.ends_with('\n') && line.len() as u64 >= MAX_LINE_BYTES => {
+                println!("上下文消息过长，断开连接");
+                return;
+            }
+            Ok(_) => tracker.set(&line),
+            Err(e) => {
+                println!("读取上下文消息失败: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+// 正在处理的连接数，占用的名额在连接结束时归还
+#[derive(Clone, Default)]
+struct ClientSlots {
+    active: Arc<AtomicUsize>,
+}
+
+struct ClientSlot {
+    active: Arc<AtomicUsize>,
+}
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl ClientSlots {
+    fn acquire(&self) -> Option<ClientSlot> {
+        if self.active.fetch_add(1, Ordering::SeqCst) >= MAX_CLIENTS {
+            self.active.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        Some(ClientSlot { active: Arc::clone(&self.active) })
+    }
+
+    // 在新线程中处理连接，连接数已达上限时直接关闭该连接
+    fn spawn<R: Read + Send + 'static>(&self, stream: R, tracker: &ContextTracker) {
+        let Some(slot) = self.acquire() else {
+            println!("上下文连接过多，拒绝新的连接");
+            return;
+        };
+        let tracker = tracker.clone();
+        thread::spawn(move || {
+            let _slot = slot;
+            handle_client(stream, &tracker);
+        });
+    }
+}
+
+// 在后台线程中启动上下文接口，返回接入地址
+pub fn start_server(app_dir: &Path, tracker: ContextTracker) -> Result<String, String> {
+    platform::start_server(app_dir, tracker)
+}
+
+#[cfg(unix)]
+mod platform {
+    use super::{ClientSlots, ContextTracker, CONTEXT_SOCKET_DIR, CONTEXT_SOCKET_NAME};
+    use std::fs;
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use std::os::unix::net::UnixListener;
+    use std::path::Path;
+    use std::thread;
+
+    pub fn start_server(app_dir: &Path, tracker: ContextTracker) -> Result<String, String> {
+        // 套接字放在仅当前用户可访问的目录中，绑定到设置权限之间不会被其他用户连接
+        let dir = app_dir.join(CONTEXT_SOCKET_DIR);
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&dir)
+            .map_err(|e| format!("创建上下文套接字目录失败: {}", e))?;
+        // 目录已存在时 mode 不生效，需要再设置一次
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))
+            .map_err(|e| format!("设置上下文套接字目录权限失败: {}", e))?;
+        let path = dir.join(CONTEXT_SOCKET_NAME);
+        // 上次退出时遗留的套接字文件会导致绑定失败
+        if path.exists() {
+            fs::remove_file(&path).map_err(|e| format!("删除旧的上下文套接字失败: {}", e))?;
+        }
+        let listener = UnixListener::bind(&path).map_err(|e| format!("创建上下文套接字失败: {}", e))?;
+        // 只允许当前用户写入
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("设置上下文套接字权限失败: {}", e))?;
+        let slots = ClientSlots::default();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => slots.spawn(stream, &tracker),
+                    Err(e) => println!("接受上下文连接失败: {}", e),
+                }
+            }
+        });
+        Ok(path.display().to_string())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{ClientSlots, ContextTracker, CONTEXT_PIPE_NAME};
+    use std::fs::File;
+    use std::os::windows::io::FromRawHandle;
+    use std::path::Path;
+    use std::thread;
+    use windows::core::HSTRING;
+    use windows::Win32::Foundation::{CloseHandle, GetLastError, ERROR_PIPE_CONNECTED};
+    use windows::Win32::Storage::FileSystem::PIPE_ACCESS_INBOUND;
+    use windows::Win32::System::Pipes::{
+        ConnectNamedPipe, CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE,
+        PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+    };
+
+    pub fn start_server(_app_dir: &Path, tracker: ContextTracker) -> Result<String, String> {
+        let name = HSTRING::from(CONTEXT_PIPE_NAME);
+        // 每个连接使用一个管道实例，连接后再创建下一个实例等待新的连接
+        let create_pipe = move || unsafe {
+            let handle = CreateNamedPipeW(
+                &name,
+                PIPE_ACCESS_INBOUND,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                0,
+                4096,
+                0,
+                None,
+            );
+            if handle.is_invalid() {
+                Err(format!("创建上下文命名管道失败: {:?}", GetLastError()))
+            } else {
+                Ok(handle)
+            }
+        };
+        let mut handle = create_pipe()?;
+        let slots = ClientSlots::default();
+        thread::spawn(move || loop {
+            let connected = unsafe {
+                ConnectNamedPipe(handle, None).as_bool() || GetLastError() == ERROR_PIPE_CONNECTED
+            };
+            if connected {
+                // File 在连接结束时关闭管道实例
+                let stream = unsafe { File::from_raw_handle(handle.0 as _) };
+                slots.spawn(stream, &tracker);
+            } else {
+                let _ = unsafe { CloseHandle(handle) };
+            }
+            handle = match create_pipe() {
+                Ok(handle) => handle,
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            };
+        });
+        Ok(CONTEXT_PIPE_NAME.to_string())
+    }
+}
+
+// 其他平台暂不支持
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use super::ContextTracker;
+    use std::path::Path;
+
+    pub fn start_server(_app_dir: &Path, _tracker: ContextTracker) -> Result<String, String> {
+        Err("当前平台不支持上下文接口".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn expires_and_clears_tags() {
+        let tracker = ContextTracker::new();
+        let start = Instant::now();
+        tracker.set_at("  project:crate \n", start);
+
+        // (用例, 距设置的时间, 期望的标签)
+        let cases = [
+            ("刚设置", Duration::ZERO, Some("project:crate")),
+            ("未到期", CONTEXT_TTL - Duration::from_secs(1), Some("project:crate")),
+            ("到期后失效", CONTEXT_TTL, None),
+            ("失效后不再恢复", Duration::ZERO, None),
+        ];
+        for (name, elapsed, expected) in cases {
+            assert_eq!(tracker.current_at(start + elapsed).as_deref(), expected, "{}", name);
+        }
+
+        tracker.set_at("project:crate", start);
+        tracker.set_at("   ", start);
+        assert_eq!(tracker.current_at(start), None, "空标签清除");
+
+        tracker.set_at(&"长".repeat(MAX_TAG_CHARS + 10), start);
+        assert_eq!(tracker.current_at(start).unwrap().chars().count(), MAX_TAG_CHARS, "超长标签截断");
+    }
+
+    #[test]
+    fn reads_tags_line_by_line() {
+        let long_line = "x".repeat(MAX_LINE_BYTES as usize + 1);
+        // (用例, 连接发来的内容, 期望的标签)
+        let cases = [
+            ("逐行更新", "project:a\nproject:b\n".to_string(), Some("project:b")),
+            ("末行无换行", "project:a\nproject:b".to_string(), Some("project:b")),
+            ("空行清除", "project:a\n\n".to_string(), None),
+            ("超长消息断开连接", format!("project:a\n{}\nproject:b\n", long_line), Some("project:a")),
+        ];
+        for (name, input, expected) in cases {
+            let tracker = ContextTracker::new();
+            handle_client(Cursor::new(input), &tracker);
+            assert_eq!(tracker.current().as_deref(), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn limits_concurrent_clients() {
+        let slots = ClientSlots::default();
+        let held: Vec<_> = (0..MAX_CLIENTS).map(|_| slots.acquire().expect("未达上限")).collect();
+        assert!(slots.acquire().is_none(), "达到上限后拒绝");
+        drop(held);
+        assert!(slots.acquire().is_some(), "连接结束后归还名额");
+    }
+}
@@ -1,94 +1,302 @@
 use rusqlite::{Connection, Result, params};
-use chrono::{DateTime, Local};
+use std::path::Path;
+use chrono::{DateTime, Local, TimeZone};
+use crate::rollup::Granularity;
 use serde::{Serialize, Deserialize};
-use serde_json;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct KeyboardEventRecord {
     pub timestamp: DateTime<Local>,
     pub key_code: String,
     pub app_name: String,
+    #[serde(default)]
+    pub hold_ms: Option<i64>, // 按住时长（毫秒），未收到松开事件时为空
+    #[serde(default)]
+    pub flight_ms: Option<i64>, // 距上一次按键的间隔（毫秒），监听开始后的第一次按键为空
+    #[serde(default)]
+    pub raw_key: Option<String>, // 物理按键标识（rdev按键名，如 ShiftLeft、Kp5、Unknown(12)）
+    #[serde(default)]
+    pub scan_code: Option<u32>, // 平台按键码（Windows 虚拟键码、Linux 内核按键码、macOS CGKeyCode），输入源无法提供时为空
+    #[serde(default)]
+    pub repeat_count: i64, // 按住期间系统自动重复的次数
+    #[serde(default)]
+    pub device_id: Option<String>, // 产生按键的键盘，输入源无法区分设备时为空
+    #[serde(default)]
+    pub context_tag: Option<String>, // 外部工具告知的上下文标签（项目、分支、任务等）
 }
 
-// 初始化数据库，创建必要的表
-pub fn init_db(db_path: &str) -> Result<Connection> {
-    let conn = Connection::open(db_path)?;
-    
-    // 创建键盘事件表
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS keyboard_events (
-            id INTEGER PRIMARY KEY,
-            timestamp TEXT NOT NULL,
-            key_code TEXT NOT NULL,
-            app_name TEXT NOT NULL
-        )",
-        [],
-    )?;
-    println!("创建键盘事件表成功");
-    
-    // 创建应用统计表
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS app_stats (
-            id INTEGER PRIMARY KEY,
-            app_name TEXT UNIQUE NOT NULL,
-            key_count INTEGER NOT NULL DEFAULT 0,
-            last_used TEXT NOT NULL
-        )",
-        [],
-    )?;
-    println!("创建应用统计表成功");
-    
-    // 创建按键统计表
+// 活跃状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivityState {
+    Active,
+    Idle,
+}
+
+impl ActivityState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActivityState::Active => "active",
+            ActivityState::Idle => "idle",
+        }
+    }
+}
+
+// 一段连续的活跃或空闲时间
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityInterval {
+    pub start_time: DateTime<Local>,
+    pub end_time: DateTime<Local>,
+    pub state: ActivityState,
+}
+
+// 一次鼠标点击
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MouseClickRecord {
+    pub timestamp: DateTime<Local>,
+    pub button: String, // Left、Right、Middle 或 Unknown(n)
+    pub app_name: String,
+}
+
+// 某一分钟内某个应用的鼠标移动距离和滚动量
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MouseActivityRecord {
+    pub minute: DateTime<Local>,
+    pub app_name: String,
+    pub move_distance: f64, // 指针移动距离（像素）
+    pub scroll_x: i64,      // 水平滚动格数（绝对值之和）
+    pub scroll_y: i64,      // 垂直滚动格数（绝对值之和）
+}
+
+// 键盘设备信息，按 device_id 保存一份
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputDeviceRecord {
+    pub device_id: String,
+    pub name: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub last_seen: DateTime<Local>,
+}
+
+// 按应用策略只记录次数的按键使用的 key_code，不对应任何真实按键
+pub const MASKED_KEY_CODE: &str = "[masked]";
+// 按应用策略只记录次数的鼠标事件使用的应用名称
+pub const MASKED_APP_NAME: &str = "[masked]";
+
+// 时间以 UTC 毫秒保存，同时保存记录时的时区偏移（秒），
+// 按小时、星期等本地时间分组时使用记录时的偏移：strftime('%H', ts / 1000 + utc_offset, 'unixepoch')
+pub fn utc_offset_seconds(time: &DateTime<Local>) -> i32 {
+    time.offset().local_minus_utc()
+}
+
+// UTC 毫秒转换为本地时间
+pub fn from_epoch_millis(ms: i64) -> DateTime<Local> {
+    Local.timestamp_millis_opt(ms).single().unwrap_or_else(Local::now)
+}
+
+// db_meta 中的键：早于该时间（UTC 毫秒）的原始按键记录已按保留策略清理，只剩汇总
+pub const RAW_RETAINED_FROM: &str = "raw_retained_from";
+// 同上，早于该时间的分钟、小时汇总已清理
+pub const MINUTE_RETAINED_FROM: &str = "minute_retained_from";
+pub const HOUR_RETAINED_FROM: &str = "hour_retained_from";
+
+pub fn get_meta(conn: &Connection, key: &str) -> Result<Option<i64>> {
+    let mut stmt = conn.prepare_cached("SELECT value FROM db_meta WHERE key = ?1")?;
+    let mut rows = stmt.query(params![key])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(row.get(0)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn set_meta(conn: &Connection, key: &str, value: i64) -> Result<()> {
     conn.execute(
-        "CREATE TABLE IF NOT EXISTS key_stats (
-            id INTEGER PRIMARY KEY,
-            key_code TEXT UNIQUE NOT NULL,
-            count INTEGER NOT NULL DEFAULT 0
-        )",
-        [],
+        "INSERT INTO db_meta (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value],
     )?;
-    println!("创建按键统计表成功");
-    
+    Ok(())
+}
+
+// 原始按键记录保留的起始时间，没有清理过时为 0
+pub fn raw_retained_from(conn: &Connection) -> Result<i64> {
+    Ok(get_meta(conn, RAW_RETAINED_FROM)?.unwrap_or(0))
+}
+
+// 按键记录和汇总表只保存应用和按键的编号，名称各保存一份在 apps、keys 表中。
+// 需要按名称筛选或显示名称时连接这两个表，连接后可以直接使用 app_name、key_code 列
+pub const EVENTS_WITH_NAMES: &str = "keyboard_events JOIN apps USING (app_id) JOIN keys USING (key_id)";
+
+// 获取应用名称对应的编号，第一次出现时分配
+pub fn app_id(conn: &Connection, app_name: &str) -> Result<i64> {
+    intern(conn, "SELECT app_id FROM apps WHERE app_name = ?1", "INSERT INTO apps (app_name) VALUES (?1)", app_name)
+}
+
+// 获取按键名称对应的编号，第一次出现时分配
+pub fn key_id(conn: &Connection, key_code: &str) -> Result<i64> {
+    intern(conn, "SELECT key_id FROM keys WHERE key_code = ?1", "INSERT INTO keys (key_code) VALUES (?1)", key_code)
+}
+
+fn intern(conn: &Connection, select_sql: &str, insert_sql: &str, name: &str) -> Result<i64> {
+    let mut stmt = conn.prepare_cached(select_sql)?;
+    let mut rows = stmt.query(params![name])?;
+    if let Some(row) = rows.next()? {
+        return row.get(0);
+    }
+    conn.prepare_cached(insert_sql)?.execute(params![name])?;
+    Ok(conn.last_insert_rowid())
+}
+
+// 打开数据库，并把表结构升级到当前版本
+pub fn init_db(db_path: &str) -> Result<Connection> {
+    let mut conn = Connection::open(db_path)?;
+    crate::migrations::migrate(&mut conn, Some(Path::new(db_path)))?;
     Ok(conn)
 }
 
 // 插入键盘事件记录
 pub fn insert_event(conn: &Connection, event: &KeyboardEventRecord) -> Result<()> {
+    let key_id = key_id(conn, &event.key_code)?;
+    let app_id = app_id(conn, &event.app_name)?;
+
     // 插入事件记录
-    conn.execute(
-        "INSERT INTO keyboard_events (timestamp, key_code, app_name) 
-         VALUES (?1, ?2, ?3)",
-        params![
-            event.timestamp.to_rfc3339(),
-            event.key_code,
-            event.app_name
-        ],
-    )?;
-    println!("插入事件记录成功");
+    conn.prepare_cached(
+        "INSERT INTO keyboard_events (ts, utc_offset, key_id, app_id, hold_ms, flight_ms, raw_key, scan_code, repeat_count, device_id, context_tag) 
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)"
+    )?.execute(params![
+        event.timestamp.timestamp_millis(),
+        utc_offset_seconds(&event.timestamp),
+        key_id,
+        app_id,
+        event.hold_ms,
+        event.flight_ms,
+        event.raw_key,
+        event.scan_code,
+        event.repeat_count,
+        event.device_id,
+        event.context_tag
+    ])?;
     
     // 更新应用统计
-    conn.execute(
-        "INSERT INTO app_stats (app_name, key_count, last_used) 
+    conn.prepare_cached(
+        "INSERT INTO app_stats (app_name, key_count, last_used_ts) 
          VALUES (?1, 1, ?2)
          ON CONFLICT(app_name) DO UPDATE SET 
          key_count = key_count + 1,
-         last_used = ?2",
-        params![
-            event.app_name,
-            event.timestamp.to_rfc3339()
-        ],
-    )?;
-    println!("更新应用统计成功");
+         last_used_ts = ?2"
+    )?.execute(params![
+        event.app_name,
+        event.timestamp.timestamp_millis()
+    ])?;
     
     // 更新按键统计
-    conn.execute(
+    conn.prepare_cached(
         "INSERT INTO key_stats (key_code, count) 
          VALUES (?1, 1)
          ON CONFLICT(key_code) DO UPDATE SET 
-         count = count + 1",
-        params![event.key_code],
+         count = count + 1"
+    )?.execute(params![event.key_code])?;
+
+    // 更新按分钟、小时、天的汇总
+    crate::rollup::record_event(conn, event, app_id, key_id)?;
+    Ok(())
+}
+
+// 记录键盘设备，已存在时更新名称和最近使用时间
+pub fn upsert_input_device(conn: &Connection, device: &InputDeviceRecord) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO input_devices (device_id, name, vendor_id, product_id, last_seen_ts) 
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(device_id) DO UPDATE SET 
+         name = excluded.name,
+         last_seen_ts = excluded.last_seen_ts"
+    )?.execute(params![
+        device.device_id,
+        device.name,
+        device.vendor_id,
+        device.product_id,
+        device.last_seen.timestamp_millis()
+    ])?;
+    Ok(())
+}
+
+// 插入一段活跃或空闲时间
+pub fn insert_activity_interval(conn: &Connection, interval: &ActivityInterval) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO activity_intervals (start_ts, end_ts, utc_offset, state) VALUES (?1, ?2, ?3, ?4)"
+    )?.execute(params![
+        interval.start_time.timestamp_millis(),
+        interval.end_time.timestamp_millis(),
+        utc_offset_seconds(&interval.start_time),
+        interval.state.as_str()
+    ])?;
+    Ok(())
+}
+
+// 查询与指定时间范围有重叠的活跃/空闲时间段，结果截取到范围之内
+pub fn query_activity_intervals(
+    conn: &Connection,
+    start_time: DateTime<Local>,
+    end_time: DateTime<Local>
+) -> Result<Vec<ActivityInterval>> {
+    let mut stmt = conn.prepare(
+        "SELECT start_ts, end_ts, state
+         FROM activity_intervals
+         WHERE end_ts >= ?1 AND start_ts <= ?2
+         ORDER BY start_ts"
     )?;
-    println!("更新按键统计成功");
+    let rows = stmt.query_map(
+        params![start_time.timestamp_millis(), end_time.timestamp_millis()],
+        |row| {
+            let state = match row.get::<_, String>(2)?.as_str() {
+                "idle" => ActivityState::Idle,
+                _ => ActivityState::Active,
+            };
+            Ok(ActivityInterval {
+                start_time: from_epoch_millis(row.get(0)?).max(start_time),
+                end_time: from_epoch_millis(row.get(1)?).min(end_time),
+                state,
+            })
+        },
+    )?;
+
+    let mut result = Vec::new();
+    for row in rows {
+        result.push(row?);
+    }
+    Ok(result)
+}
+
+// 插入一次鼠标点击
+pub fn insert_mouse_click(conn: &Connection, click: &MouseClickRecord) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO mouse_clicks (ts, utc_offset, button, app_name) VALUES (?1, ?2, ?3, ?4)"
+    )?.execute(params![
+        click.timestamp.timestamp_millis(),
+        utc_offset_seconds(&click.timestamp),
+        click.button,
+        click.app_name
+    ])?;
+    Ok(())
+}
+
+// 累加某一分钟内某个应用的鼠标移动和滚动
+pub fn insert_mouse_activity(conn: &Connection, activity: &MouseActivityRecord) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO mouse_activity (minute_ts, utc_offset, app_name, move_distance, scroll_x, scroll_y) 
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(minute_ts, app_name) DO UPDATE SET 
+         move_distance = move_distance + excluded.move_distance,
+         scroll_x = scroll_x + excluded.scroll_x,
+         scroll_y = scroll_y + excluded.scroll_y"
+    )?.execute(params![
+        activity.minute.timestamp_millis(),
+        utc_offset_seconds(&activity.minute),
+        activity.app_name,
+        activity.move_distance,
+        activity.scroll_x,
+        activity.scroll_y
+    ])?;
     Ok(())
 }
 
@@ -98,25 +306,28 @@ pub fn query_events_by_time_range(
     start_time: DateTime<Local>, 
     end_time: DateTime<Local>
 ) -> Result<Vec<KeyboardEventRecord>> {
-    let mut stmt = conn.prepare(
-        "SELECT timestamp, key_code, app_name 
-         FROM keyboard_events 
-         WHERE timestamp BETWEEN ?1 AND ?2
-         ORDER BY timestamp DESC"
-    )?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT ts, key_code, app_name, hold_ms, flight_ms, raw_key, scan_code, repeat_count, device_id, context_tag 
+         FROM {} 
+         WHERE ts BETWEEN ?1 AND ?2
+         ORDER BY ts DESC",
+        EVENTS_WITH_NAMES
+    ))?;
     
     let events = stmt.query_map(
-        params![start_time.to_rfc3339(), end_time.to_rfc3339()],
+        params![start_time.timestamp_millis(), end_time.timestamp_millis()],
         |row| {
-            let timestamp_str: String = row.get(0)?;
-            let timestamp = DateTime::parse_from_rfc3339(&timestamp_str)
-                .map(|dt| dt.with_timezone(&Local))
-                .unwrap_or_else(|_| Local::now());
-            
             Ok(KeyboardEventRecord {
-                timestamp,
+                timestamp: from_epoch_millis(row.get(0)?),
                 key_code: row.get(1)?,
                 app_name: row.get(2)?,
+                hold_ms: row.get(3)?,
+                flight_ms: row.get(4)?,
+                raw_key: row.get(5)?,
+                scan_code: row.get(6)?,
+                repeat_count: row.get(7)?,
+                device_id: row.get(8)?,
+                context_tag: row.get(9)?,
             })
         },
     )?;
@@ -142,15 +353,11 @@ pub fn get_key_count_by_time_range(
     start_time: DateTime<Local>, 
     end_time: DateTime<Local>
 ) -> Result<i64> {
-    let mut stmt = conn.prepare(
-        "SELECT COUNT(*) FROM keyboard_events 
-         WHERE timestamp BETWEEN ?1 AND ?2"
-    )?;
-    
-    let count: i64 = stmt.query_row(
-        params![start_time.to_rfc3339(), end_time.to_rfc3339()],
-        |row| row.get(0)
-    )?;
+    let sql = format!(
+        "SELECT COALESCE(SUM(count), 0) FROM ({})",
+        crate::rollup::source_sql(conn, &start_time, &end_time, Granularity::Day)?
+    );
+    let count: i64 = conn.query_row(&sql, [], |row| row.get(0))?;
     
     Ok(count)
 }
@@ -195,24 +402,27 @@ pub fn get_top_apps(conn: &Connection, limit: i64) -> Result<Vec<(String, i64)>>
     Ok(result)
 }
 
-// 计算指定时间范围内的平均KPM
+// 计算指定时间范围内的平均KPM：按键次数除以活跃时间。
+// 只统计有活跃时间记录的范围内的按键，没有活跃时间记录时（如升级前的数据）返回 0
 pub fn calculate_average_kpm(
     conn: &Connection, 
     start_time: DateTime<Local>, 
     end_time: DateTime<Local>
 ) -> Result<f64> {
-    // 获取时间范围内的总按键次数
-    let key_count = get_key_count_by_time_range(conn, start_time.clone(), end_time.clone())?;
-    
-    // 计算时间范围的分钟数
-    let duration = end_time.signed_duration_since(start_time);
-    let minutes = duration.num_minutes() as f64;
-    
-    if minutes > 0.0 {
-        Ok(key_count as f64 / minutes)
-    } else {
-        Ok(0.0)
+    let intervals = query_activity_intervals(conn, start_time, end_time)?;
+    let active_ms: i64 = intervals.iter()
+        .filter(|interval| interval.state == ActivityState::Active)
+        .map(|interval| interval.end_time.signed_duration_since(interval.start_time).num_milliseconds().max(0))
+        .sum();
+    let (Some(first), Some(last)) = (intervals.first(), intervals.last()) else {
+        return Ok(0.0);
+    };
+    if active_ms <= 0 {
+        return Ok(0.0);
     }
+    // 时间段在最后一次输入处结束，结束时刻的按键也计入
+    let key_count = get_key_count_by_time_range(conn, first.start_time, last.end_time + chrono::Duration::milliseconds(1))?;
+    Ok(key_count as f64 / (active_ms as f64 / 60_000.0))
 }
 
 // 清空所有数据
@@ -220,28 +430,93 @@ pub fn clear_all_data(conn: &mut Connection) -> Result<()> {
     conn.execute("DELETE FROM keyboard_events", [])?;
     conn.execute("DELETE FROM app_stats", [])?;
     conn.execute("DELETE FROM key_stats", [])?;
+    conn.execute("DELETE FROM activity_intervals", [])?;
+    conn.execute("DELETE FROM mouse_clicks", [])?;
+    conn.execute("DELETE FROM mouse_activity", [])?;
+    conn.execute("DELETE FROM input_devices", [])?;
+    conn.execute("DELETE FROM rollup_minute", [])?;
+    conn.execute("DELETE FROM rollup_hour", [])?;
+    conn.execute("DELETE FROM rollup_day", [])?;
+    conn.execute("DELETE FROM db_meta", [])?;
+    conn.execute("DELETE FROM apps", [])?;
+    conn.execute("DELETE FROM keys", [])?;
     Ok(())
 }
 
+// 隐私模式下按分钟保存的按键的判断条件（keys 表），与 privacy::is_private_key 保持一致
+const PRIVATE_KEY_CONDITION: &str =
+    "((length(key_code) = 1 AND unicode(key_code) BETWEEN 33 AND 126)
+      OR (key_code LIKE 'Shift+_' AND unicode(substr(key_code, 7)) BETWEEN 33 AND 126)
+      OR key_code IN ('Shift', 'Space', 'Enter', 'Backspace', 'Shift+Space', 'Shift+Enter', 'Shift+Backspace'))";
+
+// 按隐私模式处理已有的字符键及空格、回车、退格、Shift 记录：时间截断到分钟，去掉按键节奏，并按分钟内排序重新写入以打乱原有顺序
+// 返回处理的记录数
+pub fn harden_character_events(conn: &mut Connection) -> Result<usize> {
+    // 覆盖删除的数据页，避免原始记录残留在数据库文件中；只在本次处理期间开启，完成后恢复原来的设置
+    let secure_delete: i64 = conn.query_row("PRAGMA secure_delete", [], |row| row.get(0))?;
+    conn.execute_batch("PRAGMA secure_delete = ON")?;
+    let result = rewrite_character_events(conn).and_then(|count| {
+        // WAL 模式下原来的数据页在检查点之前仍留在数据库文件中，立即写回并清空 WAL
+        let busy: i64 = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))?;
+        if busy != 0 {
+            println!("数据库正在被读取，原始记录将在下次检查点时覆盖");
+        }
+        Ok(count)
+    });
+    conn.execute_batch(&format!("PRAGMA secure_delete = {}", secure_delete))?;
+    result
+}
+
+fn rewrite_character_events(conn: &mut Connection) -> Result<usize> {
+    let tx = conn.transaction()?;
+    tx.execute_batch(&format!(
+        "DROP TABLE IF EXISTS temp.hardened_events;
+         CREATE TEMP TABLE hardened_events AS
+             SELECT ts - ts % 60000 AS ts, utc_offset,
+                    key_id, app_id, raw_key, scan_code, repeat_count, device_id, context_tag
+             FROM keyboard_events
+             WHERE key_id IN (SELECT key_id FROM keys WHERE {condition});
+         DELETE FROM keyboard_events WHERE key_id IN (SELECT key_id FROM keys WHERE {condition});",
+        condition = PRIVATE_KEY_CONDITION
+    ))?;
+    let count = tx.execute(
+        "INSERT INTO keyboard_events (ts, utc_offset, key_id, app_id, hold_ms, flight_ms, raw_key, scan_code, repeat_count, device_id, context_tag)
+         SELECT ts, utc_offset, key_id, app_id, NULL, NULL, raw_key, scan_code, repeat_count, device_id, context_tag
+         FROM hardened_events
+         ORDER BY ts, app_id, key_id",
+        [],
+    )?;
+    tx.execute_batch("DROP TABLE temp.hardened_events")?;
+    tx.commit()?;
+    Ok(count)
+}
+
 // 导出数据为JSON格式
 pub fn export_data_as_json(
     conn: &Connection, 
     start_time: DateTime<Local>, 
     end_time: DateTime<Local>
 ) -> Result<String> {
-    let mut events = query_events_by_time_range(conn, start_time, end_time)?;
+    let events = query_events_by_time_range(conn, start_time, end_time)?;
     
     // 使用serde_json::Value手动构建JSON以添加额外字段
     let json_events: Vec<serde_json::Value> = events.iter().map(|event| {
         let mut map = serde_json::Map::new();
         map.insert("readable_time".to_string(), serde_json::Value::String(event.timestamp.format("%Y-%m-%d %H:%M:%S").to_string()));
         map.insert("key_code".to_string(), serde_json::Value::String(event.key_code.clone()));
+        map.insert("raw_key".to_string(), event.raw_key.clone().map(serde_json::Value::String).unwrap_or(serde_json::Value::Null));
+        map.insert("scan_code".to_string(), event.scan_code.map(serde_json::Value::from).unwrap_or(serde_json::Value::Null));
         map.insert("app_name".to_string(), serde_json::Value::String(event.app_name.clone()));
+        map.insert("hold_ms".to_string(), event.hold_ms.map(serde_json::Value::from).unwrap_or(serde_json::Value::Null));
+        map.insert("flight_ms".to_string(), event.flight_ms.map(serde_json::Value::from).unwrap_or(serde_json::Value::Null));
+        map.insert("repeat_count".to_string(), serde_json::Value::from(event.repeat_count));
+        map.insert("device_id".to_string(), event.device_id.clone().map(serde_json::Value::String).unwrap_or(serde_json::Value::Null));
+        map.insert("context_tag".to_string(), event.context_tag.clone().map(serde_json::Value::String).unwrap_or(serde_json::Value::Null));
         serde_json::Value::Object(map)
     }).collect();
     
     let json = serde_json::to_string_pretty(&json_events)
-        .map_err(|e| rusqlite::Error::InvalidQuery)?; // 简单转换错误类型
+        .map_err(|_| rusqlite::Error::InvalidQuery)?; // 简单转换错误类型
     Ok(json)
 }
 
@@ -253,16 +528,23 @@ pub fn export_data_as_csv(
 ) -> Result<String> {
     let events = query_events_by_time_range(conn, start_time, end_time)?;
     
-    let mut csv_content = String::from("readable_time,key_code,app_name\n");
+    let mut csv_content = String::from("readable_time,key_code,raw_key,scan_code,app_name,hold_ms,flight_ms,repeat_count,device_id,context_tag\n");
     for event in events {
         // 格式化时间戳为易读格式
         let readable_time = event.timestamp.format("%Y-%m-%d %H:%M:%S").to_string();
         
         // 简单处理CSV，实际项目中可能需要更复杂的转义处理
-        let line = format!("{},\"{}\",\"{}\"\n",
+        let line = format!("{},\"{}\",\"{}\",{},\"{}\",{},{},{},\"{}\",\"{}\"\n",
             readable_time,
             event.key_code.replace(',', "\\,"),
-            event.app_name.replace('"', "\"\"")
+            event.raw_key.as_deref().unwrap_or_default().replace('"', "\"\""),
+            event.scan_code.map(|code| code.to_string()).unwrap_or_default(),
+            event.app_name.replace('"', "\"\""),
+            event.hold_ms.map(|ms| ms.to_string()).unwrap_or_default(),
+            event.flight_ms.map(|ms| ms.to_string()).unwrap_or_default(),
+            event.repeat_count,
+            event.device_id.as_deref().unwrap_or_default().replace('"', "\"\""),
+            event.context_tag.as_deref().unwrap_or_default().replace('"', "\"\"")
         );
         csv_content.push_str(&line);
     }
@@ -280,17 +562,23 @@ pub fn delete_data_by_time_range(
     let key_counts: Vec<(String, i64)>;
     let app_counts: Vec<(String, i64)>;
     
+    // 按汇总统计，原始记录按保留策略清理后的时间段也能正确扣减。
+    // 扣减和删除使用同一组桶，部分覆盖的已清理桶整桶删除
+    let retained = crate::rollup::Retained::load(conn)?;
+    let parts = crate::rollup::plan(&start_time, &end_time, Granularity::Day, &retained);
+    let source = crate::rollup::parts_sql(&parts);
+    
     // 在独立作用域中获取数据，确保stmt被释放
     {
-        let mut stmt = conn.prepare(
-            "SELECT key_code, COUNT(*) as count 
-             FROM keyboard_events 
-             WHERE timestamp BETWEEN ?1 AND ?2
-             GROUP BY key_code"
-        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT key_code, SUM(count) as count 
+             FROM ({}) 
+             GROUP BY key_id",
+            source
+        ))?;
         
         key_counts = stmt.query_map(
-            params![start_time.to_rfc3339(), end_time.to_rfc3339()],
+            [],
             |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
         )?
         .filter_map(|r| r.ok())
@@ -299,29 +587,45 @@ pub fn delete_data_by_time_range(
     
     // 在独立作用域中获取数据，确保stmt被释放
     {
-        let mut stmt = conn.prepare(
-            "SELECT app_name, COUNT(*) as count 
-             FROM keyboard_events 
-             WHERE timestamp BETWEEN ?1 AND ?2
-             GROUP BY app_name"
-        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT app_name, SUM(count) as count 
+             FROM ({}) 
+             GROUP BY app_id",
+            source
+        ))?;
         
         app_counts = stmt.query_map(
-            params![start_time.to_rfc3339(), end_time.to_rfc3339()],
+            [],
             |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
         )?
         .filter_map(|r| r.ok())
         .collect();
     }
+    let deleted_count = key_counts.iter().map(|(_, count)| *count as usize).sum();
     
     // 开始事务
     let tx = conn.transaction()?;
     
-    // 删除键盘事件记录
-    let deleted_count = tx.execute(
-        "DELETE FROM keyboard_events 
-         WHERE timestamp BETWEEN ?1 AND ?2",
-        params![start_time.to_rfc3339(), end_time.to_rfc3339()],
+    // 删除键盘事件记录和对应的汇总
+    crate::rollup::delete_parts(&tx, &parts)?;
+    
+    // 删除范围内开始的活跃/空闲时间段
+    tx.execute(
+        "DELETE FROM activity_intervals 
+         WHERE start_ts BETWEEN ?1 AND ?2",
+        params![start_time.timestamp_millis(), end_time.timestamp_millis()],
+    )?;
+    
+    // 删除范围内的鼠标数据
+    tx.execute(
+        "DELETE FROM mouse_clicks 
+         WHERE ts BETWEEN ?1 AND ?2",
+        params![start_time.timestamp_millis(), end_time.timestamp_millis()],
+    )?;
+    tx.execute(
+        "DELETE FROM mouse_activity 
+         WHERE minute_ts BETWEEN ?1 AND ?2",
+        params![start_time.timestamp_millis(), end_time.timestamp_millis()],
     )?;
     
     // 更新按键统计
@@ -377,10 +681,10 @@ pub fn export_summary_as_json(
     let top_apps = get_top_apps(conn, 10)?;
     
     // 获取指定时间范围内的总按键次数
-    let total_key_count = get_key_count_by_time_range(conn, start_time.clone(), end_time.clone())?;
+    let total_key_count = get_key_count_by_time_range(conn, start_time, end_time)?;
     
     // 计算时间范围内的平均KPM
-    let avg_kpm = calculate_average_kpm(conn, start_time.clone(), end_time.clone())?;
+    let avg_kpm = calculate_average_kpm(conn, start_time, end_time)?;
     
     // 使用serde_json构建JSON
     let mut summary = serde_json::Map::new();
@@ -437,10 +741,10 @@ pub fn export_summary_as_csv(
     let top_apps = get_top_apps(conn, 10)?;
     
     // 获取指定时间范围内的总按键次数
-    let total_key_count = get_key_count_by_time_range(conn, start_time.clone(), end_time.clone())?;
+    let total_key_count = get_key_count_by_time_range(conn, start_time, end_time)?;
     
     // 计算时间范围内的平均KPM
-    let avg_kpm = calculate_average_kpm(conn, start_time.clone(), end_time.clone())?;
+    let avg_kpm = calculate_average_kpm(conn, start_time, end_time)?;
     
     // 构建CSV内容
     let mut csv_content = String::new();
@@ -465,7 +769,7 @@ pub fn export_summary_as_csv(
     }
     
     // 换行
-    csv_content.push_str("\n");
+    csv_content.push('\n');
     
     // 添加最常用应用统计
     csv_content.push_str("最常用应用\n");
@@ -477,30 +781,26 @@ pub fn export_summary_as_csv(
     Ok(csv_content)
 }
 
+// 连续输入时段：开始时间、结束时间、持续秒数
+pub type TypingSession = (DateTime<Local>, DateTime<Local>, i64);
+
 // 识别连续输入时段，用于健康风险评估
 pub fn identify_continuous_typing_sessions(
     conn: &Connection,
     start_time: DateTime<Local>,
     end_time: DateTime<Local>,
     max_gap_seconds: i64
-) -> Result<Vec<(DateTime<Local>, DateTime<Local>, i64)>> {
+) -> Result<Vec<TypingSession>> {
     // 查询指定时间范围内的键盘事件
     let mut stmt = conn.prepare(
-        "SELECT timestamp FROM keyboard_events 
-         WHERE timestamp BETWEEN ?1 AND ?2
-         ORDER BY timestamp ASC"
+        "SELECT ts FROM keyboard_events 
+         WHERE ts BETWEEN ?1 AND ?2
+         ORDER BY ts ASC"
     )?;
     
     let timestamp_rows = stmt.query_map(
-        params![start_time.to_rfc3339(), end_time.to_rfc3339()],
-        |row| {
-            let timestamp_str: String = row.get(0)?;
-            let timestamp = DateTime::parse_from_rfc3339(&timestamp_str)
-                .map(|dt| dt.with_timezone(&Local))
-                .unwrap_or_else(|_| Local::now());
-            
-            Ok(timestamp)
-        },
+        params![start_time.timestamp_millis(), end_time.timestamp_millis()],
+        |row| Ok(from_epoch_millis(row.get(0)?)),
     )?;
     
     let mut timestamps: Vec<DateTime<Local>> = Vec::new();
@@ -509,7 +809,7 @@ pub fn identify_continuous_typing_sessions(
     }
     
     // 识别连续输入时段
-    let mut sessions: Vec<(DateTime<Local>, DateTime<Local>, i64)> = Vec::new();
+    let mut sessions: Vec<TypingSession> = Vec::new();
     
     if timestamps.is_empty() {
         return Ok(sessions);
@@ -518,8 +818,7 @@ pub fn identify_continuous_typing_sessions(
     let mut session_start = timestamps[0];
     let mut last_time = timestamps[0];
     
-    for i in 1..timestamps.len() {
-        let current_time = timestamps[i];
+    for &current_time in &timestamps[1..] {
         let gap = current_time.signed_duration_since(last_time).num_seconds();
         
         if gap > max_gap_seconds {
@@ -541,6 +840,49 @@ pub fn identify_continuous_typing_sessions(
     Ok(sessions)
 }
 
+// 按住时长超过该值视为长按（毫秒）
+pub const LONG_HOLD_MS: i64 = 500;
+
+// 超过该值的按键间隔视为停顿（离开、暂停、隔夜等）而不是打字节奏，不记录间隔（毫秒）
+pub const MAX_FLIGHT_MS: i64 = 5000;
+
+// 计算已排序数据的百分位数（最近秩法），p取值0-100
+pub fn percentile(sorted: &[i64], p: f64) -> i64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+// 获取指定时间范围内非修饰键的按住时长（升序）
+pub fn get_hold_times(
+    conn: &Connection,
+    start_time: DateTime<Local>,
+    end_time: DateTime<Local>
+) -> Result<Vec<i64>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT hold_ms FROM {} 
+         WHERE ts BETWEEN ?1 AND ?2
+         AND hold_ms IS NOT NULL
+         AND key_code NOT IN ('Ctrl', 'Shift', 'Alt', 'Win')
+         ORDER BY hold_ms ASC",
+        EVENTS_WITH_NAMES
+    ))?;
+
+    let rows = stmt.query_map(
+        params![start_time.timestamp_millis(), end_time.timestamp_millis()],
+        |row| row.get::<_, i64>(0)
+    )?;
+
+    let mut result = Vec::new();
+    for row in rows {
+        result.push(row?);
+    }
+
+    Ok(result)
+}
+
 // 计算健康风险相关指标
 pub fn calculate_health_risk_metrics(
     conn: &Connection,
@@ -548,7 +890,7 @@ pub fn calculate_health_risk_metrics(
     end_time: DateTime<Local>
 ) -> Result<serde_json::Value> {
     // 获取总按键数
-    let total_key_count = get_key_count_by_time_range(conn, start_time.clone(), end_time.clone())?;
+    let total_key_count = get_key_count_by_time_range(conn, start_time, end_time)?;
     
     // 计算时间范围的天数
     let days = (end_time.signed_duration_since(start_time).num_seconds() as f64 / 86400.0).max(1.0);
@@ -557,10 +899,10 @@ pub fn calculate_health_risk_metrics(
     let daily_avg_keys = total_key_count as f64 / days;
     
     // 计算平均KPM
-    let avg_kpm = calculate_average_kpm(conn, start_time.clone(), end_time.clone())?;
+    let avg_kpm = calculate_average_kpm(conn, start_time, end_time)?;
     
     // 识别连续输入时段（定义5分钟无按键为会话中断）
-    let sessions = identify_continuous_typing_sessions(conn, start_time.clone(), end_time.clone(), 300)?;
+    let sessions = identify_continuous_typing_sessions(conn, start_time, end_time, 300)?;
     
     // 计算长时间会话
     let long_sessions_threshold = 60 * 60; // 60分钟连续输入定义为长会话（秒）
@@ -575,6 +917,19 @@ pub fn calculate_health_risk_metrics(
         0.0
     };
     
+    // 计算按键按住时长（长按是手指劳损的信号之一）
+    let hold_times = get_hold_times(conn, start_time, end_time)?;
+    let avg_hold_ms = if !hold_times.is_empty() {
+        hold_times.iter().sum::<i64>() as f64 / hold_times.len() as f64
+    } else {
+        0.0
+    };
+    let long_hold_ratio = if !hold_times.is_empty() {
+        hold_times.iter().filter(|ms| **ms >= LONG_HOLD_MS).count() as f64 / hold_times.len() as f64 * 100.0
+    } else {
+        0.0
+    };
+
     // 构建返回的JSON对象
     let mut metrics = serde_json::Map::new();
     
@@ -585,6 +940,9 @@ pub fn calculate_health_risk_metrics(
     metrics.insert("total_sessions".to_string(), serde_json::Value::Number(serde_json::Number::from(sessions.len())));
     metrics.insert("long_sessions_count".to_string(), serde_json::Value::Number(serde_json::Number::from(long_sessions.len())));
     metrics.insert("avg_session_duration_seconds".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(avg_session_duration).unwrap_or(serde_json::Number::from(0))));
+    metrics.insert("avg_hold_ms".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(avg_hold_ms).unwrap_or(serde_json::Number::from(0))));
+    metrics.insert("p90_hold_ms".to_string(), serde_json::Value::Number(serde_json::Number::from(percentile(&hold_times, 90.0))));
+    metrics.insert("long_hold_ratio".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(long_hold_ratio).unwrap_or(serde_json::Number::from(0))));
     
     // 添加长会话详情
     let long_sessions_details: Vec<serde_json::Value> = long_sessions.iter().map(|(start, end, duration)| {
@@ -610,27 +968,16 @@ pub fn calculate_health_risk_metrics(
 
 // 新增：获取最早的按键事件时间
 pub fn get_first_event_time(conn: &Connection) -> Result<Option<DateTime<Local>>> {
-    // 检查表是否为空
-    let count: i64 = conn.query_row(
-        "SELECT COUNT(*) FROM keyboard_events",
+    // 查询最早的时间戳，原始记录按保留策略清理后以天汇总为准；表为空时返回None
+    let first_ts: Option<i64> = conn.query_row(
+        "SELECT MIN(first_ts) FROM (
+             SELECT MIN(ts) AS first_ts FROM keyboard_events
+             UNION ALL
+             SELECT MIN(bucket_ts) FROM rollup_day
+         )",
         [],
         |row| row.get(0)
     )?;
     
-    if count == 0 {
-        return Ok(None); // 表为空，返回None
-    }
-    
-    // 查询最早的时间戳
-    let timestamp_str: String = conn.query_row(
-        "SELECT timestamp FROM keyboard_events ORDER BY timestamp ASC LIMIT 1",
-        [],
-        |row| row.get(0)
-    )?;
-    
-    // 将时间戳字符串转换为DateTime<Local>
-    match DateTime::parse_from_rfc3339(&timestamp_str) {
-        Ok(dt) => Ok(Some(dt.with_timezone(&Local))),
-        Err(_) => Ok(None) // 无法解析时间戳，返回None
-    }
+    Ok(first_ts.map(from_epoch_millis))
 }
\ No newline at end of file
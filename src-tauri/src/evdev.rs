@@ -0,0 +1,513 @@
+// Linux evdev 输入源：直接读取 /dev/input/event*，不依赖 X11，在 Wayland 下同样可用，
+// 并且能区分按键来自哪一个键盘。需要当前用户有 /dev/input 的读取权限（通常是加入 input 组）
+use crate::input::{InputCallback, InputDevice, InputEvent, InputSource, StopSignal, STOP_POLL_INTERVAL};
+use chrono::{DateTime, Local};
+use rdev::{EventType, Key};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const EV_KEY: u16 = 0x01;
+// 小于该值的按键码是键盘按键，之上是鼠标、手柄等按钮
+const BTN_MISC: u16 = 0x100;
+// 判断设备是否为键盘时要求支持的按键：A、空格、回车
+const KEYBOARD_PROBE_KEYS: [u16; 3] = [30, 57, 28];
+// 扫描新接入设备的间隔
+const RESCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+// struct input_event：timeval + type(u16) + code(u16) + value(i32)
+#[cfg(target_pointer_width = "64")]
+const TIME_FIELD_SIZE: usize = 8;
+#[cfg(not(target_pointer_width = "64"))]
+const TIME_FIELD_SIZE: usize = 4;
+const EVENT_SIZE: usize = TIME_FIELD_SIZE * 2 + 8;
+
+enum DeviceMessage {
+    Event(InputEvent),
+    Closed(PathBuf),
+}
+
+pub struct EvdevSource {
+    input_dir: PathBuf,
+    sys_dir: PathBuf,
+}
+
+impl EvdevSource {
+    pub fn new() -> Self {
+        EvdevSource {
+            input_dir: PathBuf::from("/dev/input"),
+            sys_dir: PathBuf::from("/sys/class/input"),
+        }
+    }
+
+    // 打开尚未打开的键盘设备，每个设备一个读取线程，收到停止信号后退出
+    fn open_new_devices(
+        &self,
+        opened: &mut HashSet<PathBuf>,
+        tx: &Sender<DeviceMessage>,
+        stop: &StopSignal,
+        readers: &mut Vec<JoinHandle<()>>,
+    ) {
+        let entries = match fs::read_dir(&self.sys_dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                println!("读取输入设备列表失败: {}", e);
+                return;
+            }
+        };
+        for entry in entries.flatten() {
+            let node = entry.file_name().to_string_lossy().to_string();
+            if !node.starts_with("event") {
+                continue;
+            }
+            let path = self.input_dir.join(&node);
+            if opened.contains(&path) {
+                continue;
+            }
+            let sys_device = entry.path().join("device");
+            if !is_keyboard(&sys_device) {
+                continue;
+            }
+            let file = match OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(&path) {
+                Ok(file) => file,
+                Err(e) => {
+                    println!("无法打开输入设备 {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            let device = Arc::new(read_device_info(&sys_device));
+            println!("开始读取键盘设备: {} ({})", device.name, path.display());
+            opened.insert(path.clone());
+            let (tx, stop) = (tx.clone(), stop.clone());
+            readers.push(thread::spawn(move || read_device(file, path, device, tx, stop)));
+        }
+    }
+}
+
+impl Default for EvdevSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputSource for EvdevSource {
+    fn name(&self) -> &str {
+        "evdev"
+    }
+
+    // 收到停止信号后等待各设备的读取线程退出再返回
+    fn run(self: Box<Self>, mut callback: InputCallback, stop: StopSignal) -> Result<(), String> {
+        let (tx, rx) = channel();
+        let mut opened = HashSet::new();
+        let mut readers = Vec::new();
+        self.open_new_devices(&mut opened, &tx, &stop, &mut readers);
+        if opened.is_empty() {
+            return Err("没有可读取的键盘设备，请确认当前用户有 /dev/input 的读取权限（例如加入 input 组）".to_string());
+        }
+
+        let mut last_scan = Instant::now();
+        while !stop.load(Ordering::Relaxed) {
+            match rx.recv_timeout(STOP_POLL_INTERVAL) {
+                Ok(DeviceMessage::Event(event)) => callback(event),
+                Ok(DeviceMessage::Closed(path)) => {
+                    println!("输入设备已断开: {}", path.display());
+                    opened.remove(&path);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+            if last_scan.elapsed() >= RESCAN_INTERVAL {
+                readers.retain(|reader| !reader.is_finished());
+                self.open_new_devices(&mut opened, &tx, &stop, &mut readers);
+                last_scan = Instant::now();
+            }
+        }
+        // 读取线程最多在一个 STOP_POLL_INTERVAL 后发现停止信号
+        drop(rx);
+        for reader in readers {
+            let _ = reader.join();
+        }
+        Ok(())
+    }
+}
+
+// 以非阻塞方式读取设备，每隔 STOP_POLL_INTERVAL 检查一次停止信号
+fn read_device(file: File, path: PathBuf, device: Arc<InputDevice>, tx: Sender<DeviceMessage>, stop: StopSignal) {
+    let mut buf = [0u8; EVENT_SIZE * 64];
+    let mut poll_fd = libc::pollfd { fd: file.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+    let timeout_ms = STOP_POLL_INTERVAL.as_millis() as libc::c_int;
+    while !stop.load(Ordering::Relaxed) {
+        poll_fd.revents = 0;
+        // SAFETY: poll_fd 在调用期间有效，file 持有该文件描述符
+        let ready = unsafe { libc::poll(&mut poll_fd, 1, timeout_ms) };
+        if ready < 0 && io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            break;
+        }
+        if ready <= 0 {
+            continue;
+        }
+        // 读出当前所有事件，内核每次只返回完整的事件
+        loop {
+            let n = match (&file).read(&mut buf) {
+                Ok(0) => return closed(&tx, path),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => return closed(&tx, path),
+            };
+            for chunk in buf[..n].chunks_exact(EVENT_SIZE) {
+                let Ok(raw) = <&[u8; EVENT_SIZE]>::try_from(chunk) else {
+                    continue;
+                };
+                let Some((event_type, code)) = parse_key_event(raw) else {
+                    continue;
+                };
+                let event = InputEvent {
+                    timestamp: parse_time(raw),
+                    event_type,
+                    app_name: None,
+                    code: Some(code as u32),
+                    device: Some(device.clone()),
+                };
+                if tx.send(DeviceMessage::Event(event)).is_err() {
+                    return;
+                }
+            }
+        }
+        // 设备拔出后不再可读
+        if poll_fd.revents & (libc::POLLERR | libc::POLLHUP | libc::POLLNVAL) != 0 {
+            return closed(&tx, path);
+        }
+    }
+}
+
+fn closed(tx: &Sender<DeviceMessage>, path: PathBuf) {
+    let _ = tx.send(DeviceMessage::Closed(path));
+}
+
+// 解析按键事件，value 为 0 松开、1 按下、2 自动重复
+fn parse_key_event(buf: &[u8; EVENT_SIZE]) -> Option<(EventType, u16)> {
+    let offset = TIME_FIELD_SIZE * 2;
+    let kind = u16::from_ne_bytes([buf[offset], buf[offset + 1]]);
+    let code = u16::from_ne_bytes([buf[offset + 2], buf[offset + 3]]);
+    let value = i32::from_ne_bytes([buf[offset + 4], buf[offset + 5], buf[offset + 6], buf[offset + 7]]);
+    if kind != EV_KEY || code >= BTN_MISC {
+        return None;
+    }
+    let key = key_from_code(code);
+    match value {
+        0 => Some((EventType::KeyRelease(key), code)),
+        1 | 2 => Some((EventType::KeyPress(key), code)),
+        _ => None,
+    }
+}
+
+fn parse_time(buf: &[u8; EVENT_SIZE]) -> DateTime<Local> {
+    let field = |index: usize| -> i64 {
+        let bytes = &buf[index * TIME_FIELD_SIZE..(index + 1) * TIME_FIELD_SIZE];
+        match TIME_FIELD_SIZE {
+            8 => i64::from_ne_bytes(bytes.try_into().unwrap_or_default()),
+            _ => i32::from_ne_bytes(bytes.try_into().unwrap_or_default()) as i64,
+        }
+    };
+    let (secs, micros) = (field(0), field(1));
+    if secs < 0 || !(0..1_000_000).contains(&micros) {
+        return Local::now();
+    }
+    let time: SystemTime = UNIX_EPOCH + Duration::new(secs as u64, micros as u32 * 1000);
+    DateTime::<Local>::from(time)
+}
+
+fn read_sys_value(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+// capabilities/key 是以空格分隔的十六进制位图，高位在前，每段为一个 unsigned long
+fn is_keyboard(sys_device: &Path) -> bool {
+    let Some(bitmap) = read_sys_value(&sys_device.join("capabilities/key")) else {
+        return false;
+    };
+    let words: Vec<u64> = bitmap.split_whitespace()
+        .rev()
+        .filter_map(|word| u64::from_str_radix(word, 16).ok())
+        .collect();
+    let word_bits = usize::BITS as usize;
+    KEYBOARD_PROBE_KEYS.iter().all(|&code| {
+        let code = code as usize;
+        words.get(code / word_bits)
+            .is_some_and(|word| word & (1u64 << (code % word_bits)) != 0)
+    })
+}
+
+fn read_device_info(sys_device: &Path) -> InputDevice {
+    let name = read_sys_value(&sys_device.join("name")).unwrap_or_else(|| "unknown".to_string());
+    let hex_id = |file: &str| {
+        read_sys_value(&sys_device.join("id").join(file))
+            .and_then(|value| u16::from_str_radix(&value, 16).ok())
+            .unwrap_or(0)
+    };
+    let (vendor_id, product_id) = (hex_id("vendor"), hex_id("product"));
+    InputDevice {
+        id: format!("{:04x}:{:04x}:{}", vendor_id, product_id, name),
+        name,
+        vendor_id,
+        product_id,
+    }
+}
+
+// Linux 按键码（input-event-codes.h）到 rdev 按键的映射
+fn key_from_code(code: u16) -> Key {
+    match code {
+        1 => Key::Escape,
+        2 => Key::Num1,
+        3 => Key::Num2,
+        4 => Key::Num3,
+        5 => Key::Num4,
+        6 => Key::Num5,
+        7 => Key::Num6,
+        8 => Key::Num7,
+        9 => Key::Num8,
+        10 => Key::Num9,
+        11 => Key::Num0,
+        12 => Key::Minus,
+        13 => Key::Equal,
+        14 => Key::Backspace,
+        15 => Key::Tab,
+        16 => Key::KeyQ,
+        17 => Key::KeyW,
+        18 => Key::KeyE,
+        19 => Key::KeyR,
+        20 => Key::KeyT,
+        21 => Key::KeyY,
+        22 => Key::KeyU,
+        23 => Key::KeyI,
+        24 => Key::KeyO,
+        25 => Key::KeyP,
+        26 => Key::LeftBracket,
+        27 => Key::RightBracket,
+        28 => Key::Return,
+        29 => Key::ControlLeft,
+        30 => Key::KeyA,
+        31 => Key::KeyS,
+        32 => Key::KeyD,
+        33 => Key::KeyF,
+        34 => Key::KeyG,
+        35 => Key::KeyH,
+        36 => Key::KeyJ,
+        37 => Key::KeyK,
+        38 => Key::KeyL,
+        39 => Key::SemiColon,
+        40 => Key::Quote,
+        41 => Key::BackQuote,
+        42 => Key::ShiftLeft,
+        43 => Key::BackSlash,
+        44 => Key::KeyZ,
+        45 => Key::KeyX,
+        46 => Key::KeyC,
+        47 => Key::KeyV,
+        48 => Key::KeyB,
+        49 => Key::KeyN,
+        50 => Key::KeyM,
+        51 => Key::Comma,
+        52 => Key::Dot,
+        53 => Key::Slash,
+        54 => Key::ShiftRight,
+        55 => Key::KpMultiply,
+        56 => Key::Alt,
+        57 => Key::Space,
+        58 => Key::CapsLock,
+        59 => Key::F1,
+        60 => Key::F2,
+        61 => Key::F3,
+        62 => Key::F4,
+        63 => Key::F5,
+        64 => Key::F6,
+        65 => Key::F7,
+        66 => Key::F8,
+        67 => Key::F9,
+        68 => Key::F10,
+        69 => Key::NumLock,
+        70 => Key::ScrollLock,
+        71 => Key::Kp7,
+        72 => Key::Kp8,
+        73 => Key::Kp9,
+        74 => Key::KpMinus,
+        75 => Key::Kp4,
+        76 => Key::Kp5,
+        77 => Key::Kp6,
+        78 => Key::KpPlus,
+        79 => Key::Kp1,
+        80 => Key::Kp2,
+        81 => Key::Kp3,
+        82 => Key::Kp0,
+        83 => Key::KpDelete,
+        86 => Key::IntlBackslash,
+        87 => Key::F11,
+        88 => Key::F12,
+        96 => Key::KpReturn,
+        97 => Key::ControlRight,
+        98 => Key::KpDivide,
+        99 => Key::PrintScreen,
+        100 => Key::AltGr,
+        102 => Key::Home,
+        103 => Key::UpArrow,
+        104 => Key::PageUp,
+        105 => Key::LeftArrow,
+        106 => Key::RightArrow,
+        107 => Key::End,
+        108 => Key::DownArrow,
+        109 => Key::PageDown,
+        110 => Key::Insert,
+        111 => Key::Delete,
+        119 => Key::Pause,
+        125 => Key::MetaLeft,
+        126 => Key::MetaRight,
+        _ => Key::Unknown(code as u32),
+    }
+}
+
+// rdev 按键反查 Linux 按键码，映射表以外的按键返回空
+pub(crate) fn code_from_key(key: Key) -> Option<u16> {
+    (1..BTN_MISC).find(|code| key_from_code(*code) == key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 按 capabilities/key 的格式生成位图：每段为一个 unsigned long，高位在前
+    fn bitmap(codes: &[u16]) -> String {
+        let word_bits = usize::BITS as usize;
+        let mut words = vec![0u64; 0x300 / word_bits];
+        for &code in codes {
+            words[code as usize / word_bits] |= 1u64 << (code as usize % word_bits);
+        }
+        words.iter().rev().map(|word| format!("{:x}", word)).collect::<Vec<_>>().join(" ")
+    }
+
+    fn event_buf(secs: i64, micros: i64) -> [u8; EVENT_SIZE] {
+        let mut buf = [0u8; EVENT_SIZE];
+        for (index, value) in [secs, micros].into_iter().enumerate() {
+            let range = index * TIME_FIELD_SIZE..(index + 1) * TIME_FIELD_SIZE;
+            match TIME_FIELD_SIZE {
+                8 => buf[range].copy_from_slice(&value.to_ne_bytes()),
+                _ => buf[range].copy_from_slice(&(value as i32).to_ne_bytes()),
+            }
+        }
+        buf
+    }
+
+    #[test]
+    fn maps_linux_key_codes() {
+        let cases = [
+            (1, Key::Escape),
+            (14, Key::Backspace),
+            (28, Key::Return),
+            (30, Key::KeyA),
+            (42, Key::ShiftLeft),
+            (54, Key::ShiftRight),
+            (57, Key::Space),
+            (76, Key::Kp5),
+            (96, Key::KpReturn),
+            (100, Key::AltGr),
+            (125, Key::MetaLeft),
+            (183, Key::Unknown(183)),
+        ];
+        for (code, key) in cases {
+            assert_eq!(key_from_code(code), key, "按键码 {}", code);
+        }
+        // 每个按键码对应不同的按键，反查得到原来的按键码
+        for code in 1..BTN_MISC {
+            assert_eq!(code_from_key(key_from_code(code)), Some(code), "按键码 {}", code);
+        }
+        assert_eq!(code_from_key(Key::Function), None);
+    }
+
+    #[test]
+    fn detects_keyboards_from_capabilities() {
+        let dir = std::env::temp_dir().join(format!("keyboard-statistics-evdev-{}", std::process::id()));
+        let all_keys: Vec<u16> = (1..BTN_MISC).collect();
+        let cases: Vec<(&str, Option<String>, bool)> = vec![
+            ("完整键盘", Some(bitmap(&all_keys)), true),
+            ("只有部分按键的键盘", Some(bitmap(&[1, 28, 30, 57, 113])), true),
+            ("缺少回车", Some(bitmap(&[30, 57])), false),
+            ("电源键", Some(bitmap(&[116])), false),
+            ("鼠标按钮", Some(bitmap(&[0x110, 0x111, 0x112])), false),
+            ("只有一段的位图", Some("0".to_string()), false),
+            ("无效位图", Some("zz".to_string()), false),
+            ("没有按键能力", None, false),
+        ];
+        for (name, capabilities, expected) in cases {
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(dir.join("capabilities")).unwrap();
+            if let Some(capabilities) = capabilities {
+                fs::write(dir.join("capabilities/key"), format!("{}\n", capabilities)).unwrap();
+            }
+            assert_eq!(is_keyboard(&dir), expected, "用例: {}", name);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn stops_reading_when_signalled() {
+        let path = std::env::temp_dir().join(format!("keyboard-statistics-evdev-{}.fifo", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let c_path = std::ffi::CString::new(path.to_str().unwrap()).unwrap();
+        // SAFETY: c_path 是有效的 C 字符串
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        // 以读写方式打开，测试中的写入端与读取线程共用同一个管道
+        let file = OpenOptions::new().read(true).write(true).custom_flags(libc::O_NONBLOCK).open(&path).unwrap();
+        let mut writer = file.try_clone().unwrap();
+
+        let (tx, rx) = channel();
+        let stop: StopSignal = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let device = Arc::new(InputDevice { id: "kb".to_string(), name: "kb".to_string(), vendor_id: 0, product_id: 0 });
+        let reader = {
+            let (path, stop) = (path.clone(), stop.clone());
+            thread::spawn(move || read_device(file, path, device, tx, stop))
+        };
+
+        // A 键按下
+        let mut buf = event_buf(1_709_254_800, 0);
+        let offset = TIME_FIELD_SIZE * 2;
+        buf[offset..offset + 2].copy_from_slice(&EV_KEY.to_ne_bytes());
+        buf[offset + 2..offset + 4].copy_from_slice(&30u16.to_ne_bytes());
+        buf[offset + 4..offset + 8].copy_from_slice(&1i32.to_ne_bytes());
+        std::io::Write::write_all(&mut writer, &buf).unwrap();
+        match rx.recv_timeout(Duration::from_secs(5)).unwrap() {
+            DeviceMessage::Event(event) => {
+                assert!(matches!(event.event_type, EventType::KeyPress(Key::KeyA)));
+                assert_eq!(event.code, Some(30));
+            }
+            DeviceMessage::Closed(_) => panic!("设备不应关闭"),
+        }
+
+        // 没有新事件时也能在停止后退出
+        let stopped_at = Instant::now();
+        stop.store(true, Ordering::Relaxed);
+        reader.join().unwrap();
+        assert!(stopped_at.elapsed() < STOP_POLL_INTERVAL * 3);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn parses_event_times() {
+        // 2024-03-01T01:00:00Z 之后 123.456 毫秒
+        let time = parse_time(&event_buf(1_709_254_800, 123_456));
+        assert_eq!(time.timestamp_millis(), 1_709_254_800_123);
+
+        // 无效的时间使用当前时间
+        for (secs, micros) in [(-1, 0), (1_709_254_800, 1_000_000), (1_709_254_800, -1)] {
+            let time = parse_time(&event_buf(secs, micros));
+            assert!(Local::now().signed_duration_since(time).num_seconds().abs() < 5, "{} {}", secs, micros);
+        }
+    }
+}
@@ -0,0 +1,181 @@
+// 前台应用解析：按平台获取当前接收键盘输入的应用进程名
+
+// 无法识别前台应用时使用的名称
+pub const UNKNOWN_APP: &str = "unknown";
+
+pub fn active_app_name() -> String {
+    match platform::active_app_name() {
+        Some(name) if !name.is_empty() => name,
+        _ => UNKNOWN_APP.to_string(),
+    }
+}
+
+// 是否运行在 Wayland 会话中，此时 rdev 的 X11 钩子收不到其他应用的按键
+#[cfg(target_os = "linux")]
+pub fn is_wayland_session() -> bool {
+    platform::is_wayland()
+}
+
+#[cfg(windows)]
+mod platform {
+    use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId};
+    use windows::Win32::System::Threading::{OpenProcess, PROCESS_QUERY_INFORMATION, PROCESS_VM_READ};
+    use windows::Win32::System::ProcessStatus::GetProcessImageFileNameW;
+    use windows::Win32::Foundation::CloseHandle;
+
+    pub fn active_app_name() -> Option<String> {
+        unsafe {
+            let hwnd = GetForegroundWindow();
+
+            // 获取进程名称
+            let mut pid = 0u32;
+            GetWindowThreadProcessId(hwnd, Some(&mut pid));
+            if pid == 0 {
+                return None;
+            }
+            let handle = OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, false, pid).ok()?;
+            let mut path = [0u16; 260];
+            let len = GetProcessImageFileNameW(handle, &mut path) as usize;
+            // 每次按键都会打开进程，句柄用完立即关闭
+            let _ = CloseHandle(handle);
+            if len == 0 {
+                return None;
+            }
+            let process_path = String::from_utf16_lossy(&path[..len]);
+            process_path.split('\\').last().map(|s| s.to_string())
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::cell::Cell;
+    use std::ffi::CString;
+    use std::os::raw::{c_int, c_uchar, c_ulong};
+    use std::ptr;
+    use std::time::{Duration, Instant};
+    use x11::xlib;
+
+    // 连接 X 服务器失败后，在该时长内不再重试，避免每次按键都尝试连接
+    const DISPLAY_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+    // Xlib 连接不能跨线程共享，每个调用线程各自持有一个
+    thread_local! {
+        static DISPLAY: Cell<*mut xlib::Display> = const { Cell::new(ptr::null_mut()) };
+        static DISPLAY_FAILED_AT: Cell<Option<Instant>> = const { Cell::new(None) };
+    }
+
+    pub fn active_app_name() -> Option<String> {
+        // Wayland 下无法通过 X11 获取其他应用的窗口信息
+        if is_wayland() {
+            return None;
+        }
+        let pid = active_window_pid()?;
+        process_name(pid)
+    }
+
+    pub fn is_wayland() -> bool {
+        let session_type = std::env::var("XDG_SESSION_TYPE").unwrap_or_default();
+        session_type.eq_ignore_ascii_case("wayland")
+            || (std::env::var_os("WAYLAND_DISPLAY").is_some() && std::env::var_os("DISPLAY").is_none())
+    }
+
+    // _NET_ACTIVE_WINDOW → _NET_WM_PID
+    fn active_window_pid() -> Option<u32> {
+        let display = display()?;
+        unsafe {
+            let root = xlib::XDefaultRootWindow(display);
+            let window = window_property(display, root, "_NET_ACTIVE_WINDOW")?;
+            if window == 0 {
+                return None;
+            }
+            let pid = window_property(display, window, "_NET_WM_PID")?;
+            u32::try_from(pid).ok().filter(|pid| *pid != 0)
+        }
+    }
+
+    fn display() -> Option<*mut xlib::Display> {
+        DISPLAY.with(|cell| {
+            if cell.get().is_null() {
+                let failed_at = DISPLAY_FAILED_AT.with(Cell::get);
+                if failed_at.is_some_and(|at| at.elapsed() < DISPLAY_RETRY_INTERVAL) {
+                    return None;
+                }
+                unsafe {
+                    // 窗口可能在查询期间被销毁，忽略 BadWindow 等错误，避免默认处理函数退出进程
+                    xlib::XSetErrorHandler(Some(ignore_x_error));
+                    cell.set(xlib::XOpenDisplay(ptr::null()));
+                }
+                let failed = cell.get().is_null();
+                DISPLAY_FAILED_AT.with(|at| at.set(failed.then(Instant::now)));
+            }
+            let display = cell.get();
+            if display.is_null() { None } else { Some(display) }
+        })
+    }
+
+    unsafe extern "C" fn ignore_x_error(_display: *mut xlib::Display, _event: *mut xlib::XErrorEvent) -> c_int {
+        0
+    }
+
+    // 读取 32 位格式属性的第一个值（窗口ID或CARDINAL）
+    unsafe fn window_property(display: *mut xlib::Display, window: xlib::Window, name: &str) -> Option<c_ulong> {
+        let name = CString::new(name).ok()?;
+        let atom = xlib::XInternAtom(display, name.as_ptr(), xlib::True);
+        if atom == 0 {
+            return None;
+        }
+
+        let mut actual_type: xlib::Atom = 0;
+        let mut actual_format: c_int = 0;
+        let mut item_count: c_ulong = 0;
+        let mut bytes_after: c_ulong = 0;
+        let mut data: *mut c_uchar = ptr::null_mut();
+        let status = xlib::XGetWindowProperty(
+            display,
+            window,
+            atom,
+            0,
+            1,
+            xlib::False,
+            xlib::AnyPropertyType as c_ulong,
+            &mut actual_type,
+            &mut actual_format,
+            &mut item_count,
+            &mut bytes_after,
+            &mut data,
+        );
+        if status != xlib::Success as c_int || data.is_null() {
+            return None;
+        }
+
+        // 32 位格式的数据在客户端以 long 数组返回
+        let value = if item_count > 0 && actual_format == 32 {
+            Some(*(data as *const c_ulong))
+        } else {
+            None
+        };
+        xlib::XFree(data as *mut _);
+        value
+    }
+
+    // 优先使用可执行文件名，无权限读取 exe 链接时退回 comm（最多15个字符）
+    fn process_name(pid: u32) -> Option<String> {
+        let exe_name = std::fs::read_link(format!("/proc/{}/exe", pid))
+            .ok()
+            .and_then(|path| path.file_name().map(|name| name.to_string_lossy().to_string()));
+        if exe_name.is_some() {
+            return exe_name;
+        }
+        std::fs::read_to_string(format!("/proc/{}/comm", pid))
+            .ok()
+            .map(|comm| comm.trim().to_string())
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+mod platform {
+    pub fn active_app_name() -> Option<String> {
+        None
+    }
+}
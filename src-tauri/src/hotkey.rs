@@ -0,0 +1,177 @@
+use crate::combo::{Chord, Modifier};
+use serde::{Serialize, Deserialize};
+
+pub const DEFAULT_INCOGNITO_MINUTES: u64 = 15;
+
+// 快捷键设置，保存在配置文件中
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HotkeySettings {
+    pub pause: Option<String>,     // 切换暂停/恢复录制，为空时不启用
+    pub incognito: Option<String>, // 临时暂停，到时自动恢复，为空时不启用
+    pub incognito_minutes: u64,    // 临时暂停的时长（分钟）
+}
+
+// 快捷键默认不启用：启用后暂停录制时监听仍需保持运行以识别快捷键，由用户在设置中开启
+impl Default for HotkeySettings {
+    fn default() -> Self {
+        HotkeySettings {
+            pause: None,
+            incognito: None,
+            incognito_minutes: DEFAULT_INCOGNITO_MINUTES,
+        }
+    }
+}
+
+impl HotkeySettings {
+    // 是否配置了任一快捷键，此时暂停录制需要保持监听以便用快捷键恢复
+    pub fn any_enabled(&self) -> bool {
+        self.pause.is_some() || self.incognito.is_some()
+    }
+}
+
+// 快捷键触发的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyAction {
+    TogglePause,
+    Incognito(u64), // 暂停指定分钟数后自动恢复
+}
+
+// 解析后的快捷键，例如 "Ctrl+Alt+Shift+P"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hotkey {
+    modifiers: Vec<Modifier>,
+    key: String,
+}
+
+impl Hotkey {
+    // 修饰键不区分大小写和顺序，至少需要一个修饰键，避免普通输入误触发
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut modifiers = Vec::new();
+        let mut key = None;
+        for part in text.split('+').map(str::trim) {
+            let modifier = match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => Some(Modifier::Ctrl),
+                "alt" | "option" => Some(Modifier::Alt),
+                "shift" => Some(Modifier::Shift),
+                "win" | "meta" | "super" | "cmd" => Some(Modifier::Win),
+                _ => None,
+            };
+            match (modifier, &key) {
+                (Some(modifier), None) => modifiers.push(modifier),
+                (None, None) if !part.is_empty() => key = Some(part.to_string()),
+                _ => return Err(format!("无效的快捷键: {}", text)),
+            }
+        }
+        let key = key.ok_or_else(|| format!("快捷键缺少按键: {}", text))?;
+        if modifiers.is_empty() {
+            return Err(format!("快捷键至少需要一个修饰键: {}", text));
+        }
+        modifiers.sort();
+        modifiers.dedup();
+        Ok(Hotkey { modifiers, key })
+    }
+
+    pub fn matches(&self, chord: &Chord) -> bool {
+        chord.modifiers == self.modifiers
+            && chord.key.as_deref().is_some_and(|key| key.eq_ignore_ascii_case(&self.key))
+    }
+}
+
+// 监听线程使用的快捷键绑定
+#[derive(Debug, Clone, Default)]
+pub struct HotkeyBindings {
+    pause: Option<Hotkey>,
+    incognito: Option<Hotkey>,
+    incognito_minutes: u64,
+}
+
+impl HotkeyBindings {
+    pub fn from_settings(settings: &HotkeySettings) -> Result<Self, String> {
+        let parse = |text: &Option<String>| text.as_deref().map(Hotkey::parse).transpose();
+        if settings.incognito.is_some() && settings.incognito_minutes == 0 {
+            return Err("临时暂停时长不能为0".to_string());
+        }
+        Ok(HotkeyBindings {
+            pause: parse(&settings.pause)?,
+            incognito: parse(&settings.incognito)?,
+            incognito_minutes: settings.incognito_minutes,
+        })
+    }
+
+    pub fn action_for(&self, chord: &Chord) -> Option<HotkeyAction> {
+        if self.pause.as_ref().is_some_and(|hotkey| hotkey.matches(chord)) {
+            Some(HotkeyAction::TogglePause)
+        } else if self.incognito.as_ref().is_some_and(|hotkey| hotkey.matches(chord)) {
+            Some(HotkeyAction::Incognito(self.incognito_minutes))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(modifiers: &[Modifier], key: Option<&str>) -> Chord {
+        Chord {
+            modifiers: modifiers.to_vec(),
+            key: key.map(str::to_string),
+        }
+    }
+
+    // (用例名称, 快捷键文本, 期望的修饰键和按键，无效时为空)
+    type Case = (&'static str, &'static str, Option<(&'static [Modifier], &'static str)>);
+
+    #[test]
+    fn parses_hotkeys() {
+        use Modifier::*;
+        let cases: &[Case] = &[
+            ("三个修饰键", "Ctrl+Alt+Shift+P", Some((&[Ctrl, Alt, Shift], "P"))),
+            ("修饰键不区分大小写和顺序", "shift+CTRL+p", Some((&[Ctrl, Shift], "p"))),
+            ("macOS 修饰键名称", "Cmd+Option+K", Some((&[Alt, Win], "K"))),
+            ("忽略空格", " Ctrl + F12 ", Some((&[Ctrl], "F12"))),
+            ("重复的修饰键", "Ctrl+Ctrl+S", Some((&[Ctrl], "S"))),
+            ("缺少修饰键", "P", None),
+            ("缺少按键", "Ctrl+Alt", None),
+            ("多个按键", "Ctrl+A+B", None),
+            ("修饰键在按键之后", "Ctrl+A+Shift", None),
+            ("空的按键", "Ctrl++", None),
+            ("空字符串", "", None),
+        ];
+        for (name, text, expected) in cases {
+            let parsed = Hotkey::parse(text).ok().map(|hotkey| (hotkey.modifiers, hotkey.key));
+            let expected = expected.map(|(modifiers, key)| (modifiers.to_vec(), key.to_string()));
+            assert_eq!(parsed, expected, "用例: {}", name);
+        }
+    }
+
+    #[test]
+    fn matches_chords() {
+        use Modifier::*;
+        let hotkey = Hotkey::parse("Ctrl+Shift+p").unwrap();
+        assert!(hotkey.matches(&chord(&[Ctrl, Shift], Some("P"))));
+        assert!(!hotkey.matches(&chord(&[Ctrl], Some("P"))));
+        assert!(!hotkey.matches(&chord(&[Ctrl, Alt, Shift], Some("P"))));
+        assert!(!hotkey.matches(&chord(&[Ctrl, Shift], Some("O"))));
+        assert!(!hotkey.matches(&chord(&[Ctrl, Shift], None)));
+    }
+
+    #[test]
+    fn hotkeys_are_opt_in() {
+        let settings = HotkeySettings::default();
+        assert!(!settings.any_enabled());
+        let bindings = HotkeyBindings::from_settings(&settings).unwrap();
+        assert_eq!(bindings.action_for(&chord(&[Modifier::Ctrl, Modifier::Alt, Modifier::Shift], Some("P"))), None);
+
+        let settings = HotkeySettings {
+            pause: Some("Ctrl+Alt+P".to_string()),
+            incognito: Some("Ctrl+Alt+I".to_string()),
+            incognito_minutes: 5,
+        };
+        let bindings = HotkeyBindings::from_settings(&settings).unwrap();
+        assert_eq!(bindings.action_for(&chord(&[Modifier::Ctrl, Modifier::Alt], Some("P"))), Some(HotkeyAction::TogglePause));
+        assert_eq!(bindings.action_for(&chord(&[Modifier::Ctrl, Modifier::Alt], Some("I"))), Some(HotkeyAction::Incognito(5)));
+        assert!(HotkeyBindings::from_settings(&HotkeySettings { incognito_minutes: 0, ..settings }).is_err());
+    }
+}
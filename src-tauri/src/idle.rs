@@ -0,0 +1,186 @@
+use crate::bus::{BusEvent, Subscriber};
+use crate::database::{ActivityInterval, ActivityState};
+use crate::writer::EventWriter;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+// 默认无输入超过5分钟视为离开
+pub const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 300;
+// 活跃时间段每隔该时长写入一次，异常退出时最多丢失这段时间
+const CHECKPOINT_SECS: i64 = 60;
+
+// 当前活跃状态
+#[derive(Debug, Clone, Serialize)]
+pub struct ActivityStatus {
+    pub state: ActivityState,
+    pub since: Option<DateTime<Local>>, // 进入当前状态的时间，还没有任何输入时为空
+    pub idle_timeout_secs: u64,
+}
+
+#[derive(Default)]
+struct IdleState {
+    interval_start: Option<DateTime<Local>>, // 尚未写入的活跃时间段的开始时间
+    streak_start: Option<DateTime<Local>>,   // 本次连续活跃的开始时间
+    last_input: Option<DateTime<Local>>,
+}
+
+// 空闲检测：超过设定时长没有输入视为离开，下一次输入时恢复活跃，
+// 并把活跃/空闲时间段写入数据库
+#[derive(Clone)]
+pub struct IdleTracker {
+    state: Arc<Mutex<IdleState>>,
+    timeout_secs: Arc<AtomicU64>,
+    writer: Option<EventWriter>,
+}
+
+impl IdleTracker {
+    pub fn new(timeout_secs: u64) -> Self {
+        IdleTracker {
+            state: Arc::new(Mutex::new(IdleState::default())),
+            timeout_secs: Arc::new(AtomicU64::new(timeout_secs)),
+            writer: None,
+        }
+    }
+
+    // 共享状态的副本，负责写入时间段
+    pub fn with_writer(&self, writer: EventWriter) -> Self {
+        IdleTracker {
+            writer: Some(writer),
+            ..self.clone()
+        }
+    }
+
+    pub fn set_timeout_secs(&self, secs: u64) {
+        self.timeout_secs.store(secs, Ordering::Relaxed);
+    }
+
+    pub fn status(&self, now: DateTime<Local>) -> ActivityStatus {
+        let timeout_secs = self.timeout_secs.load(Ordering::Relaxed);
+        let state = self.state.lock().unwrap();
+        let (activity, since) = match state.last_input {
+            Some(last) if now.signed_duration_since(last).num_seconds() < timeout_secs as i64 => {
+                (ActivityState::Active, state.streak_start)
+            }
+            last => (ActivityState::Idle, last),
+        };
+        ActivityStatus {
+            state: activity,
+            since,
+            idle_timeout_secs: timeout_secs,
+        }
+    }
+
+    fn record(&self, start_time: DateTime<Local>, end_time: DateTime<Local>, state: ActivityState) {
+        if end_time <= start_time {
+            return;
+        }
+        if let Some(writer) = &self.writer {
+            writer.submit_interval(ActivityInterval { start_time, end_time, state });
+        }
+    }
+
+    fn on_input(&self, at: DateTime<Local>) {
+        let timeout_secs = self.timeout_secs.load(Ordering::Relaxed) as i64;
+        let mut state = self.state.lock().unwrap();
+        match (state.interval_start, state.last_input) {
+            // 离开后回来：活跃时间段在最后一次输入处结束，中间为空闲
+            (Some(start), Some(last)) if at.signed_duration_since(last).num_seconds() >= timeout_secs => {
+                self.record(start, last, ActivityState::Active);
+                self.record(last, at, ActivityState::Idle);
+                state.interval_start = Some(at);
+                state.streak_start = Some(at);
+            }
+            (Some(start), _) if at.signed_duration_since(start).num_seconds() >= CHECKPOINT_SECS => {
+                self.record(start, at, ActivityState::Active);
+                state.interval_start = Some(at);
+            }
+            (Some(_), _) => {}
+            // 写入后的第一次输入：未离开时从上次输入处接着记录
+            (None, Some(last)) if at.signed_duration_since(last).num_seconds() < timeout_secs => {
+                state.interval_start = Some(last);
+            }
+            (None, _) => {
+                state.interval_start = Some(at);
+                state.streak_start = Some(at);
+            }
+        }
+        state.last_input = Some(at);
+    }
+
+    // 写入未结束的活跃时间段，下一次输入开始新的时间段
+    fn flush(&self) {
+        let mut state = self.state.lock().unwrap();
+        if let (Some(start), Some(last)) = (state.interval_start, state.last_input) {
+            self.record(start, last, ActivityState::Active);
+        }
+        state.interval_start = None;
+    }
+}
+
+impl Subscriber for IdleTracker {
+    fn handle(&mut self, event: &BusEvent) {
+        match event {
+            BusEvent::KeyPressed(event) => self.on_input(event.timestamp),
+            BusEvent::Mouse(event) => self.on_input(event.timestamp),
+            BusEvent::Flush => self.flush(),
+            BusEvent::RecordReady(_) | BusEvent::DeviceSeen(_) | BusEvent::Tick(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::query_activity_intervals;
+    use crate::store::{MemoryStore, Store};
+    use chrono::{Duration, TimeZone};
+
+    fn time(secs: i64) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap() + Duration::seconds(secs)
+    }
+
+    #[test]
+    fn records_active_and_idle_intervals() {
+        let store = Arc::new(MemoryStore::new().unwrap());
+        let writer = EventWriter::spawn(store.clone());
+        let tracker = IdleTracker::new(300).with_writer(writer.clone());
+        assert_eq!(tracker.status(time(0)).state, ActivityState::Idle);
+        assert_eq!(tracker.status(time(0)).since, None);
+
+        tracker.on_input(time(0));
+        tracker.on_input(time(10));
+        // 距时间段开始超过 CHECKPOINT_SECS，写入一段活跃时间
+        tracker.on_input(time(70));
+        tracker.on_input(time(80));
+        let status = tracker.status(time(90));
+        assert_eq!((status.state, status.since), (ActivityState::Active, Some(time(0))));
+        let status = tracker.status(time(380));
+        assert_eq!((status.state, status.since), (ActivityState::Idle, Some(time(80))));
+
+        // 离开后回来：活跃时间在最后一次输入处结束，中间为空闲
+        tracker.on_input(time(480));
+        tracker.on_input(time(500));
+        let status = tracker.status(time(500));
+        assert_eq!((status.state, status.since), (ActivityState::Active, Some(time(480))));
+
+        // 退出前写入未结束的时间段，未离开时下一次输入从上次输入处接着记录
+        tracker.flush();
+        tracker.on_input(time(510));
+        tracker.flush();
+        // 没有新的输入时不写入空的时间段
+        tracker.flush();
+
+        writer.flush();
+        let intervals = query_activity_intervals(&store.read().unwrap(), time(0), time(1000)).unwrap();
+        let intervals: Vec<_> = intervals.iter().map(|i| (i.start_time, i.end_time, i.state)).collect();
+        assert_eq!(intervals, [
+            (time(0), time(70), ActivityState::Active),
+            (time(70), time(80), ActivityState::Active),
+            (time(80), time(480), ActivityState::Idle),
+            (time(480), time(500), ActivityState::Active),
+            (time(500), time(510), ActivityState::Active),
+        ]);
+    }
+}
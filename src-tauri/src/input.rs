@@ -0,0 +1,328 @@
+use rdev::{listen, Button, EventType, Key};
+use chrono::{DateTime, Local};
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// 默认输入源：Linux 的 Wayland 会话使用 evdev，其余使用 rdev
+pub const DEFAULT_INPUT_BACKEND: &str = "auto";
+
+// 产生按键的物理设备
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputDevice {
+    pub id: String, // 设备标识：厂商ID:产品ID:设备名
+    pub name: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+}
+
+// 输入事件，由输入源产生并交给 KeyboardMonitor 处理
+#[derive(Debug, Clone)]
+pub struct InputEvent {
+    pub timestamp: DateTime<Local>,
+    pub event_type: EventType,
+    pub app_name: Option<String>, // 为空时由 KeyboardMonitor 查询前台应用
+    pub code: Option<u32>, // 平台按键码，输入源无法提供时为空
+    pub device: Option<Arc<InputDevice>>, // 输入源无法区分设备时为空
+}
+
+// 事件回调，输入源在自己的线程中调用
+pub type InputCallback = Box<dyn FnMut(InputEvent) + Send>;
+
+// 停止信号，置位后输入源应尽快从 run 返回
+pub type StopSignal = Arc<AtomicBool>;
+
+// 阻塞等待期间检查停止信号的间隔
+pub const STOP_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+// 输入源抽象：阻塞运行，把事件逐个交给回调，直到结束、出错或收到停止信号
+pub trait InputSource: Send {
+    fn name(&self) -> &str;
+    fn run(self: Box<Self>, callback: InputCallback, stop: StopSignal) -> Result<(), String>;
+}
+
+// rdev 的 listen 一旦开始就无法结束，整个进程只启动一次：
+// 停止时只解除回调，再次启动时重新接上；listen 出错返回后下一次启动会重新监听
+static RDEV_CALLBACK: Mutex<Option<InputCallback>> = Mutex::new(None);
+static RDEV_LISTENING: AtomicBool = AtomicBool::new(false);
+static RDEV_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
+// 基于 rdev 全局钩子的输入源（默认）
+pub struct RdevSource;
+
+impl InputSource for RdevSource {
+    fn name(&self) -> &str {
+        "rdev"
+    }
+
+    fn run(self: Box<Self>, callback: InputCallback, stop: StopSignal) -> Result<(), String> {
+        *RDEV_CALLBACK.lock().unwrap() = Some(callback);
+        if !RDEV_LISTENING.swap(true, Ordering::SeqCst) {
+            thread::spawn(|| {
+                let result = listen(|event| {
+                    if let Some(callback) = RDEV_CALLBACK.lock().unwrap().as_mut() {
+                        callback(InputEvent {
+                            timestamp: DateTime::<Local>::from(event.time),
+                            code: crate::keycodes::event_code(&event.event_type),
+                            event_type: event.event_type,
+                            app_name: None,
+                            device: None,
+                        });
+                    }
+                });
+                // listen 只会在出错时返回
+                let error = match result {
+                    Ok(()) => "键盘监听意外结束".to_string(),
+                    Err(e) => format!("键盘监听错误: {:?}", e),
+                };
+                *RDEV_ERROR.lock().unwrap() = Some(error);
+                RDEV_LISTENING.store(false, Ordering::SeqCst);
+            });
+        }
+
+        loop {
+            thread::sleep(STOP_POLL_INTERVAL);
+            if stop.load(Ordering::Relaxed) {
+                *RDEV_CALLBACK.lock().unwrap() = None;
+                return Ok(());
+            }
+            if let Some(error) = RDEV_ERROR.lock().unwrap().take() {
+                *RDEV_CALLBACK.lock().unwrap() = None;
+                return Err(error);
+            }
+        }
+    }
+}
+
+// 按名称创建实时输入源："rdev"、"evdev"（仅 Linux）或 "auto"
+pub fn source_by_name(name: &str) -> Result<Box<dyn InputSource>, String> {
+    match name {
+        "rdev" => Ok(Box::new(RdevSource)),
+        #[cfg(target_os = "linux")]
+        "evdev" => Ok(Box::new(crate::evdev::EvdevSource::new())),
+        #[cfg(target_os = "linux")]
+        "auto" if crate::foreground::is_wayland_session() => Ok(Box::new(crate::evdev::EvdevSource::new())),
+        "auto" => Ok(Box::new(RdevSource)),
+        _ => Err(format!("不支持的输入源: {}", name)),
+    }
+}
+
+// 回放输入源：从文本文件读取事件，用于无界面环境下复现问题
+//
+// 每行一个事件，格式为：<RFC3339时间> <press|release> <rdev按键名> [应用名]
+// 例如：2024-05-01T10:00:00.120+08:00 press ControlLeft Code.exe
+// 鼠标事件：<时间> click <Left|Right|Middle|Unknown(n)> [应用名]
+//           <时间> move <x>,<y> [应用名]
+//           <时间> wheel <dx>,<dy> [应用名]
+// 空行和以 # 开头的行会被忽略
+pub struct ReplaySource {
+    events: Vec<InputEvent>,
+    realtime: bool, // 是否按照原始时间间隔回放
+}
+
+impl ReplaySource {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("读取回放文件失败: {}", e))?;
+        Self::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut events = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let event = parse_trace_line(line)
+                .map_err(|e| format!("回放文件第 {} 行解析失败: {}", index + 1, e))?;
+            events.push(event);
+        }
+        Ok(ReplaySource {
+            events,
+            realtime: false,
+        })
+    }
+
+    pub fn with_realtime(mut self, realtime: bool) -> Self {
+        self.realtime = realtime;
+        self
+    }
+}
+
+impl InputSource for ReplaySource {
+    fn name(&self) -> &str {
+        "replay"
+    }
+
+    fn run(self: Box<Self>, mut callback: InputCallback, stop: StopSignal) -> Result<(), String> {
+        let mut last_time: Option<DateTime<Local>> = None;
+        for event in self.events {
+            if stop.load(Ordering::Relaxed) {
+                break;
+            }
+            if self.realtime {
+                if let Some(last) = last_time {
+                    if let Ok(gap) = event.timestamp.signed_duration_since(last).to_std() {
+                        thread::sleep(gap);
+                    }
+                }
+                last_time = Some(event.timestamp);
+            }
+            callback(event);
+        }
+        Ok(())
+    }
+}
+
+fn parse_trace_line(line: &str) -> Result<InputEvent, String> {
+    let mut parts = line.splitn(4, char::is_whitespace);
+    let time_str = parts.next().ok_or("缺少时间")?;
+    let action = parts.next().ok_or("缺少事件类型")?;
+    let target = parts.next().ok_or("缺少按键名")?;
+    let app_name = parts.next()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+
+    let timestamp = DateTime::parse_from_rfc3339(time_str)
+        .map(|dt| dt.with_timezone(&Local))
+        .map_err(|e| format!("无效的时间 {}: {}", time_str, e))?;
+    let parse_target_key = || parse_key(target).ok_or_else(|| format!("未知按键 {}", target));
+    let event_type = match action {
+        "press" => EventType::KeyPress(parse_target_key()?),
+        "release" => EventType::KeyRelease(parse_target_key()?),
+        "click" => EventType::ButtonPress(parse_button(target).ok_or_else(|| format!("未知鼠标按键 {}", target))?),
+        "move" => {
+            let (x, y) = parse_pair::<f64>(target)?;
+            EventType::MouseMove { x, y }
+        }
+        "wheel" => {
+            let (delta_x, delta_y) = parse_pair::<i64>(target)?;
+            EventType::Wheel { delta_x, delta_y }
+        }
+        _ => return Err(format!("未知事件类型 {}", action)),
+    };
+
+    Ok(InputEvent {
+        timestamp,
+        event_type,
+        app_name,
+        code: None,
+        device: None,
+    })
+}
+
+fn parse_button(name: &str) -> Option<Button> {
+    match name {
+        "Left" => Some(Button::Left),
+        "Right" => Some(Button::Right),
+        "Middle" => Some(Button::Middle),
+        _ => name.strip_prefix("Unknown(")
+            .and_then(|s| s.strip_suffix(')'))
+            .and_then(|code| code.parse::<u8>().ok())
+            .map(Button::Unknown),
+    }
+}
+
+// 解析 "a,b" 形式的一对数值
+fn parse_pair<T: std::str::FromStr>(value: &str) -> Result<(T, T), String> {
+    let (a, b) = value.split_once(',').ok_or_else(|| format!("无效的坐标 {}", value))?;
+    match (a.parse(), b.parse()) {
+        (Ok(a), Ok(b)) => Ok((a, b)),
+        _ => Err(format!("无效的坐标 {}", value)),
+    }
+}
+
+// 按 rdev 的 Debug 名称解析按键，例如 "KeyA"、"ShiftLeft"、"Unknown(12)"
+pub fn parse_key(name: &str) -> Option<Key> {
+    if let Some(code) = name.strip_prefix("Unknown(").and_then(|s| s.strip_suffix(')')) {
+        return code.parse::<u32>().ok().map(Key::Unknown);
+    }
+    ALL_KEYS.iter().copied().find(|key| format!("{:?}", key) == name)
+}
+
+const ALL_KEYS: [Key; 105] = [
+    Key::Alt, Key::AltGr, Key::Backspace, Key::CapsLock, Key::ControlLeft, Key::ControlRight,
+    Key::Delete, Key::DownArrow, Key::End, Key::Escape,
+    Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6,
+    Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12,
+    Key::Home, Key::LeftArrow, Key::MetaLeft, Key::MetaRight, Key::PageDown, Key::PageUp,
+    Key::Return, Key::RightArrow, Key::ShiftLeft, Key::ShiftRight, Key::Space, Key::Tab,
+    Key::UpArrow, Key::PrintScreen, Key::ScrollLock, Key::Pause, Key::NumLock, Key::BackQuote,
+    Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5,
+    Key::Num6, Key::Num7, Key::Num8, Key::Num9, Key::Num0,
+    Key::Minus, Key::Equal,
+    Key::KeyQ, Key::KeyW, Key::KeyE, Key::KeyR, Key::KeyT, Key::KeyY, Key::KeyU, Key::KeyI,
+    Key::KeyO, Key::KeyP, Key::LeftBracket, Key::RightBracket,
+    Key::KeyA, Key::KeyS, Key::KeyD, Key::KeyF, Key::KeyG, Key::KeyH, Key::KeyJ, Key::KeyK,
+    Key::KeyL, Key::SemiColon, Key::Quote, Key::BackSlash, Key::IntlBackslash,
+    Key::KeyZ, Key::KeyX, Key::KeyC, Key::KeyV, Key::KeyB, Key::KeyN, Key::KeyM,
+    Key::Comma, Key::Dot, Key::Slash, Key::Insert,
+    Key::KpReturn, Key::KpMinus, Key::KpPlus, Key::KpMultiply, Key::KpDivide,
+    Key::Kp0, Key::Kp1, Key::Kp2, Key::Kp3, Key::Kp4,
+    Key::Kp5, Key::Kp6, Key::Kp7, Key::Kp8, Key::Kp9,
+    Key::KpDelete, Key::Function,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_trace_lines() {
+        let s = |line: &str| parse_trace_line(line).map(|event| format!("{:?} {:?}", event.event_type, event.app_name));
+        let cases = [
+            ("2024-05-01T10:00:00.120+08:00 press ControlLeft Code.exe", "KeyPress(ControlLeft) Some(\"Code.exe\")"),
+            ("2024-05-01T10:00:00.120+08:00 release KeyA", "KeyRelease(KeyA) None"),
+            ("2024-05-01T10:00:00Z press Unknown(12) My App.exe", "KeyPress(Unknown(12)) Some(\"My App.exe\")"),
+            ("2024-05-01T10:00:00Z click Right chrome.exe", "ButtonPress(Right) Some(\"chrome.exe\")"),
+            ("2024-05-01T10:00:00Z click Unknown(8)", "ButtonPress(Unknown(8)) None"),
+            ("2024-05-01T10:00:00Z move 10.5,-3", "MouseMove { x: 10.5, y: -3.0 } None"),
+            ("2024-05-01T10:00:00Z wheel 0,-2", "Wheel { delta_x: 0, delta_y: -2 } None"),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(s(line).as_deref(), Ok(expected), "{}", line);
+        }
+
+        let errors = [
+            "2024-05-01 press KeyA",
+            "2024-05-01T10:00:00Z tap KeyA",
+            "2024-05-01T10:00:00Z press KeyAA",
+            "2024-05-01T10:00:00Z press",
+            "2024-05-01T10:00:00Z click Back",
+            "2024-05-01T10:00:00Z move 10",
+            "2024-05-01T10:00:00Z wheel 1.5,2",
+        ];
+        for line in errors {
+            assert!(parse_trace_line(line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn parses_trace_files() {
+        // 示例轨迹与回放示例使用同一份文件
+        let source = ReplaySource::parse(include_str!("../examples/traces/ctrl_c.trace")).unwrap();
+        assert_eq!(source.events.len(), 8);
+        assert!(source.events.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp));
+        assert!(source.events.iter().all(|event| event.app_name.as_deref() == Some("Code.exe")));
+
+        // 跳过空行和注释，出错时报告行号
+        let content = "# 注释\n\n2024-05-01T10:00:00Z press KeyA\n2024-05-01T10:00:01Z press Nope\n";
+        let err = ReplaySource::parse(content).err().unwrap();
+        assert!(err.contains("第 4 行"), "{}", err);
+    }
+
+    #[test]
+    fn parses_every_known_key_name() {
+        for key in ALL_KEYS {
+            assert_eq!(parse_key(&format!("{:?}", key)), Some(key));
+        }
+        assert_eq!(parse_key("Unknown(300)"), Some(Key::Unknown(300)));
+        assert_eq!(parse_key("Unknown(x)"), None);
+        assert_eq!(parse_key("keya"), None);
+    }
+}
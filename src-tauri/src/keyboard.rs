@@ -1,191 +1,601 @@
-use rdev::{listen, Event, EventType};
-use rdev::Key::*;
-use std::sync::mpsc::{channel, Sender};
-use std::thread;
+use rdev::EventType;
+use std::collections::{HashMap, HashSet};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 use serde::{Serialize, Deserialize};
 use chrono::{DateTime, Local};
-use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowTextW};
-use windows::Win32::System::Threading::{OpenProcess, PROCESS_QUERY_INFORMATION, PROCESS_VM_READ};
-use windows::Win32::System::ProcessStatus::GetProcessImageFileNameW;
-use windows::Win32::Foundation::{HWND, HANDLE};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use tauri::{AppHandle, Emitter};
-use indexmap::IndexSet;
-use crate::database::{init_db, insert_event, KeyboardEventRecord};
+use tauri::AppHandle;
+use crate::database::{InputDeviceRecord, KeyboardEventRecord, MASKED_APP_NAME, MASKED_KEY_CODE, MAX_FLIGHT_MS};
+use crate::store::Store;
+use crate::policy::{mode_for_app, AppPolicy, RecordingMode};
+use crate::privacy::is_private_key;
+use crate::writer::{EventWriter, WriterStats};
+use crate::input::{source_by_name, InputEvent, InputSource, StopSignal, DEFAULT_INPUT_BACKEND};
+use crate::foreground::active_app_name;
+use crate::combo::{Chord, ComboDetector, DeviceKey, Modifier};
+use crate::hotkey::{HotkeyAction, HotkeyBindings, HotkeySettings};
+use crate::context::ContextTracker;
+use crate::bus::{
+    BusEvent, DatabaseSubscriber, EventBus, KeyPopupSubscriber, KpmTracker, SubscriberInfo,
+    DATABASE_SUBSCRIBER, IDLE_SUBSCRIBER, KEY_POPUP_SUBSCRIBER, KPM_SUBSCRIBER, MOUSE_SUBSCRIBER,
+};
+use crate::mouse::{MouseCapture, MouseSubscriber};
+use crate::idle::{ActivityStatus, IdleTracker, DEFAULT_IDLE_TIMEOUT_SECS};
+use crate::supervisor::{sleep_unless_stopped, Backoff, MonitorStatus, StatusReporter, STABLE_RUN};
 use std::sync::Mutex;
-use std::path::PathBuf;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct KeyboardEvent {
-    timestamp: DateTime<Local>,
-    key_code: String,
-    app_name: String,
+    pub timestamp: DateTime<Local>,
+    pub key_code: String,
+    pub app_name: String,
+    pub chord: Chord,
 }
 
 pub struct KeyboardMonitor {
-    sender: Option<Sender<KeyboardEvent>>,
-    is_running: bool,
+    bus: Arc<EventBus>, // 按键事件总线
+    kpm_tracker: KpmTracker, // 实时KPM
+    idle_tracker: IdleTracker, // 空闲检测
+    status: StatusReporter, // 监听器生命周期状态
+    stop_signal: StopSignal, // 当前输入源的停止信号
     pub enabled: Arc<AtomicBool>,
+    pause_control: PauseControl, // 暂停与恢复，界面和快捷键共用
+    hotkeys: Arc<Mutex<HotkeyBindings>>, // 暂停/临时暂停快捷键
+    hotkey_listener: Arc<Mutex<Option<HotkeyListener>>>, // 快捷键切换录制状态后的回调
+    context: ContextTracker, // 外部工具告知的上下文标签
     app_handle: Option<AppHandle>,
-    pressed_keys: Arc<std::sync::Mutex<IndexSet<String>>>, // 新增
-    db_conn: Option<Arc<Mutex<rusqlite::Connection>>>, // 新增，持有数据库连接
-    app_dir: PathBuf, // 新增字段
+    combo_detector: Arc<Mutex<ComboDetector>>, // 组合键检测
+    app_policies: Arc<Mutex<Vec<AppPolicy>>>, // 按应用的录制策略
+    privacy_mode: Arc<AtomicBool>, // 隐私模式：字符键按分钟保存
+    mouse_enabled: Arc<AtomicBool>, // 是否统计鼠标，默认关闭
+    input_backend: String, // 实时输入源：auto、rdev 或 evdev
+    writer: Option<EventWriter>, // 数据库写入线程
+    store: Result<Arc<dyn Store>, String>, // 共用的数据库，打开失败时为错误原因
+    listener: Option<JoinHandle<()>>, // 输入源所在线程，出错时在其中按退避间隔重启
 }
 
+// 创建输入源，监听线程出错重启时再次调用
+type SourceFactory = Box<dyn FnMut() -> Result<Box<dyn InputSource>, String> + Send>;
+
+// 快捷键切换录制状态后调用，参数为是否正在记录；临时暂停不会调用
+pub type HotkeyListener = Box<dyn Fn(bool) + Send>;
+
 impl KeyboardMonitor {
-    pub fn new(app_dir: PathBuf) -> Self {
+    pub fn new(store: Result<Arc<dyn Store>, String>) -> Self {
+        let bus = Arc::new(EventBus::new());
+        let kpm_tracker = KpmTracker::new();
+        bus.subscribe(KPM_SUBSCRIBER, Box::new(kpm_tracker.clone()));
+        let status = StatusReporter::new();
+        let enabled = Arc::new(AtomicBool::new(true));
+        let combo_detector = Arc::new(Mutex::new(ComboDetector::new()));
+        let pause_control = PauseControl {
+            enabled: enabled.clone(),
+            status: status.clone(),
+            combo_detector: combo_detector.clone(),
+            generation: Arc::new(AtomicU64::new(0)),
+            timed: Arc::new(AtomicBool::new(false)),
+        };
         KeyboardMonitor {
-            sender: None,
-            is_running: false,
-            enabled: Arc::new(AtomicBool::new(true)),
+            bus,
+            kpm_tracker,
+            idle_tracker: IdleTracker::new(DEFAULT_IDLE_TIMEOUT_SECS),
+            status,
+            stop_signal: Arc::new(AtomicBool::new(false)),
+            enabled,
+            pause_control,
+            hotkeys: Arc::new(Mutex::new(HotkeyBindings::default())),
+            hotkey_listener: Arc::new(Mutex::new(None)),
+            context: ContextTracker::new(),
             app_handle: None,
-            pressed_keys: Arc::new(std::sync::Mutex::new(IndexSet::new())), // 新增
-            db_conn: None, // 新增
-            app_dir, // 新增字段
+            combo_detector,
+            app_policies: Arc::new(Mutex::new(Vec::new())),
+            privacy_mode: Arc::new(AtomicBool::new(false)),
+            mouse_enabled: Arc::new(AtomicBool::new(false)),
+            input_backend: DEFAULT_INPUT_BACKEND.to_string(),
+            writer: None,
+            store,
+            listener: None,
         }
     }
     // 设置 AppHandle
     pub fn set_app_handle(&mut self, app_handle: AppHandle) {
+        self.bus.subscribe(KEY_POPUP_SUBSCRIBER, Box::new(KeyPopupSubscriber::new(app_handle.clone())));
+        self.status.set_app_handle(app_handle.clone());
         self.app_handle = Some(app_handle);
     }
 
+    // 更新按应用的录制策略，立即对之后的按键生效
+    pub fn set_app_policies(&self, policies: Vec<AppPolicy>) {
+        *self.app_policies.lock().unwrap() = policies;
+    }
+
+    // 开启或关闭隐私模式，只影响之后写入的记录；关闭时立即写入按分钟缓冲的记录
+    pub fn set_privacy_mode(&self, enabled: bool) {
+        self.privacy_mode.store(enabled, Ordering::Relaxed);
+        self.bus.publish(BusEvent::Tick(Local::now()));
+    }
+
+    // 开启或关闭鼠标统计
+    pub fn set_mouse_enabled(&self, enabled: bool) {
+        self.mouse_enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    // 更新暂停/临时暂停快捷键，快捷键无效时返回错误且保留原设置
+    pub fn set_hotkeys(&self, settings: &HotkeySettings) -> Result<(), String> {
+        *self.hotkeys.lock().unwrap() = HotkeyBindings::from_settings(settings)?;
+        Ok(())
+    }
+
+    // 设置快捷键切换录制状态后的回调，用于保存录制状态
+    pub fn set_hotkey_listener(&self, listener: HotkeyListener) {
+        *self.hotkey_listener.lock().unwrap() = Some(listener);
+    }
+
+    // 上下文标签，由上下文接口写入
+    pub fn context(&self) -> ContextTracker {
+        self.context.clone()
+    }
+
+    // 设置实时输入源，下次启动监听时生效
+    pub fn set_input_backend(&mut self, backend: &str) {
+        self.input_backend = backend.to_string();
+    }
+
+    // 监听线程是否仍在运行（包括暂停和出错后等待重启）
     pub fn is_running(&self) -> bool {
-        self.is_running
+        self.listener.as_ref().is_some_and(|handle| !handle.is_finished())
+    }
+
+    pub fn status(&self) -> MonitorStatus {
+        self.status.status()
     }
 
+    // 启动实时监听，输入源出错时自动重启
     pub fn start(&mut self) -> Result<(), String> {
-        if self.is_running {
+        // 先创建一次，不支持的输入源直接报错
+        source_by_name(&self.input_backend)?;
+        let backend = self.input_backend.clone();
+        self.spawn_listener(Box::new(move || source_by_name(&backend)), true)
+    }
+
+    // 使用指定的输入源启动监听，例如回放文件，出错时不重启
+    pub fn start_with_source(&mut self, source: Box<dyn InputSource>) -> Result<(), String> {
+        let mut source = Some(source);
+        self.spawn_listener(Box::new(move || source.take().ok_or_else(|| "输入源不能重复使用".to_string())), false)
+    }
+
+    fn spawn_listener(&mut self, mut make_source: SourceFactory, restart: bool) -> Result<(), String> {
+        if self.is_running() {
             self.resume();
             return Ok(());
         }
-        let enabled = self.enabled.clone();
-        let (tx, rx) = channel();
-        self.sender = Some(tx.clone());
-        self.is_running = true;
-
-        let app_handle = self.app_handle.clone();
-        let pressed_keys = self.pressed_keys.clone(); // 新增
-
-        // 初始化数据库连接，只初始化一次
-        if self.db_conn.is_none() {
-            let db_path = self.app_dir.join("keyboard_events.db"); // 使用 app_dir
-            let conn = match crate::database::init_db(db_path.to_str().unwrap()) {
-                Ok(c) => Arc::new(Mutex::new(c)),
+        // 回收上一次已结束的监听线程
+        self.join();
+
+        // 初始化数据库写入线程，只初始化一次
+        if self.writer.is_none() {
+            let store = match &self.store {
+                Ok(store) => store.clone(),
                 Err(e) => {
-                    println!("数据库初始化失败: {:?}", e);
+                    println!("数据库初始化失败: {}", e);
+                    self.status.failed("数据库初始化失败".to_string());
                     return Err("数据库初始化失败".to_string());
                 }
             };
-            self.db_conn = Some(conn.clone());
+            let writer = EventWriter::spawn(store);
+            self.bus.subscribe(DATABASE_SUBSCRIBER, Box::new(DatabaseSubscriber::new(writer.clone(), self.privacy_mode.clone())));
+            self.bus.subscribe(IDLE_SUBSCRIBER, Box::new(self.idle_tracker.with_writer(writer.clone())));
+            self.bus.subscribe(MOUSE_SUBSCRIBER, Box::new(MouseSubscriber::new(writer.clone())));
+            self.writer = Some(writer);
         }
-        let db_conn = self.db_conn.as_ref().unwrap().clone();
 
-        thread::spawn(move || {
-            if let Err(error) = listen(move |event| {
-                if !enabled.load(Ordering::Relaxed) {
-                    return;
+        let stop: StopSignal = Arc::new(AtomicBool::new(false));
+        self.stop_signal = stop.clone();
+        self.enabled.store(true, Ordering::Relaxed);
+        self.status.starting();
+
+        let status = self.status.clone();
+        let enabled = self.enabled.clone();
+        let bus = self.bus.clone();
+        // 已按下但尚未松开的按键，松开时计算按住时长后再写入数据库
+        let pending_presses: Arc<Mutex<HashMap<DeviceKey, KeyboardEventRecord>>> = Arc::new(Mutex::new(HashMap::new()));
+        let handler = InputHandler {
+            bus: self.bus.clone(),
+            enabled: self.enabled.clone(),
+            pause_control: self.pause_control.clone(),
+            hotkeys: self.hotkeys.clone(),
+            hotkey_listener: self.hotkey_listener.clone(),
+            context: self.context.clone(),
+            combo_detector: self.combo_detector.clone(),
+            app_policies: self.app_policies.clone(),
+            privacy_mode: self.privacy_mode.clone(),
+            mouse_enabled: self.mouse_enabled.clone(),
+            pending: pending_presses.clone(),
+            mouse_capture: MouseCapture::new(),
+            last_press_time: None,
+            seen_devices: HashSet::new(),
+        };
+
+        self.listener = Some(thread::spawn(move || {
+            let mut backoff = Backoff::new();
+            loop {
+                let started = Instant::now();
+                let result = make_source().and_then(|source| {
+                    println!("使用输入源: {}", source.name());
+                    status.running(source.name(), !enabled.load(Ordering::Relaxed));
+                    // 每次（重新）启动都从干净的按键状态开始
+                    let mut handler = handler.fresh();
+                    source.run(Box::new(move |event| handler.handle(event)), stop.clone())
+                });
+
+                // 输入源结束后写入剩余未松开的按键
+                let remaining: Vec<KeyboardEventRecord> = pending_presses.lock().unwrap().drain().map(|(_, r)| r).collect();
+                for record in remaining {
+                    bus.publish(BusEvent::RecordReady(record));
                 }
-                match event.event_type {
-                    EventType::KeyPress(key) => {
-                        let key_code = key_to_string(&key);
-                        let mut keys = pressed_keys.lock().unwrap();
-                        let has_modifier = keys.iter().any(|k| matches!(k.as_str(), "Tab" | "Ctrl" | "Shift" | "Alt" | "Win"));
-                        // 检查按键是否已存在
-                        let is_new_key = keys.insert(key_code.clone());
-                        // 不是新按键，不发送事件
-                        if !is_new_key {
-                            return;
-                        }
-                        if (!has_modifier) {
-                            keys.clear();
-                            keys.insert(key_code.clone());
-                        }
-                        let app_name = get_active_window_info();
-                        let mut keys_vec: Vec<_> = keys.iter().cloned().collect();
-                        let combo = keys_vec.join("+");
-                        let event = KeyboardEvent {
-                            timestamp: Local::now(),
-                            key_code: combo.clone(),
-                            app_name,
-                        };
-                        println!("{:?}", event);
-                        let _ = tx.send(event.clone());
-                        // 使用全局数据库连接
-                        let record = KeyboardEventRecord {
-                            timestamp: event.timestamp,
-                            key_code: event.key_code.clone(),
-                            app_name: event.app_name.clone(),
-                        };
-                        // 使用锁保护数据库操作
-                        if let Ok(conn) = db_conn.lock() {
-                            if let Err(e) = crate::database::insert_event(&conn, &record) {
-                                println!("插入数据库失败: {:?}", e);
-                            }
-                        }
-                        
+                bus.flush();
 
-                        if let Some(handle) = &app_handle {
-                            let result = handle.emit_to("key_popup", "key-pressed", KeyPressedPayload {
-                                key_code: combo,
-                            });
-                        }
-                    }
-                    EventType::KeyRelease(key) => {
-                        let key_code = key_to_string(&key);
-                        let mut keys = pressed_keys.lock().unwrap();
-                        keys.remove(&key_code);
-                    }
-                    _ => {}
+                let error = match result {
+                    // 收到停止信号，或有限的输入源（如回放）已结束
+                    Ok(()) => break,
+                    Err(_) if stop.load(Ordering::Relaxed) => break,
+                    Err(error) => error,
+                };
+                println!("输入源出错: {}", error);
+                status.failed(error);
+                if !restart {
+                    return;
                 }
-            }) {
-                println!("键盘监听错误: {:?}", error);
+                if started.elapsed() >= STABLE_RUN {
+                    backoff.reset();
+                }
+                if sleep_unless_stopped(&stop, backoff.next_delay()) {
+                    break;
+                }
+                status.restarting();
             }
-        });
+            status.stopped();
+        }));
 
         Ok(())
     }
 
+    // 将写入队列中的事件立即落盘，应用退出前调用
+    pub fn flush(&self) {
+        self.bus.flush();
+        if let Some(writer) = &self.writer {
+            writer.flush();
+        }
+    }
+
+    // 最近60秒的按键次数，由总线上的KPM订阅者实时统计
+    pub fn current_kpm(&self) -> f64 {
+        self.kpm_tracker.current_kpm()
+    }
+
+    pub fn activity_status(&self) -> ActivityStatus {
+        self.idle_tracker.status(Local::now())
+    }
+
+    // 设置无输入多久后视为离开（秒）
+    pub fn set_idle_timeout(&self, secs: u64) {
+        self.idle_tracker.set_timeout_secs(secs);
+    }
+
+    pub fn subscribers(&self) -> Vec<SubscriberInfo> {
+        self.bus.subscribers()
+    }
+
+    // 单独启用或停用某个事件消费者，订阅者不存在时返回错误
+    pub fn set_subscriber_enabled(&self, name: &str, enabled: bool) -> Result<(), String> {
+        if self.bus.set_enabled(name, enabled) {
+            Ok(())
+        } else {
+            Err(format!("未找到事件订阅者: {}", name))
+        }
+    }
+
+    pub fn writer_stats(&self) -> Option<WriterStats> {
+        self.writer.as_ref().map(|writer| writer.stats())
+    }
+
+    // 等待输入源结束，回放等有限输入源播放完毕后返回
+    pub fn join(&mut self) {
+        if let Some(handle) = self.listener.take() {
+            let _ = handle.join();
+        }
+    }
+
+    // 停止监听：通知输入源结束并等待监听线程退出，未松开的按键在退出前写入
     pub fn stop(&mut self) {
-        self.enabled.store(false, Ordering::Relaxed); // 暂停统计
+        self.stop_signal.store(true, Ordering::Relaxed);
+        self.join();
     }
+
+    // 暂停统计，输入源保持运行
+    pub fn pause(&mut self) {
+        self.pause_control.pause();
+    }
+
     pub fn resume(&mut self) {
+        self.pause_control.resume();
+    }
+
+    // 临时暂停，到时自动恢复
+    pub fn pause_for(&mut self, minutes: u64) {
+        self.pause_control.pause_for(minutes);
+    }
+}
+
+// 暂停与恢复，供界面命令、快捷键和临时暂停的计时器共用
+#[derive(Clone)]
+struct PauseControl {
+    enabled: Arc<AtomicBool>,
+    status: StatusReporter,
+    combo_detector: Arc<Mutex<ComboDetector>>,
+    generation: Arc<AtomicU64>, // 每次暂停或恢复加一，使之前的临时暂停计时器失效
+    timed: Arc<AtomicBool>,     // 当前是否为到时自动恢复的临时暂停
+}
+
+impl PauseControl {
+    fn pause(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.timed.store(false, Ordering::Relaxed);
+        self.enabled.store(false, Ordering::Relaxed); // 暂停统计
+        self.status.set_paused(true, None);
+    }
+
+    fn resume(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.timed.store(false, Ordering::Relaxed);
+        // 暂停期间的按键状态不可靠，恢复时重新开始跟踪
+        self.combo_detector.lock().unwrap().reset();
         self.enabled.store(true, Ordering::Relaxed); // 恢复统计
+        self.status.set_paused(false, None);
+    }
+
+    fn pause_for(&self, minutes: u64) {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        self.timed.store(true, Ordering::Relaxed);
+        self.enabled.store(false, Ordering::Relaxed);
+        self.status.set_paused(true, Some(Local::now() + chrono::Duration::minutes(minutes as i64)));
+        let control = self.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_secs(minutes * 60));
+            // 期间手动暂停或恢复过则不再自动恢复
+            if control.generation.load(Ordering::SeqCst) == generation {
+                control.resume();
+            }
+        });
     }
 }
 
-// 用于发送到前端的事件载荷
-#[derive(Clone, Serialize)]
-struct KeyPressedPayload {
-    key_code: String,
+// 输入事件处理：组合键识别、应用策略、按住时长和按键间隔，结果发布到总线
+struct InputHandler {
+    bus: Arc<EventBus>,
+    enabled: Arc<AtomicBool>,
+    pause_control: PauseControl,
+    hotkeys: Arc<Mutex<HotkeyBindings>>,
+    hotkey_listener: Arc<Mutex<Option<HotkeyListener>>>,
+    context: ContextTracker,
+    combo_detector: Arc<Mutex<ComboDetector>>,
+    app_policies: Arc<Mutex<Vec<AppPolicy>>>,
+    privacy_mode: Arc<AtomicBool>,
+    mouse_enabled: Arc<AtomicBool>,
+    pending: Arc<Mutex<HashMap<DeviceKey, KeyboardEventRecord>>>,
+    mouse_capture: MouseCapture,
+    last_press_time: Option<DateTime<Local>>, // 上一次按键时间，用于计算按键间隔
+    seen_devices: HashSet<String>, // 本次监听中已记录过的键盘设备
 }
-fn get_active_window_info() -> String {
-    unsafe {
-        let hwnd = GetForegroundWindow();
-        let mut title = [0u16; 512];
-        let mut process_name = String::new();
-
-        // 获取进程名称
-        let mut pid = 0u32;
-        windows::Win32::UI::WindowsAndMessaging::GetWindowThreadProcessId(hwnd, Some(&mut pid));
-        if pid != 0 {
-            let handle = OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, false, pid);
-            if let Ok(handle) = handle {
-                let mut path = [0u16; 260];
-                let len = GetProcessImageFileNameW(handle, &mut path) as usize;
-                if len > 0 {
-                    process_name = String::from_utf16_lossy(&path[..len]);
-                    if let Some(file_name) = process_name.split('\\').last() {
-                        process_name = file_name.to_string();
+
+impl InputHandler {
+    // 共享同样的状态，但清空本次监听的按键跟踪
+    fn fresh(&self) -> Self {
+        // 监听重启后之前的按下状态已失效
+        self.combo_detector.lock().unwrap().reset();
+        InputHandler {
+            bus: self.bus.clone(),
+            enabled: self.enabled.clone(),
+            pause_control: self.pause_control.clone(),
+            hotkeys: self.hotkeys.clone(),
+            hotkey_listener: self.hotkey_listener.clone(),
+            context: self.context.clone(),
+            combo_detector: self.combo_detector.clone(),
+            app_policies: self.app_policies.clone(),
+            privacy_mode: self.privacy_mode.clone(),
+            mouse_enabled: self.mouse_enabled.clone(),
+            pending: self.pending.clone(),
+            mouse_capture: MouseCapture::new(),
+            last_press_time: None,
+            seen_devices: HashSet::new(),
+        }
+    }
+
+    fn handle(&mut self, event: InputEvent) {
+        match event.event_type {
+            EventType::KeyPress(key) => {
+                let key_code = key_to_string(&key);
+                let device_key: DeviceKey = (event.device.as_ref().map(|device| device.id.clone()), key);
+                // 系统自动重复的按下事件不产生新记录，只累加到本次按下的重复次数上
+                let chord = match self.combo_detector.lock().unwrap().press(device_key.clone(), &key_code, event.timestamp) {
+                    Some(chord) => chord,
+                    None => {
+                        if let Some(record) = self.pending.lock().unwrap().get_mut(&device_key) {
+                            if record.key_code != MASKED_KEY_CODE {
+                                record.repeat_count += 1;
+                            }
+                        }
+                        return;
                     }
+                };
+                // 暂停期间也要识别快捷键，快捷键本身不记录
+                let action = self.hotkeys.lock().unwrap().action_for(&chord);
+                if let Some(action) = action {
+                    self.on_hotkey(action);
+                    return;
+                }
+                if !self.enabled.load(Ordering::Relaxed) {
+                    return;
+                }
+                if let Some(device) = &event.device {
+                    if self.seen_devices.insert(device.id.clone()) {
+                        self.bus.publish(BusEvent::DeviceSeen(InputDeviceRecord {
+                            device_id: device.id.clone(),
+                            name: device.name.clone(),
+                            vendor_id: device.vendor_id,
+                            product_id: device.product_id,
+                            last_seen: event.timestamp,
+                        }));
+                    }
+                }
+                let device_id = event.device.as_ref().map(|device| device.id.clone());
+                let app_name = event.app_name.unwrap_or_else(active_app_name);
+                // 应用策略在发布到总线之前生效，被忽略或脱敏的按键不会到达任何消费者
+                let mode = mode_for_app(&self.app_policies.lock().unwrap(), &app_name);
+                let (event, flight_ms, raw_key, scan_code) = match mode {
+                    RecordingMode::Ignore => return,
+                    // 只保留一次按键的计数，按键内容与节奏均不保存
+                    RecordingMode::CountsOnly => (KeyboardEvent {
+                        timestamp: event.timestamp,
+                        key_code: MASKED_KEY_CODE.to_string(),
+                        app_name,
+                        chord: Chord {
+                            modifiers: Vec::new(),
+                            key: Some(MASKED_KEY_CODE.to_string()),
+                        },
+                    }, None, None, None),
+                    RecordingMode::Full => {
+                        let flight_ms = self.last_press_time
+                            .map(|last| event.timestamp.signed_duration_since(last).num_milliseconds().max(0))
+                            .filter(|ms| *ms <= MAX_FLIGHT_MS);
+                        // 隐私模式下字符键等按键之后的按键不记录间隔，避免从相邻按键推算它们的时间
+                        self.last_press_time = if self.privacy_mode.load(Ordering::Relaxed) && is_private_key(&chord.label()) {
+                            None
+                        } else {
+                            Some(event.timestamp)
+                        };
+                        (KeyboardEvent {
+                            timestamp: event.timestamp,
+                            key_code: chord.label(),
+                            app_name,
+                            chord,
+                        }, flight_ms, Some(key_identity(&key)), event.code)
+                    }
+                };
+                // 脱敏的按键同样不保存设备和上下文，写入时再按分钟合并（见 DatabaseSubscriber）
+                let (device_id, context_tag) = if event.key_code == MASKED_KEY_CODE {
+                    (None, None)
+                } else {
+                    (device_id, self.context.current())
+                };
+                let record = KeyboardEventRecord {
+                    timestamp: event.timestamp,
+                    key_code: event.key_code.clone(),
+                    app_name: event.app_name.clone(),
+                    hold_ms: None,
+                    flight_ms,
+                    raw_key,
+                    scan_code,
+                    repeat_count: 0,
+                    device_id,
+                    context_tag,
+                };
+                let mut finished = Vec::new();
+                {
+                    let mut pending = self.pending.lock().unwrap();
+                    // 长时间未收到松开事件的按键（如焦点切换时丢失）直接写入，按住时长留空
+                    let stale_keys: Vec<DeviceKey> = pending.iter()
+                        .filter(|(_, r)| event.timestamp.signed_duration_since(r.timestamp).num_seconds() >= STALE_PRESS_SECS)
+                        .map(|(k, _)| k.clone())
+                        .collect();
+                    for stale_key in stale_keys {
+                        finished.extend(pending.remove(&stale_key));
+                    }
+                    finished.extend(pending.insert(device_key, record));
+                }
+
+                self.bus.publish(BusEvent::KeyPressed(event));
+                for record in finished {
+                    self.bus.publish(BusEvent::RecordReady(record));
+                }
+            }
+            EventType::KeyRelease(key) => {
+                let device_key: DeviceKey = (event.device.as_ref().map(|device| device.id.clone()), key);
+                self.combo_detector.lock().unwrap().release(&device_key);
+                // 松开时补全按住时长并写入
+                let finished = self.pending.lock().unwrap().remove(&device_key);
+                if let Some(mut record) = finished {
+                    if record.key_code != MASKED_KEY_CODE {
+                        let hold = event.timestamp.signed_duration_since(record.timestamp).num_milliseconds();
+                        record.hold_ms = Some(hold.max(0));
+                    }
+                    self.bus.publish(BusEvent::RecordReady(record));
+                }
+            }
+            EventType::ButtonPress(_) | EventType::MouseMove { .. } | EventType::Wheel { .. } => {
+                if !self.enabled.load(Ordering::Relaxed) || !self.mouse_enabled.load(Ordering::Relaxed) {
+                    self.mouse_capture.reset();
+                    return;
+                }
+                let Some(mut mouse_event) = self.mouse_capture.process(&event) else {
+                    return;
+                };
+                // 与按键相同，应用策略在发布到总线之前生效
+                match mode_for_app(&self.app_policies.lock().unwrap(), &mouse_event.app_name) {
+                    RecordingMode::Ignore => return,
+                    RecordingMode::CountsOnly => mouse_event.app_name = MASKED_APP_NAME.to_string(),
+                    RecordingMode::Full => {}
+                }
+                for mouse_event in self.mouse_capture.coalesce(mouse_event) {
+                    self.bus.publish(BusEvent::Mouse(mouse_event));
                 }
             }
+            _ => {}
         }
+    }
 
-        process_name
+    fn on_hotkey(&mut self, action: HotkeyAction) {
+        // 快捷键的修饰键已作为按下记录等待松开，一并丢弃
+        self.pending.lock().unwrap().retain(|(_, key), _| Modifier::from_key(key).is_none());
+        let recording = self.enabled.load(Ordering::Relaxed);
+        match action {
+            HotkeyAction::TogglePause => {
+                if recording {
+                    self.pause_control.pause();
+                } else {
+                    self.pause_control.resume();
+                }
+                println!("快捷键{}录制", if recording { "暂停" } else { "恢复" });
+                // 与界面上的开始/停止一样保存录制状态
+                if let Some(listener) = self.hotkey_listener.lock().unwrap().as_ref() {
+                    listener(!recording);
+                }
+            }
+            // 临时暂停不保存录制状态；临时暂停期间再按一次重新计时，手动暂停期间不起作用
+            HotkeyAction::Incognito(minutes) if !recording => {
+                if self.pause_control.timed.load(Ordering::Relaxed) {
+                    self.pause_control.pause_for(minutes);
+                    println!("快捷键重新开始临时暂停 {} 分钟", minutes);
+                }
+            }
+            HotkeyAction::Incognito(minutes) => {
+                self.pause_control.pause_for(minutes);
+                println!("快捷键临时暂停 {} 分钟", minutes);
+            }
+        }
     }
 }
 
+// 超过该时长仍未松开的按键视为丢失了松开事件（秒）
+const STALE_PRESS_SECS: i64 = 30;
+
+// 物理按键的可读名称：保留左右修饰键、小键盘等差异，按键本身以平台按键码（scan_code）区分
+fn key_identity(key: &rdev::Key) -> String {
+    format!("{:?}", key)
+}
+
 fn key_to_string(key: &rdev::Key) -> String {
     let key_str = format!("{:?}", key);
     match key_str.as_str() {
@@ -194,7 +604,6 @@ fn key_to_string(key: &rdev::Key) -> String {
         "ShiftLeft" | "ShiftRight" => "Shift".to_string(),
         "MetaLeft" | "MetaRight" => "Win".to_string(),
         "Alt" | "AltGr" => "Alt".to_string(),
-        "AltGr" => "AltGr".to_string(),
         "Enter" | "Return" => "Enter".to_string(),
         "Space" => "Space".to_string(),
         "Tab" => "Tab".to_string(),
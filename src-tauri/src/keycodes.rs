@@ -0,0 +1,241 @@
+// 平台按键码：Windows 为虚拟键码，Linux 为内核按键码（X11 按键码减 8），macOS 为 CGKeyCode
+// rdev 事件只带按键枚举，这里按 rdev 各平台的对应表换算回按键码，作为按键的物理标识保存
+use rdev::{EventType, Key};
+
+pub fn event_code(event_type: &EventType) -> Option<u32> {
+    match event_type {
+        EventType::KeyPress(key) | EventType::KeyRelease(key) => platform_code(*key),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn platform_code(key: Key) -> Option<u32> {
+    match key {
+        Key::Unknown(code) => code.checked_sub(8),
+        _ => crate::evdev::code_from_key(key).map(u32::from),
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn platform_code(key: Key) -> Option<u32> {
+    match key {
+        Key::Alt => Some(164),
+        Key::AltGr => Some(165),
+        Key::Backspace => Some(8),
+        Key::CapsLock => Some(20),
+        Key::ControlLeft => Some(162),
+        Key::ControlRight => Some(163),
+        Key::Delete => Some(46),
+        Key::DownArrow => Some(40),
+        Key::End => Some(35),
+        Key::Escape => Some(27),
+        Key::F1 => Some(112),
+        Key::F2 => Some(113),
+        Key::F3 => Some(114),
+        Key::F4 => Some(115),
+        Key::F5 => Some(116),
+        Key::F6 => Some(117),
+        Key::F7 => Some(118),
+        Key::F8 => Some(119),
+        Key::F9 => Some(120),
+        Key::F10 => Some(121),
+        Key::F11 => Some(122),
+        Key::F12 => Some(123),
+        Key::Home => Some(36),
+        Key::LeftArrow => Some(37),
+        Key::MetaLeft => Some(91),
+        Key::PageDown => Some(34),
+        Key::PageUp => Some(33),
+        Key::Return => Some(13),
+        Key::RightArrow => Some(39),
+        Key::ShiftLeft => Some(160),
+        Key::ShiftRight => Some(161),
+        Key::Space => Some(32),
+        Key::Tab => Some(9),
+        Key::UpArrow => Some(38),
+        Key::PrintScreen => Some(44),
+        Key::ScrollLock => Some(145),
+        Key::Pause => Some(19),
+        Key::NumLock => Some(144),
+        Key::BackQuote => Some(192),
+        Key::Num1 => Some(49),
+        Key::Num2 => Some(50),
+        Key::Num3 => Some(51),
+        Key::Num4 => Some(52),
+        Key::Num5 => Some(53),
+        Key::Num6 => Some(54),
+        Key::Num7 => Some(55),
+        Key::Num8 => Some(56),
+        Key::Num9 => Some(57),
+        Key::Num0 => Some(48),
+        Key::Minus => Some(189),
+        Key::Equal => Some(187),
+        Key::KeyQ => Some(81),
+        Key::KeyW => Some(87),
+        Key::KeyE => Some(69),
+        Key::KeyR => Some(82),
+        Key::KeyT => Some(84),
+        Key::KeyY => Some(89),
+        Key::KeyU => Some(85),
+        Key::KeyI => Some(73),
+        Key::KeyO => Some(79),
+        Key::KeyP => Some(80),
+        Key::LeftBracket => Some(219),
+        Key::RightBracket => Some(221),
+        Key::KeyA => Some(65),
+        Key::KeyS => Some(83),
+        Key::KeyD => Some(68),
+        Key::KeyF => Some(70),
+        Key::KeyG => Some(71),
+        Key::KeyH => Some(72),
+        Key::KeyJ => Some(74),
+        Key::KeyK => Some(75),
+        Key::KeyL => Some(76),
+        Key::SemiColon => Some(186),
+        Key::Quote => Some(222),
+        Key::BackSlash => Some(220),
+        Key::IntlBackslash => Some(226),
+        Key::KeyZ => Some(90),
+        Key::KeyX => Some(88),
+        Key::KeyC => Some(67),
+        Key::KeyV => Some(86),
+        Key::KeyB => Some(66),
+        Key::KeyN => Some(78),
+        Key::KeyM => Some(77),
+        Key::Comma => Some(188),
+        Key::Dot => Some(190),
+        Key::Slash => Some(191),
+        Key::Insert => Some(45),
+        Key::KpMinus => Some(109),
+        Key::KpPlus => Some(107),
+        Key::KpMultiply => Some(106),
+        Key::KpDivide => Some(111),
+        Key::Kp0 => Some(96),
+        Key::Kp1 => Some(97),
+        Key::Kp2 => Some(98),
+        Key::Kp3 => Some(99),
+        Key::Kp4 => Some(100),
+        Key::Kp5 => Some(101),
+        Key::Kp6 => Some(102),
+        Key::Kp7 => Some(103),
+        Key::Kp8 => Some(104),
+        Key::Kp9 => Some(105),
+        Key::KpDelete => Some(110),
+        Key::Unknown(code) => Some(code),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn platform_code(key: Key) -> Option<u32> {
+    match key {
+        Key::Alt => Some(58),
+        Key::AltGr => Some(61),
+        Key::Backspace => Some(51),
+        Key::CapsLock => Some(57),
+        Key::ControlLeft => Some(59),
+        Key::ControlRight => Some(62),
+        Key::DownArrow => Some(125),
+        Key::Escape => Some(53),
+        Key::F1 => Some(122),
+        Key::F2 => Some(120),
+        Key::F3 => Some(99),
+        Key::F4 => Some(118),
+        Key::F5 => Some(96),
+        Key::F6 => Some(97),
+        Key::F7 => Some(98),
+        Key::F8 => Some(100),
+        Key::F9 => Some(101),
+        Key::F10 => Some(109),
+        Key::F11 => Some(103),
+        Key::F12 => Some(111),
+        Key::Function => Some(63),
+        Key::LeftArrow => Some(123),
+        Key::MetaLeft => Some(55),
+        Key::MetaRight => Some(54),
+        Key::Return => Some(36),
+        Key::RightArrow => Some(124),
+        Key::ShiftLeft => Some(56),
+        Key::ShiftRight => Some(60),
+        Key::Space => Some(49),
+        Key::Tab => Some(48),
+        Key::UpArrow => Some(126),
+        Key::BackQuote => Some(50),
+        Key::Num1 => Some(18),
+        Key::Num2 => Some(19),
+        Key::Num3 => Some(20),
+        Key::Num4 => Some(21),
+        Key::Num5 => Some(23),
+        Key::Num6 => Some(22),
+        Key::Num7 => Some(26),
+        Key::Num8 => Some(28),
+        Key::Num9 => Some(25),
+        Key::Num0 => Some(29),
+        Key::Minus => Some(27),
+        Key::Equal => Some(24),
+        Key::KeyQ => Some(12),
+        Key::KeyW => Some(13),
+        Key::KeyE => Some(14),
+        Key::KeyR => Some(15),
+        Key::KeyT => Some(17),
+        Key::KeyY => Some(16),
+        Key::KeyU => Some(32),
+        Key::KeyI => Some(34),
+        Key::KeyO => Some(31),
+        Key::KeyP => Some(35),
+        Key::LeftBracket => Some(33),
+        Key::RightBracket => Some(30),
+        Key::KeyA => Some(0),
+        Key::KeyS => Some(1),
+        Key::KeyD => Some(2),
+        Key::KeyF => Some(3),
+        Key::KeyG => Some(5),
+        Key::KeyH => Some(4),
+        Key::KeyJ => Some(38),
+        Key::KeyK => Some(40),
+        Key::KeyL => Some(37),
+        Key::SemiColon => Some(41),
+        Key::Quote => Some(39),
+        Key::BackSlash => Some(42),
+        Key::KeyZ => Some(6),
+        Key::KeyX => Some(7),
+        Key::KeyC => Some(8),
+        Key::KeyV => Some(9),
+        Key::KeyB => Some(11),
+        Key::KeyN => Some(45),
+        Key::KeyM => Some(46),
+        Key::Comma => Some(43),
+        Key::Dot => Some(47),
+        Key::Slash => Some(44),
+        Key::Unknown(code) => Some(code),
+        _ => None,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+pub fn platform_code(_key: Key) -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn maps_keys_to_linux_key_codes() {
+        let cases: &[(&str, EventType, Option<u32>)] = &[
+            ("字母键", EventType::KeyPress(Key::KeyA), Some(30)),
+            ("左右修饰键不同", EventType::KeyPress(Key::ShiftLeft), Some(42)),
+            ("右修饰键", EventType::KeyRelease(Key::ShiftRight), Some(54)),
+            ("小键盘", EventType::KeyPress(Key::Kp5), Some(76)),
+            ("未知按键为 X11 按键码", EventType::KeyPress(Key::Unknown(191)), Some(183)),
+            ("无效的 X11 按键码", EventType::KeyPress(Key::Unknown(3)), None),
+            ("非按键事件", EventType::MouseMove { x: 1.0, y: 2.0 }, None),
+        ];
+        for (name, event_type, expected) in cases {
+            assert_eq!(event_code(event_type), *expected, "{}", name);
+        }
+    }
+}
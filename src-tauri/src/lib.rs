@@ -1,50 +1,44 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 pub mod analyzer;
+pub mod apps;
 pub mod keyboard;
+pub mod input;
+pub mod keycodes;
+#[cfg(target_os = "linux")]
+pub mod evdev;
+pub mod foreground;
+pub mod combo;
+pub mod context;
+pub mod hotkey;
+pub mod bus;
+pub mod policy;
+pub mod privacy;
+pub mod idle;
+pub mod mouse;
+pub mod supervisor;
+pub mod writer;
+pub mod migrations;
+pub mod rollup;
+pub mod retention;
 pub mod database;
-pub use analyzer::{DataAnalyzer, KeyStats};
-use crate::database::{init_db, insert_event, KeyboardEventRecord};
-use std::path::PathBuf;
-use tauri::Manager;
-use chrono::{DateTime, Local, Duration, NaiveDateTime, TimeZone, Datelike, Weekday};
+pub mod store;
+pub mod backup;
+#[cfg(test)]
+mod test_support;
+pub use analyzer::{ContextStats, DataAnalyzer, DeviceStats, FlightTimeReport, KeyStats, KeyView, MouseStats};
+use chrono::{DateTime, Local, Duration, TimeZone};
 
-pub fn get_key_stats(app: tauri::AppHandle, time_range: &str) -> Result<KeyStats, String> {
-    // 使用应用数据目录来获取数据库路径
-    let app_dir = app.path().app_data_dir()
-        .map_err(|e| format!("无法获取应用数据目录: {}", e))?;
-    
-    let db_path = app_dir.join("keyboard_events.db");
-    let db_path_str = db_path.to_str()
-        .ok_or_else(|| "无法将路径转换为字符串".to_string())?;
-    
-    println!("使用数据库路径: {}", db_path_str);
-    
-    let conn = match crate::database::init_db(db_path_str) {
-        Ok(c) => c,
-        Err(e) => return Err(format!("数据库连接失败: {}", e)),
-    };
-    
-    let analyzer = DataAnalyzer::new(conn);
+// 以下统计函数使用调用方传入的连接，通常是 Store 借出的只读连接
+// app_rules 为用户自定义的应用显示名称和分类规则
+pub fn get_key_stats(conn: &rusqlite::Connection, time_range: &str, app_rules: &[apps::AppRule]) -> Result<KeyStats, String> {
+    let analyzer = DataAnalyzer::new(conn).with_app_rules(app_rules);
     match analyzer.get_stats(time_range) {
         Ok(stats) => Ok(stats),
         Err(e) => Err(format!("获取统计数据失败: {}", e)),
     }
 }
 
-pub fn get_current_kpm(app: tauri::AppHandle) -> Result<f64, String> {
-    // 使用应用数据目录来获取数据库路径
-    let app_dir = app.path().app_data_dir()
-        .map_err(|e| format!("无法获取应用数据目录: {}", e))?;
-    
-    let db_path = app_dir.join("keyboard_events.db");
-    let db_path_str = db_path.to_str()
-        .ok_or_else(|| "无法将路径转换为字符串".to_string())?;
-    
-    let conn = match crate::database::init_db(db_path_str) {
-        Ok(c) => c,
-        Err(e) => return Err(format!("数据库连接失败: {}", e)),
-    };
-    
+pub fn get_current_kpm(conn: &rusqlite::Connection) -> Result<f64, String> {
     let analyzer = DataAnalyzer::new(conn);
     match analyzer.calculate_current_kpm() {
         Ok(kpm) => Ok(kpm),
@@ -52,6 +46,48 @@ pub fn get_current_kpm(app: tauri::AppHandle) -> Result<f64, String> {
     }
 }
 
+pub fn get_flight_time_report(conn: &rusqlite::Connection, time_range: &str) -> Result<FlightTimeReport, String> {
+    let analyzer = DataAnalyzer::new(conn);
+    match analyzer.get_flight_time_report(time_range) {
+        Ok(report) => Ok(report),
+        Err(e) => Err(format!("获取按键间隔统计失败: {}", e)),
+    }
+}
+
+pub fn get_mouse_stats(conn: &rusqlite::Connection, time_range: &str) -> Result<MouseStats, String> {
+    let analyzer = DataAnalyzer::new(conn);
+    match analyzer.get_mouse_stats(time_range) {
+        Ok(stats) => Ok(stats),
+        Err(e) => Err(format!("获取鼠标统计失败: {}", e)),
+    }
+}
+
+pub fn get_device_stats(conn: &rusqlite::Connection, time_range: &str) -> Result<Vec<DeviceStats>, String> {
+    let analyzer = DataAnalyzer::new(conn);
+    match analyzer.get_device_stats(time_range) {
+        Ok(stats) => Ok(stats),
+        Err(e) => Err(format!("获取键盘设备统计失败: {}", e)),
+    }
+}
+
+pub fn get_context_stats(conn: &rusqlite::Connection, time_range: &str) -> Result<Vec<ContextStats>, String> {
+    let analyzer = DataAnalyzer::new(conn);
+    match analyzer.get_context_stats(time_range) {
+        Ok(stats) => Ok(stats),
+        Err(e) => Err(format!("获取上下文统计失败: {}", e)),
+    }
+}
+
+pub fn get_key_usage(conn: &rusqlite::Connection, time_range: &str, view: &str) -> Result<Vec<(String, u64)>, String> {
+    let view = KeyView::parse(view).ok_or_else(|| "无效的按键视图".to_string())?;
+    
+    let analyzer = DataAnalyzer::new(conn);
+    match analyzer.get_key_usage(time_range, view, 100) {
+        Ok(usage) => Ok(usage),
+        Err(e) => Err(format!("获取按键使用统计失败: {}", e)),
+    }
+}
+
 // 获取根据最早数据记录调整后的时间范围
 pub fn get_adjusted_time_range(conn: &rusqlite::Connection, time_range: &str) -> Result<(DateTime<Local>, DateTime<Local>), String> {
     let now = Local::now();
@@ -2,20 +2,53 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 pub mod keyboard;
+pub mod input;
+pub mod keycodes;
+#[cfg(target_os = "linux")]
+pub mod evdev;
+pub mod foreground;
+pub mod combo;
+pub mod context;
+pub mod hotkey;
+pub mod bus;
+pub mod policy;
+pub mod privacy;
+pub mod idle;
+pub mod mouse;
+pub mod supervisor;
+pub mod writer;
+pub mod migrations;
+pub mod rollup;
+pub mod retention;
 pub mod database;
+pub mod store;
+pub mod backup;
+#[cfg(test)]
+mod test_support;
 mod tray;
 mod config;
 mod logger;
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use std::fs::File;
 use std::io::Write;
-use keyboard_statistics_lib::analyzer::KeyStats;
+use keyboard_statistics_lib::analyzer::{ContextStats, DeviceStats, FlightTimeReport, KeyStats, MouseStats};
 use chrono::{Local, Duration, TimeZone};
 use tauri::{WindowEvent, Manager};
 use crate::keyboard::KeyboardMonitor;
+use crate::writer::WriterStats;
+use crate::bus::SubscriberInfo;
+use crate::hotkey::HotkeySettings;
+use crate::retention::{RetentionReport, RetentionSettings};
+use crate::backup::{BackupInfo, BackupKind, BackupSettings};
+use crate::store::{SqliteStore, Store};
+use crate::context::ContextStatus;
+use crate::policy::AppPolicy;
+use keyboard_statistics_lib::apps::AppRule;
+use crate::idle::ActivityStatus;
+use crate::supervisor::MonitorStatus;
 use crate::config::ConfigManager;
 use crate::logger::{Logger, LogLevel};
 use tauri_plugin_dialog::DialogExt;
@@ -25,13 +58,75 @@ use serde_json;
 // 定义get_key_stats函数
 #[tauri::command]
 fn get_key_stats(app: tauri::AppHandle, time_range: &str) -> Result<KeyStats, String> {
-    keyboard_statistics_lib::get_key_stats(app, time_range)
+    let state = app.state::<AppState>();
+    let app_rules = state.config_manager.get_config().app_rules.clone();
+    let store = state.store()?;
+    let conn = store.read().map_err(|e| format!("数据库连接失败: {}", e))?;
+    keyboard_statistics_lib::get_key_stats(&conn, time_range, &app_rules)
 }
 
-// 添加获取当前KPM命令
+// 添加获取当前KPM命令，监听运行时直接使用内存中的实时统计
 #[tauri::command]
 fn get_current_kpm(app: tauri::AppHandle) -> Result<f64, String> {
-    keyboard_statistics_lib::get_current_kpm(app)
+    {
+        let state = app.state::<AppState>();
+        let monitor = state.keyboard_monitor.lock().unwrap();
+        if monitor.is_running() {
+            return Ok(monitor.current_kpm());
+        }
+    }
+    let store = app.state::<AppState>().store()?;
+    let conn = store.read().map_err(|e| format!("数据库连接失败: {}", e))?;
+    keyboard_statistics_lib::get_current_kpm(&conn)
+}
+
+// 获取按键间隔（节奏）分布
+#[tauri::command]
+fn get_flight_time_report(app: tauri::AppHandle, time_range: &str) -> Result<FlightTimeReport, String> {
+    let store = app.state::<AppState>().store()?;
+    let conn = store.read().map_err(|e| format!("数据库连接失败: {}", e))?;
+    keyboard_statistics_lib::get_flight_time_report(&conn, time_range)
+}
+
+// 获取鼠标统计
+#[tauri::command]
+fn get_mouse_stats(app: tauri::AppHandle, time_range: &str) -> Result<MouseStats, String> {
+    let store = app.state::<AppState>().store()?;
+    let conn = store.read().map_err(|e| format!("数据库连接失败: {}", e))?;
+    keyboard_statistics_lib::get_mouse_stats(&conn, time_range)
+}
+
+// 获取按键盘设备区分的统计
+#[tauri::command]
+fn get_device_stats(app: tauri::AppHandle, time_range: &str) -> Result<Vec<DeviceStats>, String> {
+    let store = app.state::<AppState>().store()?;
+    let conn = store.read().map_err(|e| format!("数据库连接失败: {}", e))?;
+    keyboard_statistics_lib::get_device_stats(&conn, time_range)
+}
+
+// 获取按上下文标签（项目、分支、任务等）区分的统计
+#[tauri::command]
+fn get_context_stats(app: tauri::AppHandle, time_range: &str) -> Result<Vec<ContextStats>, String> {
+    let store = app.state::<AppState>().store()?;
+    let conn = store.read().map_err(|e| format!("数据库连接失败: {}", e))?;
+    keyboard_statistics_lib::get_context_stats(&conn, time_range)
+}
+
+// 获取上下文接口的地址和当前标签
+#[tauri::command]
+fn get_context_status(app: tauri::AppHandle) -> Result<ContextStatus, String> {
+    let state = app.state::<AppState>();
+    let endpoint = state.context_endpoint.clone()?;
+    let tag = state.keyboard_monitor.lock().unwrap().context().current();
+    Ok(ContextStatus { endpoint, tag })
+}
+
+// 获取按键使用统计，view 为 "logical"（按显示名称）或 "physical"（区分左右修饰键、小键盘）
+#[tauri::command]
+fn get_key_usage(app: tauri::AppHandle, time_range: &str, view: &str) -> Result<Vec<(String, u64)>, String> {
+    let store = app.state::<AppState>().store()?;
+    let conn = store.read().map_err(|e| format!("数据库连接失败: {}", e))?;
+    keyboard_statistics_lib::get_key_usage(&conn, time_range, view)
 }
 
 // 添加导出数据命令
@@ -40,12 +135,8 @@ async fn export_data(app: tauri::AppHandle, format: &str, range: &str, type_str:
     let app_dir = app.path().app_data_dir()
         .map_err(|e| format!("无法获取应用数据目录: {}", e))?;
     
-    let db_path = app_dir.join("keyboard_events.db");
-    let db_path_str = db_path.to_str()
-        .ok_or_else(|| "无法将路径转换为字符串".to_string())?;
-    
-    let conn = database::init_db(db_path_str)
-        .map_err(|e| format!("数据库连接失败: {}", e))?;
+    let store = app.state::<AppState>().store()?;
+    let conn = store.read().map_err(|e| format!("数据库连接失败: {}", e))?;
     
     // 使用辅助函数获取调整后的时间范围
     let (start_time, end_time) = keyboard_statistics_lib::get_adjusted_time_range(&conn, range)?;
@@ -96,15 +187,8 @@ async fn export_data(app: tauri::AppHandle, format: &str, range: &str, type_str:
 // 添加删除数据命令
 #[tauri::command]
 async fn delete_data(app: tauri::AppHandle, range: &str) -> Result<String, String> {
-    let app_dir = app.path().app_data_dir()
-        .map_err(|e| format!("无法获取应用数据目录: {}", e))?;
-    
-    let db_path = app_dir.join("keyboard_events.db");
-    let db_path_str = db_path.to_str()
-        .ok_or_else(|| "无法将路径转换为字符串".to_string())?;
-    
-    let mut conn = database::init_db(db_path_str)
-        .map_err(|e| format!("数据库连接失败: {}", e))?;
+    let store = app.state::<AppState>().store()?;
+    let mut conn = store.write().map_err(|e| format!("数据库连接失败: {}", e))?;
     
     // 使用辅助函数获取调整后的时间范围
     let (start_time, end_time) = keyboard_statistics_lib::get_adjusted_time_range(&conn, range)?;
@@ -119,15 +203,8 @@ async fn delete_data(app: tauri::AppHandle, range: &str) -> Result<String, Strin
 // 添加清除全部数据命令
 #[tauri::command]
 async fn clear_all_data(app: tauri::AppHandle) -> Result<String, String> {
-    let app_dir = app.path().app_data_dir()
-        .map_err(|e| format!("无法获取应用数据目录: {}", e))?;
-    
-    let db_path = app_dir.join("keyboard_events.db");
-    let db_path_str = db_path.to_str()
-        .ok_or_else(|| "无法将路径转换为字符串".to_string())?;
-    
-    let mut conn = database::init_db(db_path_str)
-        .map_err(|e| format!("数据库连接失败: {}", e))?;
+    let store = app.state::<AppState>().store()?;
+    let mut conn = store.write().map_err(|e| format!("数据库连接失败: {}", e))?;
     
     database::clear_all_data(&mut conn)
         .map_err(|e| format!("清除所有数据失败: {}", e))?;
@@ -135,10 +212,162 @@ async fn clear_all_data(app: tauri::AppHandle) -> Result<String, String> {
     Ok("所有数据已清除".to_string())
 }
 
+// 按原始按键记录重新生成统计汇总表
+#[tauri::command]
+async fn rebuild_rollups(app: tauri::AppHandle) -> Result<String, String> {
+    let store = app.state::<AppState>().store()?;
+    let mut conn = store.write().map_err(|e| format!("数据库连接失败: {}", e))?;
+    
+    let count = rollup::rebuild_rollups(&mut conn)
+        .map_err(|e| format!("重建统计汇总失败: {}", e))?;
+    
+    Ok(format!("已按 {} 条按键记录重建统计汇总", count))
+}
+
+// 按配置的保留策略清理旧数据，设置变更后和后台维护线程中调用
+fn run_retention(app: &tauri::AppHandle) -> Result<RetentionReport, String> {
+    let state = app.state::<AppState>();
+    let settings = state.config_manager.get_config().retention.clone();
+    let store = state.store()?;
+    
+    retention::apply_retention(store.as_ref(), &settings, Local::now())
+        .map_err(|e| format!("清理旧数据失败: {}", e))
+}
+
+#[tauri::command]
+fn get_retention(app: tauri::AppHandle) -> RetentionSettings {
+    let state = app.state::<AppState>();
+    let config = state.config_manager.get_config();
+    config.retention.clone()
+}
+
+// 保存数据保留策略并立即清理一次
+#[tauri::command]
+async fn set_retention(app: tauri::AppHandle, settings: RetentionSettings) -> Result<String, String> {
+    settings.validate()?;
+    {
+        let state = app.state::<AppState>();
+        {
+            let mut config = state.config_manager.get_config();
+            config.retention = settings;
+        }
+        state.save_config()?;
+    }
+    let report = run_retention(&app)?;
+    Ok(format!(
+        "已清理 {} 条原始记录、{} 条分钟汇总、{} 条小时汇总",
+        report.raw_events, report.minute_buckets, report.hour_buckets
+    ))
+}
+
+// 后台定期按保留策略清理，启动后稍等片刻再执行，避免和启动时的初始化抢占数据库
+fn spawn_retention_worker(app: tauri::AppHandle) {
+    std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_secs(60));
+        loop {
+            match run_retention(&app) {
+                Ok(report) if report.raw_events + report.minute_buckets + report.hour_buckets > 0 => {
+                    let _ = Logger::info("main", &format!(
+                        "已清理过期数据: {} 条原始记录、{} 条分钟汇总、{} 条小时汇总",
+                        report.raw_events, report.minute_buckets, report.hour_buckets
+                    ));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    let _ = Logger::error("main", &e);
+                }
+            }
+            std::thread::sleep(retention::MAINTENANCE_INTERVAL);
+        }
+    });
+}
+
+// 备份文件所在的目录
+fn get_backup_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app.path().app_data_dir()
+        .map_err(|e| format!("无法获取应用数据目录: {}", e))?;
+    Ok(backup::backup_dir(&app_dir))
+}
+
+// 立即备份数据库，备份期间继续记录按键
+#[tauri::command]
+async fn create_backup(app: tauri::AppHandle) -> Result<BackupInfo, String> {
+    let store = app.state::<AppState>().store()?;
+    let dir = get_backup_dir(&app)?;
+    backup::create_backup(store.as_ref(), &dir, BackupKind::Manual, Local::now())
+}
+
+// 列出已有的备份，最新的在前
+#[tauri::command]
+async fn list_backups(app: tauri::AppHandle) -> Result<Vec<BackupInfo>, String> {
+    let dir = get_backup_dir(&app)?;
+    backup::list_backups(&dir)
+}
+
+// 用选中的备份替换当前数据，恢复前的数据另存为一份备份
+#[tauri::command]
+async fn restore_backup(app: tauri::AppHandle, file_name: String) -> Result<String, String> {
+    let store = app.state::<AppState>().store()?;
+    let dir = get_backup_dir(&app)?;
+    let current = backup::restore_backup(store.as_ref(), &dir, &file_name, Local::now())?;
+    let _ = Logger::info("main", &format!("已从备份 {} 恢复数据，恢复前的数据保存在 {}", file_name, current.file_name));
+    Ok(format!("已恢复备份 {}，恢复前的数据已保存为 {}", file_name, current.file_name))
+}
+
+#[tauri::command]
+fn get_backup_settings(app: tauri::AppHandle) -> BackupSettings {
+    let state = app.state::<AppState>();
+    let config = state.config_manager.get_config();
+    config.backup.clone()
+}
+
+// 保存自动备份设置，后台线程下次检查时按新设置执行
+#[tauri::command]
+fn set_backup_settings(app: tauri::AppHandle, settings: BackupSettings) -> Result<(), String> {
+    settings.validate()?;
+    let state = app.state::<AppState>();
+    {
+        let mut config = state.config_manager.get_config();
+        config.backup = settings;
+    }
+    state.save_config()
+}
+
+// 按自动备份设置检查是否到期，到期时备份并删除超出份数的旧备份
+fn run_scheduled_backup(app: &tauri::AppHandle) -> Result<Option<BackupInfo>, String> {
+    let state = app.state::<AppState>();
+    let settings = state.config_manager.get_config().backup.clone();
+    let store = state.store()?;
+    let dir = get_backup_dir(app)?;
+    
+    backup::run_scheduled_backup(store.as_ref(), &dir, &settings, Local::now())
+}
+
+// 后台定期检查是否需要自动备份，启动后稍等片刻再执行，避免和启动时的初始化抢占数据库
+fn spawn_backup_worker(app: tauri::AppHandle) {
+    std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_secs(120));
+        loop {
+            match run_scheduled_backup(&app) {
+                Ok(Some(info)) => {
+                    let _ = Logger::info("main", &format!("已自动备份数据库: {}", info.file_name));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    let _ = Logger::error("main", &format!("自动备份失败: {}", e));
+                }
+            }
+            std::thread::sleep(backup::CHECK_INTERVAL);
+        }
+    });
+}
+
 // 使用Mutex包装配置，以便在程序运行时修改
 struct AppState {
     config_manager: ConfigManager,
     keyboard_monitor: Mutex<KeyboardMonitor>,  // 添加键盘监听器
+    context_endpoint: Result<String, String>, // 上下文接口的地址，启动失败时为错误原因
+    store: Result<Arc<dyn Store>, String>, // 共用的数据库，打开失败时为错误原因
 }
 
 // 新增：获取当前录制状态
@@ -148,6 +377,239 @@ fn get_recording_status(app: tauri::AppHandle) -> bool {
     let config = state.config_manager.get_config();
     config.recording_enabled
 }
+// 获取监听器的生命周期状态，状态变化时还会发送 monitor-status 事件
+#[tauri::command]
+fn get_monitor_status(app: tauri::AppHandle) -> MonitorStatus {
+    let state = app.state::<AppState>();
+    let monitor = state.keyboard_monitor.lock().unwrap();
+    monitor.status()
+}
+
+// 获取数据库写入队列的计数
+#[tauri::command]
+fn get_writer_stats(app: tauri::AppHandle) -> Option<WriterStats> {
+    let state = app.state::<AppState>();
+    let monitor = state.keyboard_monitor.lock().unwrap();
+    monitor.writer_stats()
+}
+
+// 获取按键事件的消费者及其启用状态
+#[tauri::command]
+fn get_event_subscribers(app: tauri::AppHandle) -> Vec<SubscriberInfo> {
+    let state = app.state::<AppState>();
+    let monitor = state.keyboard_monitor.lock().unwrap();
+    monitor.subscribers()
+}
+
+// 单独启用或停用某个按键事件消费者，例如 "database"、"key_popup"、"kpm"
+#[tauri::command]
+fn set_event_subscriber_enabled(app: tauri::AppHandle, name: &str, enabled: bool) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let monitor = state.keyboard_monitor.lock().unwrap();
+    monitor.set_subscriber_enabled(name, enabled)
+}
+
+// 获取按应用的录制策略
+#[tauri::command]
+fn get_app_policies(app: tauri::AppHandle) -> Vec<AppPolicy> {
+    let state = app.state::<AppState>();
+    let config = state.config_manager.get_config();
+    config.app_policies.clone()
+}
+
+// 保存按应用的录制策略，按列表顺序匹配，第一条匹配的策略生效
+#[tauri::command]
+fn set_app_policies(app: tauri::AppHandle, policies: Vec<AppPolicy>) -> Result<(), String> {
+    let policies: Vec<AppPolicy> = policies.into_iter()
+        .map(|policy| AppPolicy { pattern: policy.pattern.trim().to_string(), mode: policy.mode })
+        .collect();
+    if policies.iter().any(|policy| policy.pattern.is_empty()) {
+        return Err("应用名称不能为空".to_string());
+    }
+    let state = app.state::<AppState>();
+    state.keyboard_monitor.lock().unwrap().set_app_policies(policies.clone());
+    {
+        let mut config = state.config_manager.get_config();
+        config.app_policies = policies;
+    }
+    state.save_config()
+}
+
+// 获取用户自定义的应用识别规则
+#[tauri::command]
+fn get_app_rules(app: tauri::AppHandle) -> Vec<AppRule> {
+    let state = app.state::<AppState>();
+    let config = state.config_manager.get_config();
+    config.app_rules.clone()
+}
+
+// 获取内置的应用识别规则，供界面参考
+#[tauri::command]
+fn get_builtin_app_rules() -> Vec<AppRule> {
+    keyboard_statistics_lib::apps::builtin_rules()
+}
+
+// 保存应用显示名称和分类规则，按列表顺序匹配，优先于内置规则，下次查询统计时生效
+#[tauri::command]
+fn set_app_rules(app: tauri::AppHandle, rules: Vec<AppRule>) -> Result<(), String> {
+    let trim = |text: Option<String>| text.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+    let rules: Vec<AppRule> = rules.into_iter()
+        .map(|rule| AppRule {
+            pattern: rule.pattern.trim().to_string(),
+            display_name: trim(rule.display_name),
+            category: trim(rule.category),
+        })
+        .collect();
+    if rules.iter().any(|rule| rule.pattern.is_empty()) {
+        return Err("应用名称不能为空".to_string());
+    }
+    let state = app.state::<AppState>();
+    {
+        let mut config = state.config_manager.get_config();
+        config.app_rules = rules;
+    }
+    state.save_config()
+}
+
+// 获取隐私模式状态
+#[tauri::command]
+fn get_privacy_mode(app: tauri::AppHandle) -> bool {
+    let state = app.state::<AppState>();
+    let config = state.config_manager.get_config();
+    config.privacy_mode
+}
+
+// 开启或关闭隐私模式，开启时同时处理已有的字符键记录
+#[tauri::command]
+async fn set_privacy_mode(app: tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    state.keyboard_monitor.lock().unwrap().set_privacy_mode(enabled);
+    {
+        let mut config = state.config_manager.get_config();
+        config.privacy_mode = enabled;
+    }
+    state.save_config()?;
+
+    if enabled {
+        let store = state.store()?;
+        let mut conn = store.write().map_err(|e| format!("数据库连接失败: {}", e))?;
+        let count = database::harden_character_events(&mut conn)
+            .map_err(|e| format!("处理历史记录失败: {}", e))?;
+        let _ = Logger::info("main", &format!("隐私模式已开启，处理历史字符键记录 {} 条", count));
+    }
+    Ok(())
+}
+
+// 获取当前活跃/空闲状态
+#[tauri::command]
+fn get_activity_status(app: tauri::AppHandle) -> ActivityStatus {
+    let state = app.state::<AppState>();
+    let monitor = state.keyboard_monitor.lock().unwrap();
+    monitor.activity_status()
+}
+
+// 设置无输入多久后视为离开（秒）
+#[tauri::command]
+fn set_idle_timeout(app: tauri::AppHandle, secs: u64) -> Result<(), String> {
+    if secs < 30 {
+        return Err("空闲时长不能少于30秒".to_string());
+    }
+    let state = app.state::<AppState>();
+    state.keyboard_monitor.lock().unwrap().set_idle_timeout(secs);
+    {
+        let mut config = state.config_manager.get_config();
+        config.idle_timeout_secs = secs;
+    }
+    state.save_config()
+}
+
+// 获取是否统计鼠标
+#[tauri::command]
+fn get_mouse_enabled(app: tauri::AppHandle) -> bool {
+    let state = app.state::<AppState>();
+    let config = state.config_manager.get_config();
+    config.mouse_enabled
+}
+
+// 开启或关闭鼠标统计
+#[tauri::command]
+fn set_mouse_enabled(app: tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    state.keyboard_monitor.lock().unwrap().set_mouse_enabled(enabled);
+    {
+        let mut config = state.config_manager.get_config();
+        config.mouse_enabled = enabled;
+    }
+    state.save_config()
+}
+
+// 获取输入源设置
+#[tauri::command]
+fn get_input_backend(app: tauri::AppHandle) -> String {
+    let state = app.state::<AppState>();
+    let config = state.config_manager.get_config();
+    config.input_backend.clone()
+}
+
+// 设置输入源，重启应用后生效
+#[tauri::command]
+fn set_input_backend(app: tauri::AppHandle, backend: String) -> Result<(), String> {
+    // 提前检查当前平台是否支持该输入源
+    crate::input::source_by_name(&backend)?;
+    let state = app.state::<AppState>();
+    state.keyboard_monitor.lock().unwrap().set_input_backend(&backend);
+    {
+        let mut config = state.config_manager.get_config();
+        config.input_backend = backend;
+    }
+    state.save_config()
+}
+
+// 获取全局快捷键设置
+#[tauri::command]
+fn get_hotkeys(app: tauri::AppHandle) -> HotkeySettings {
+    let state = app.state::<AppState>();
+    let config = state.config_manager.get_config();
+    config.hotkeys.clone()
+}
+
+// 设置全局快捷键，立即生效
+#[tauri::command]
+fn set_hotkeys(app: tauri::AppHandle, settings: HotkeySettings) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let mut monitor = state.keyboard_monitor.lock().unwrap();
+    monitor.set_hotkeys(&settings)?;
+    let recording_enabled = state.config_manager.get_config().recording_enabled;
+    // 停止录制时只有启用了快捷键才需要保持监听
+    if !recording_enabled {
+        if settings.any_enabled() && !monitor.is_running() {
+            monitor.start()?;
+            monitor.pause();
+        } else if !settings.any_enabled() {
+            monitor.stop();
+        }
+    }
+    {
+        let mut config = state.config_manager.get_config();
+        config.hotkeys = settings;
+    }
+    state.save_config()
+}
+
+// 快捷键切换录制状态后保存，在监听线程中调用，不能再锁定监听器
+fn save_recording_state(app: &tauri::AppHandle, recording: bool) {
+    let Some(state) = app.try_state::<AppState>() else {
+        return;
+    };
+    {
+        let mut config = state.config_manager.get_config();
+        config.recording_enabled = recording;
+    }
+    if let Err(e) = state.save_config() {
+        let _ = Logger::error("main", &format!("保存录制状态失败: {}", e));
+    }
+}
+
 // 添加开始监听命令
 #[tauri::command]
 async fn start_recording(app: tauri::AppHandle) -> Result<(), String> {
@@ -173,7 +635,12 @@ async fn start_recording(app: tauri::AppHandle) -> Result<(), String> {
 async fn stop_recording(app: tauri::AppHandle) {
     let state = app.state::<AppState>();
     let mut monitor = state.keyboard_monitor.lock().unwrap();
-    monitor.stop();
+    // 启用了快捷键时保持监听，以便用快捷键恢复录制
+    if state.config_manager.get_config().hotkeys.any_enabled() {
+        monitor.pause();
+    } else {
+        monitor.stop();
+    }
     println!("stop recording");
     // 持久化状态
     {
@@ -185,11 +652,30 @@ async fn stop_recording(app: tauri::AppHandle) {
 
 impl AppState {
     fn new(app_dir: PathBuf) -> Self {
+        let store = SqliteStore::open(&app_dir.join("keyboard_events.db"))
+            .map(|store| Arc::new(store) as Arc<dyn Store>)
+            .map_err(|e| format!("数据库连接失败: {}", e));
+        if let Err(e) = &store {
+            println!("{}", e);
+        }
+        let keyboard_monitor = KeyboardMonitor::new(store.clone());
+        // 外部工具通过上下文接口告知当前项目，失败时不影响记录
+        let context_endpoint = crate::context::start_server(&app_dir, keyboard_monitor.context());
+        match &context_endpoint {
+            Ok(endpoint) => println!("上下文接口: {}", endpoint),
+            Err(e) => println!("启动上下文接口失败: {}", e),
+        }
         AppState {
-            config_manager: ConfigManager::new(app_dir.clone()),
-            keyboard_monitor: Mutex::new(KeyboardMonitor::new(app_dir)),
+            config_manager: ConfigManager::new(app_dir),
+            keyboard_monitor: Mutex::new(keyboard_monitor),
+            context_endpoint,
+            store,
         }
     }
+
+    fn store(&self) -> Result<Arc<dyn Store>, String> {
+        self.store.clone()
+    }
     fn save_config(&self) -> Result<(), String> {
         self.config_manager.save_config()
     }
@@ -293,15 +779,8 @@ async fn open_folder(path: String) -> Result<(), String> {
 // 获取健康评估指标
 #[tauri::command]
 async fn get_health_risk_metrics(app: tauri::AppHandle) -> Result<String, String> {
-    let app_dir = app.path().app_data_dir()
-        .map_err(|e| format!("无法获取应用数据目录: {}", e))?;
-    
-    let db_path = app_dir.join("keyboard_events.db");
-    let db_path_str = db_path.to_str()
-        .ok_or_else(|| "无法将路径转换为字符串".to_string())?;
-    
-    let conn = database::init_db(db_path_str)
-        .map_err(|e| format!("数据库连接失败: {}", e))?;
+    let store = app.state::<AppState>().store()?;
+    let conn = store.read().map_err(|e| format!("数据库连接失败: {}", e))?;
     
     // 获取用户首次按键时间
     let first_event_time = database::get_first_event_time(&conn)
@@ -663,12 +1142,30 @@ fn main() {
                 let config = app_state.config_manager.get_config();
                 let mut monitor = app_state.keyboard_monitor.lock().unwrap();
                 monitor.set_app_handle(app.handle().clone());
+                monitor.set_app_policies(config.app_policies.clone());
+                monitor.set_privacy_mode(config.privacy_mode);
+                monitor.set_idle_timeout(config.idle_timeout_secs);
+                monitor.set_mouse_enabled(config.mouse_enabled);
+                monitor.set_input_backend(&config.input_backend);
+                if let Err(e) = monitor.set_hotkeys(&config.hotkeys) {
+                    let _ = Logger::error("main", &format!("快捷键设置无效: {}", e));
+                }
+                let app_handle = app.handle().clone();
+                monitor.set_hotkey_listener(Box::new(move |recording| save_recording_state(&app_handle, recording)));
                 if config.recording_enabled {
                     if let Err(e) = monitor.start() {
                         let _ = Logger::error("main", &format!("启动键盘监听器失败: {}", e));
                     } else {
                         let _ = Logger::info("main", "键盘监听器已启动");
                     }
+                } else if config.hotkeys.any_enabled() {
+                    // 保持监听但不记录，以便用快捷键恢复录制
+                    if let Err(e) = monitor.start() {
+                        let _ = Logger::error("main", &format!("启动键盘监听器失败: {}", e));
+                    } else {
+                        monitor.pause();
+                        let _ = Logger::info("main", "键盘监听器已暂停");
+                    }
                 } else {
                     monitor.stop();
                     let _ = Logger::info("main", "键盘监听器已停止");
@@ -681,6 +1178,8 @@ fn main() {
                 return Err(e);
             }
             let _ = Logger::info("main", "托盘图标已设置");
+            spawn_retention_worker(app.handle().clone());
+            spawn_backup_worker(app.handle().clone());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -692,11 +1191,44 @@ fn main() {
             start_recording,
             stop_recording,
             get_recording_status,
+            get_monitor_status,
+            get_writer_stats,
+            get_event_subscribers,
+            set_event_subscriber_enabled,
+            get_app_policies,
+            set_app_policies,
+            get_app_rules,
+            get_builtin_app_rules,
+            set_app_rules,
+            get_privacy_mode,
+            set_privacy_mode,
+            get_activity_status,
+            set_idle_timeout,
+            get_mouse_enabled,
+            set_mouse_enabled,
+            get_mouse_stats,
+            get_device_stats,
+            get_context_stats,
+            get_context_status,
+            get_input_backend,
+            set_input_backend,
+            get_hotkeys,
+            set_hotkeys,
             get_key_stats,
             get_current_kpm,
+            get_flight_time_report,
+            get_key_usage,
             export_data,
             delete_data,
             clear_all_data,
+            rebuild_rollups,
+            get_retention,
+            set_retention,
+            create_backup,
+            list_backups,
+            restore_backup,
+            get_backup_settings,
+            set_backup_settings,
             get_database_path,
             open_folder,
             get_health_risk_metrics,
@@ -750,11 +1282,14 @@ fn main() {
         })
         .build(tauri::generate_context!())
         .expect("启动失败")
-        .run(|_app_handle, event| {
+        .run(|app_handle, event| {
             match event {
                 tauri::RunEvent::Exit => {
-                    // 应用退出时确保日志写入
+                    // 应用退出时确保按键数据和日志写入
                     let _ = Logger::info("main", "应用接收到退出事件");
+                    if let Some(state) = app_handle.try_state::<AppState>() {
+                        state.keyboard_monitor.lock().unwrap().flush();
+                    }
                     let _ = logger::shutdown();
                 }
                 _ => {}
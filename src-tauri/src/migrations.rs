@@ -0,0 +1,762 @@
+// 数据库结构迁移：当前版本保存在 PRAGMA user_version 中，按版本顺序执行尚未执行的迁移，
+// 每个迁移在单独的事务中完成并同时更新版本号，中途失败时数据库停留在上一个完整的版本
+//
+// 引入版本号之前的数据库版本都是 0，但可能已经由旧的 init_db 建好了部分表和列，
+// 因此版本 1-9 的迁移都可以在已有的表上重复执行
+use rusqlite::{ffi, Connection, Result, TransactionBehavior};
+use std::path::{Path, PathBuf};
+
+struct Migration {
+    version: u32,
+    description: &'static str,
+    up: fn(&Connection) -> Result<()>,
+}
+
+// 只能在末尾追加新的迁移，已发布的迁移不能修改
+const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, description: "键盘事件、应用统计和按键统计表", up: create_base_tables },
+    Migration { version: 2, description: "按键按住时长", up: add_hold_ms },
+    Migration { version: 3, description: "按键间隔", up: add_flight_ms },
+    Migration { version: 4, description: "物理按键标识", up: add_raw_key },
+    Migration { version: 5, description: "自动重复次数", up: add_repeat_count },
+    Migration { version: 6, description: "活跃/空闲时间段表", up: create_activity_intervals },
+    Migration { version: 7, description: "鼠标统计表", up: create_mouse_tables },
+    Migration { version: 8, description: "键盘设备", up: add_input_devices },
+    Migration { version: 9, description: "上下文标签", up: add_context_tag },
+    Migration { version: 10, description: "时间改为 UTC 毫秒并建立索引", up: convert_timestamps_to_epoch },
+    Migration { version: 11, description: "按分钟、小时、天的按键汇总表", up: create_rollup_tables },
+    Migration { version: 12, description: "数据库维护状态", up: create_db_meta },
+    Migration { version: 13, description: "应用名称和按键名称改为编号", up: intern_app_and_key_names },
+    Migration { version: 14, description: "平台按键码", up: add_scan_code },
+    Migration { version: 15, description: "脱敏按键按分钟保存", up: harden_masked_events },
+];
+
+// 当前程序使用的数据库版本
+pub const SCHEMA_VERSION: u32 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+pub fn schema_version(conn: &Connection) -> Result<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+// 把数据库升级到当前版本；db_path 不为空且数据库中已有数据时，先备份到同目录下
+pub fn migrate(conn: &mut Connection, db_path: Option<&Path>) -> Result<()> {
+    let version = schema_version(conn)?;
+    if version > SCHEMA_VERSION {
+        return Err(rusqlite::Error::SqliteFailure(
+            ffi::Error::new(ffi::SQLITE_MISMATCH),
+            Some(format!("数据库版本 {} 高于程序支持的版本 {}，请升级程序", version, SCHEMA_VERSION)),
+        ));
+    }
+    if version == SCHEMA_VERSION {
+        return Ok(());
+    }
+    if let Some(db_path) = db_path {
+        if has_tables(conn)? {
+            let backup_path = backup_path(db_path, version);
+            // 同一版本的备份已存在说明之前的升级没有完成，保留最早的备份
+            if !backup_path.exists() {
+                conn.execute("VACUUM INTO ?1", [backup_path.to_string_lossy()])?;
+                println!("升级前已备份数据库: {}", backup_path.display());
+            }
+        }
+    }
+
+    for migration in MIGRATIONS {
+        // 立即加写锁，并重新读取版本，其他连接可能已经完成了这个迁移
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        if schema_version(&tx)? >= migration.version {
+            continue;
+        }
+        (migration.up)(&tx)?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+        println!("数据库已升级到版本 {}: {}", migration.version, migration.description);
+    }
+    Ok(())
+}
+
+// 升级前的备份文件，例如 keyboard_events.db.v3.bak
+pub fn backup_path(db_path: &Path, version: u32) -> PathBuf {
+    let mut name = db_path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".v{}.bak", version));
+    db_path.with_file_name(name)
+}
+
+fn has_tables(conn: &Connection) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table')",
+        [],
+        |row| row.get(0),
+    )
+}
+
+// 为旧版本数据库补充新增的列
+fn ensure_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt.query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|r| r.ok())
+        .any(|name| name == column);
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+        println!("为{}表添加{}列成功", table, column);
+    }
+    Ok(())
+}
+
+fn create_base_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS keyboard_events (
+            id INTEGER PRIMARY KEY,
+            timestamp TEXT NOT NULL,
+            key_code TEXT NOT NULL,
+            app_name TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS app_stats (
+            id INTEGER PRIMARY KEY,
+            app_name TEXT UNIQUE NOT NULL,
+            key_count INTEGER NOT NULL DEFAULT 0,
+            last_used TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS key_stats (
+            id INTEGER PRIMARY KEY,
+            key_code TEXT UNIQUE NOT NULL,
+            count INTEGER NOT NULL DEFAULT 0
+        );"
+    )
+}
+
+fn add_hold_ms(conn: &Connection) -> Result<()> {
+    ensure_column(conn, "keyboard_events", "hold_ms", "INTEGER")
+}
+
+fn add_flight_ms(conn: &Connection) -> Result<()> {
+    ensure_column(conn, "keyboard_events", "flight_ms", "INTEGER")
+}
+
+fn add_raw_key(conn: &Connection) -> Result<()> {
+    ensure_column(conn, "keyboard_events", "raw_key", "TEXT")
+}
+
+fn add_repeat_count(conn: &Connection) -> Result<()> {
+    ensure_column(conn, "keyboard_events", "repeat_count", "INTEGER NOT NULL DEFAULT 0")
+}
+
+fn create_activity_intervals(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS activity_intervals (
+            id INTEGER PRIMARY KEY,
+            start_time TEXT NOT NULL,
+            end_time TEXT NOT NULL,
+            state TEXT NOT NULL
+        );"
+    )
+}
+
+// 鼠标点击逐条记录，移动和滚动按分钟汇总
+fn create_mouse_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS mouse_clicks (
+            id INTEGER PRIMARY KEY,
+            timestamp TEXT NOT NULL,
+            button TEXT NOT NULL,
+            app_name TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS mouse_activity (
+            id INTEGER PRIMARY KEY,
+            minute TEXT NOT NULL,
+            app_name TEXT NOT NULL,
+            move_distance REAL NOT NULL DEFAULT 0,
+            scroll_x INTEGER NOT NULL DEFAULT 0,
+            scroll_y INTEGER NOT NULL DEFAULT 0,
+            UNIQUE(minute, app_name)
+        );"
+    )
+}
+
+fn add_input_devices(conn: &Connection) -> Result<()> {
+    ensure_column(conn, "keyboard_events", "device_id", "TEXT")?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS input_devices (
+            device_id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            vendor_id INTEGER NOT NULL,
+            product_id INTEGER NOT NULL,
+            last_seen TEXT NOT NULL
+        );"
+    )
+}
+
+fn add_context_tag(conn: &Connection) -> Result<()> {
+    ensure_column(conn, "keyboard_events", "context_tag", "TEXT")
+}
+
+// RFC3339 文本时间转换为 UTC 毫秒，无法解析时为 NULL
+fn epoch_millis_sql(column: &str) -> String {
+    format!("CAST(round(unixepoch({}, 'subsec') * 1000) AS INTEGER)", column)
+}
+
+// RFC3339 文本末尾的时区偏移（如 +08:00）转换为秒，以 Z 结尾时为 0
+fn utc_offset_sql(column: &str) -> String {
+    format!(
+        "(CASE WHEN substr({c}, -6, 1) IN ('+', '-') THEN
+             (CASE substr({c}, -6, 1) WHEN '-' THEN -1 ELSE 1 END)
+             * (CAST(substr({c}, -5, 2) AS INTEGER) * 3600 + CAST(substr({c}, -2) AS INTEGER) * 60)
+         ELSE 0 END)",
+        c = column
+    )
+}
+
+// 时间列从 RFC3339 文本改为 UTC 毫秒（ts）加记录时的时区偏移（utc_offset，秒），
+// 按整数比较代替文本比较，并为时间范围查询建立索引。
+// SQLite 不能修改列的类型，因此重建各表；无法解析时间的旧记录本来就查询不到，直接丢弃，
+// 并从按键统计和应用统计中扣除，使累计值与保留的记录一致
+fn convert_timestamps_to_epoch(conn: &Connection) -> Result<()> {
+    conn.execute_batch(&format!(
+        "CREATE TEMP TABLE dropped_events AS
+            SELECT key_code, app_name FROM keyboard_events WHERE {ts} IS NULL;
+        UPDATE key_stats
+            SET count = count - (SELECT COUNT(*) FROM temp.dropped_events d WHERE d.key_code = key_stats.key_code)
+            WHERE key_code IN (SELECT key_code FROM temp.dropped_events);
+        DELETE FROM key_stats WHERE count <= 0;
+        UPDATE app_stats
+            SET key_count = key_count - (SELECT COUNT(*) FROM temp.dropped_events d WHERE d.app_name = app_stats.app_name)
+            WHERE app_name IN (SELECT app_name FROM temp.dropped_events);
+        DELETE FROM app_stats WHERE key_count <= 0;
+        DROP TABLE temp.dropped_events;",
+        ts = epoch_millis_sql("timestamp"),
+    ))?;
+
+    conn.execute_batch(&format!(
+        "CREATE TABLE keyboard_events_new (
+            id INTEGER PRIMARY KEY,
+            ts INTEGER NOT NULL,
+            utc_offset INTEGER NOT NULL,
+            key_code TEXT NOT NULL,
+            app_name TEXT NOT NULL,
+            hold_ms INTEGER,
+            flight_ms INTEGER,
+            raw_key TEXT,
+            repeat_count INTEGER NOT NULL DEFAULT 0,
+            device_id TEXT,
+            context_tag TEXT
+        );
+        INSERT INTO keyboard_events_new (id, ts, utc_offset, key_code, app_name, hold_ms, flight_ms, raw_key, repeat_count, device_id, context_tag)
+            SELECT id, {ts}, {offset}, key_code, app_name, hold_ms, flight_ms, raw_key, repeat_count, device_id, context_tag
+            FROM keyboard_events
+            WHERE {ts} IS NOT NULL;
+        DROP TABLE keyboard_events;
+        ALTER TABLE keyboard_events_new RENAME TO keyboard_events;
+        CREATE INDEX idx_keyboard_events_ts ON keyboard_events(ts);
+        CREATE INDEX idx_keyboard_events_app_ts ON keyboard_events(app_name, ts);
+        CREATE INDEX idx_keyboard_events_key_ts ON keyboard_events(key_code, ts);",
+        ts = epoch_millis_sql("timestamp"),
+        offset = utc_offset_sql("timestamp"),
+    ))?;
+
+    conn.execute_batch(&format!(
+        "CREATE TABLE mouse_clicks_new (
+            id INTEGER PRIMARY KEY,
+            ts INTEGER NOT NULL,
+            utc_offset INTEGER NOT NULL,
+            button TEXT NOT NULL,
+            app_name TEXT NOT NULL
+        );
+        INSERT INTO mouse_clicks_new (id, ts, utc_offset, button, app_name)
+            SELECT id, {ts}, {offset}, button, app_name
+            FROM mouse_clicks
+            WHERE {ts} IS NOT NULL;
+        DROP TABLE mouse_clicks;
+        ALTER TABLE mouse_clicks_new RENAME TO mouse_clicks;
+        CREATE INDEX idx_mouse_clicks_ts ON mouse_clicks(ts);",
+        ts = epoch_millis_sql("timestamp"),
+        offset = utc_offset_sql("timestamp"),
+    ))?;
+
+    // 同一分钟在不同时区下写入的文本不同，转换后合并
+    conn.execute_batch(&format!(
+        "CREATE TABLE mouse_activity_new (
+            id INTEGER PRIMARY KEY,
+            minute_ts INTEGER NOT NULL,
+            utc_offset INTEGER NOT NULL,
+            app_name TEXT NOT NULL,
+            move_distance REAL NOT NULL DEFAULT 0,
+            scroll_x INTEGER NOT NULL DEFAULT 0,
+            scroll_y INTEGER NOT NULL DEFAULT 0,
+            UNIQUE(minute_ts, app_name)
+        );
+        INSERT INTO mouse_activity_new (minute_ts, utc_offset, app_name, move_distance, scroll_x, scroll_y)
+            SELECT {ts} AS minute_ts, MAX({offset}), app_name, SUM(move_distance), SUM(scroll_x), SUM(scroll_y)
+            FROM mouse_activity
+            WHERE {ts} IS NOT NULL
+            GROUP BY minute_ts, app_name;
+        DROP TABLE mouse_activity;
+        ALTER TABLE mouse_activity_new RENAME TO mouse_activity;",
+        ts = epoch_millis_sql("minute"),
+        offset = utc_offset_sql("minute"),
+    ))?;
+
+    conn.execute_batch(&format!(
+        "CREATE TABLE activity_intervals_new (
+            id INTEGER PRIMARY KEY,
+            start_ts INTEGER NOT NULL,
+            end_ts INTEGER NOT NULL,
+            utc_offset INTEGER NOT NULL,
+            state TEXT NOT NULL
+        );
+        INSERT INTO activity_intervals_new (id, start_ts, end_ts, utc_offset, state)
+            SELECT id, {start}, {end}, {offset}, state
+            FROM activity_intervals
+            WHERE {start} IS NOT NULL AND {end} IS NOT NULL;
+        DROP TABLE activity_intervals;
+        ALTER TABLE activity_intervals_new RENAME TO activity_intervals;
+        CREATE INDEX idx_activity_intervals_start_ts ON activity_intervals(start_ts);",
+        start = epoch_millis_sql("start_time"),
+        end = epoch_millis_sql("end_time"),
+        offset = utc_offset_sql("start_time"),
+    ))?;
+
+    // 最近使用时间只用于显示，无法解析时记为 0
+    conn.execute_batch(&format!(
+        "CREATE TABLE app_stats_new (
+            id INTEGER PRIMARY KEY,
+            app_name TEXT UNIQUE NOT NULL,
+            key_count INTEGER NOT NULL DEFAULT 0,
+            last_used_ts INTEGER NOT NULL
+        );
+        INSERT INTO app_stats_new (id, app_name, key_count, last_used_ts)
+            SELECT id, app_name, key_count, COALESCE({last_used}, 0)
+            FROM app_stats;
+        DROP TABLE app_stats;
+        ALTER TABLE app_stats_new RENAME TO app_stats;
+
+        CREATE TABLE input_devices_new (
+            device_id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            vendor_id INTEGER NOT NULL,
+            product_id INTEGER NOT NULL,
+            last_seen_ts INTEGER NOT NULL
+        );
+        INSERT INTO input_devices_new (device_id, name, vendor_id, product_id, last_seen_ts)
+            SELECT device_id, name, vendor_id, product_id, COALESCE({last_seen}, 0)
+            FROM input_devices;
+        DROP TABLE input_devices;
+        ALTER TABLE input_devices_new RENAME TO input_devices;",
+        last_used = epoch_millis_sql("last_used"),
+        last_seen = epoch_millis_sql("last_seen"),
+    ))
+}
+
+// 汇总表按记录时的本地时间对齐，并由已有的原始记录生成
+fn create_rollup_tables(conn: &Connection) -> Result<()> {
+    for (table, bucket_ms) in [("rollup_minute", 60_000), ("rollup_hour", 3_600_000), ("rollup_day", 86_400_000)] {
+        conn.execute_batch(&format!(
+            "CREATE TABLE {table} (
+                bucket_ts INTEGER NOT NULL,
+                utc_offset INTEGER NOT NULL,
+                app_name TEXT NOT NULL,
+                key_code TEXT NOT NULL,
+                count INTEGER NOT NULL,
+                PRIMARY KEY (bucket_ts, app_name, key_code)
+            ) WITHOUT ROWID;
+            INSERT INTO {table} (bucket_ts, utc_offset, app_name, key_code, count)
+                SELECT ts - (ts + utc_offset * 1000) % {bucket_ms} AS bucket, MAX(utc_offset), app_name, key_code, COUNT(*)
+                FROM keyboard_events
+                GROUP BY bucket, app_name, key_code;",
+            table = table,
+            bucket_ms = bucket_ms,
+        ))?;
+    }
+    Ok(())
+}
+
+// 数据保留等后台维护的状态，值均为整数
+fn create_db_meta(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE db_meta (
+            key TEXT PRIMARY KEY,
+            value INTEGER NOT NULL
+        );"
+    )
+}
+
+// 应用名称和按键名称各保存一份在 apps、keys 表中，按键记录和汇总表只保存编号
+fn intern_app_and_key_names(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE apps (
+            app_id INTEGER PRIMARY KEY,
+            app_name TEXT NOT NULL UNIQUE
+        );
+        CREATE TABLE keys (
+            key_id INTEGER PRIMARY KEY,
+            key_code TEXT NOT NULL UNIQUE
+        );
+        INSERT INTO apps (app_name)
+            SELECT app_name FROM keyboard_events
+            UNION SELECT app_name FROM rollup_minute
+            UNION SELECT app_name FROM rollup_hour
+            UNION SELECT app_name FROM rollup_day;
+        INSERT INTO keys (key_code)
+            SELECT key_code FROM keyboard_events
+            UNION SELECT key_code FROM rollup_minute
+            UNION SELECT key_code FROM rollup_hour
+            UNION SELECT key_code FROM rollup_day;
+
+        CREATE TABLE keyboard_events_new (
+            id INTEGER PRIMARY KEY,
+            ts INTEGER NOT NULL,
+            utc_offset INTEGER NOT NULL,
+            key_id INTEGER NOT NULL REFERENCES keys(key_id),
+            app_id INTEGER NOT NULL REFERENCES apps(app_id),
+            hold_ms INTEGER,
+            flight_ms INTEGER,
+            raw_key TEXT,
+            repeat_count INTEGER NOT NULL DEFAULT 0,
+            device_id TEXT,
+            context_tag TEXT
+        );
+        INSERT INTO keyboard_events_new (id, ts, utc_offset, key_id, app_id, hold_ms, flight_ms, raw_key, repeat_count, device_id, context_tag)
+            SELECT e.id, e.ts, e.utc_offset, k.key_id, a.app_id, e.hold_ms, e.flight_ms, e.raw_key, e.repeat_count, e.device_id, e.context_tag
+            FROM keyboard_events e
+            JOIN keys k ON k.key_code = e.key_code
+            JOIN apps a ON a.app_name = e.app_name;
+        DROP TABLE keyboard_events;
+        ALTER TABLE keyboard_events_new RENAME TO keyboard_events;
+        CREATE INDEX idx_keyboard_events_ts ON keyboard_events(ts);
+        CREATE INDEX idx_keyboard_events_app_ts ON keyboard_events(app_id, ts);
+        CREATE INDEX idx_keyboard_events_key_ts ON keyboard_events(key_id, ts);"
+    )?;
+
+    for table in ["rollup_minute", "rollup_hour", "rollup_day"] {
+        conn.execute_batch(&format!(
+            "CREATE TABLE {table}_new (
+                bucket_ts INTEGER NOT NULL,
+                utc_offset INTEGER NOT NULL,
+                app_id INTEGER NOT NULL REFERENCES apps(app_id),
+                key_id INTEGER NOT NULL REFERENCES keys(key_id),
+                count INTEGER NOT NULL,
+                PRIMARY KEY (bucket_ts, app_id, key_id)
+            ) WITHOUT ROWID;
+            INSERT INTO {table}_new (bucket_ts, utc_offset, app_id, key_id, count)
+                SELECT r.bucket_ts, r.utc_offset, a.app_id, k.key_id, r.count
+                FROM {table} r
+                JOIN apps a ON a.app_name = r.app_name
+                JOIN keys k ON k.key_code = r.key_code;
+            DROP TABLE {table};
+            ALTER TABLE {table}_new RENAME TO {table};",
+            table = table,
+        ))?;
+    }
+    Ok(())
+}
+
+// 平台按键码，和 raw_key 一起作为按键的物理标识
+fn add_scan_code(conn: &Connection) -> Result<()> {
+    ensure_column(conn, "keyboard_events", "scan_code", "INTEGER")
+}
+
+// 只记录次数的应用中已写入的按键：时间截断到所在分钟，去掉按键节奏、设备和上下文。
+// 截断后仍在原来的分钟内，汇总不变
+fn harden_masked_events(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "UPDATE keyboard_events
+         SET ts = ts - (ts + utc_offset * 1000) % 60000,
+             hold_ms = NULL, flight_ms = NULL, raw_key = NULL, scan_code = NULL,
+             repeat_count = 0, device_id = NULL, context_tag = NULL
+         WHERE key_id IN (SELECT key_id FROM keys WHERE key_code = '[masked]');"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{init_db, insert_event, query_events_by_time_range, KeyboardEventRecord};
+    use chrono::{Local, TimeZone};
+    use std::fs;
+
+    // 每个历史版本的数据库，由当时的 init_db 建表并写入少量数据
+    const FIXTURES: &[(u32, &str)] = &[
+        (1, include_str!("../tests/fixtures/migrations/v1.sql")),
+        (2, include_str!("../tests/fixtures/migrations/v2.sql")),
+        (3, include_str!("../tests/fixtures/migrations/v3.sql")),
+        (4, include_str!("../tests/fixtures/migrations/v4.sql")),
+        (5, include_str!("../tests/fixtures/migrations/v5.sql")),
+        (6, include_str!("../tests/fixtures/migrations/v6.sql")),
+        (7, include_str!("../tests/fixtures/migrations/v7.sql")),
+        (8, include_str!("../tests/fixtures/migrations/v8.sql")),
+        (9, include_str!("../tests/fixtures/migrations/v9.sql")),
+        (10, include_str!("../tests/fixtures/migrations/v10.sql")),
+        (11, include_str!("../tests/fixtures/migrations/v11.sql")),
+        (12, include_str!("../tests/fixtures/migrations/v12.sql")),
+        (13, include_str!("../tests/fixtures/migrations/v13.sql")),
+        (14, include_str!("../tests/fixtures/migrations/v14.sql")),
+    ];
+
+    const LATEST_TABLES: &[&str] = &[
+        "keyboard_events", "app_stats", "key_stats", "activity_intervals",
+        "mouse_clicks", "mouse_activity", "input_devices",
+        "rollup_minute", "rollup_hour", "rollup_day", "db_meta", "apps", "keys",
+    ];
+
+    const LATEST_EVENT_COLUMNS: &[&str] = &[
+        "id", "ts", "utc_offset", "key_id", "app_id", "hold_ms", "flight_ms",
+        "raw_key", "repeat_count", "device_id", "context_tag", "scan_code",
+    ];
+
+    fn temp_db(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("keyboard-statistics-migrations-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{}.db", name));
+        for stale in [path.clone(), backup_path(&path, 0)] {
+            let _ = fs::remove_file(stale);
+        }
+        path
+    }
+
+    fn fixture_db(name: &str, sql: &str, user_version: u32) -> PathBuf {
+        let path = temp_db(name);
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(sql).unwrap();
+        conn.pragma_update(None, "user_version", user_version).unwrap();
+        path
+    }
+
+    fn columns(conn: &Connection, table: &str) -> Vec<String> {
+        let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table)).unwrap();
+        let columns = stmt.query_map([], |row| row.get(1)).unwrap();
+        columns.collect::<Result<_>>().unwrap()
+    }
+
+    fn count(conn: &Connection, table: &str) -> u64 {
+        conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0)).unwrap()
+    }
+
+    fn assert_latest_schema(conn: &Connection) {
+        assert_eq!(schema_version(conn).unwrap(), SCHEMA_VERSION);
+        for table in LATEST_TABLES {
+            assert!(!columns(conn, table).is_empty(), "缺少表 {}", table);
+        }
+        assert_eq!(columns(conn, "keyboard_events"), LATEST_EVENT_COLUMNS);
+    }
+
+    #[test]
+    fn migrations_are_ordered() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, index as u32 + 1);
+        }
+    }
+
+    #[test]
+    fn creates_latest_schema_on_empty_database() {
+        let path = temp_db("empty");
+        let conn = init_db(path.to_str().unwrap()).unwrap();
+        assert_latest_schema(&conn);
+        // 新建的数据库不需要备份
+        assert!(!backup_path(&path, 0).exists());
+    }
+
+    // 引入版本号之前的数据库版本都是 0，表结构可能是版本 1-9 中的任意一个
+    #[test]
+    fn upgrades_unversioned_databases() {
+        for (version, sql) in FIXTURES.iter().filter(|(version, _)| *version <= 9) {
+            let path = fixture_db(&format!("unversioned-v{}", version), sql, 0);
+            let conn = init_db(path.to_str().unwrap()).unwrap();
+            assert_latest_schema(&conn);
+            assert_eq!(count(&conn, "keyboard_events"), 3, "版本 {}", version);
+            assert_eq!(count(&conn, "app_stats"), 2, "版本 {}", version);
+
+            let backup = Connection::open(backup_path(&path, 0)).unwrap();
+            assert_eq!(schema_version(&backup).unwrap(), 0);
+            assert_eq!(count(&backup, "keyboard_events"), 3);
+        }
+    }
+
+    #[test]
+    fn upgrades_every_historical_version() {
+        for (version, sql) in FIXTURES {
+            let path = fixture_db(&format!("v{}", version), sql, *version);
+            let _ = fs::remove_file(backup_path(&path, *version));
+            let conn = init_db(path.to_str().unwrap()).unwrap();
+            assert_latest_schema(&conn);
+            assert_eq!(count(&conn, "keyboard_events"), 3, "版本 {}", version);
+            assert_eq!(backup_path(&path, *version).exists(), *version < SCHEMA_VERSION);
+
+            // 升级后旧数据仍能读取，新列为空；记录时间为 +08:00，前后各放宽一天以适应运行测试的时区
+            let start = Local.with_ymd_and_hms(2024, 2, 29, 0, 0, 0).unwrap();
+            let end = Local.with_ymd_and_hms(2024, 3, 3, 0, 0, 0).unwrap();
+            let events = query_events_by_time_range(&conn, start, end).unwrap();
+            assert_eq!(events.len(), 3);
+            assert!(events.iter().any(|event| event.key_code == "Ctrl+C" && event.app_name == "Code.exe"));
+            for table in ["rollup_minute", "rollup_hour", "rollup_day"] {
+                let total: i64 = conn.query_row(&format!("SELECT SUM(count) FROM {}", table), [], |row| row.get(0)).unwrap();
+                assert_eq!(total, 3, "版本 {} {}", version, table);
+            }
+
+            // 并且可以写入完整的新记录
+            insert_event(&conn, &KeyboardEventRecord {
+                timestamp: Local::now(),
+                key_code: "B".to_string(),
+                app_name: "Code.exe".to_string(),
+                hold_ms: Some(90),
+                flight_ms: Some(200),
+                raw_key: Some("KeyB".to_string()),
+                scan_code: Some(48),
+                repeat_count: 0,
+                device_id: None,
+                context_tag: Some("project:crate".to_string()),
+            }).unwrap();
+            assert_eq!(count(&conn, "keyboard_events"), 4);
+        }
+    }
+
+    // 2024-03-01T09:00:00+08:00
+    const FIXTURE_START_MS: i64 = 1_709_254_800_000;
+
+    #[test]
+    fn converts_text_timestamps_to_epoch_millis() {
+        let path = fixture_db("epoch", FIXTURES[8].1, 9);
+        let conn = init_db(path.to_str().unwrap()).unwrap();
+
+        let (ts, offset, hour): (i64, i64, String) = conn.query_row(
+            "SELECT ts, utc_offset, strftime('%H', ts / 1000 + utc_offset, 'unixepoch')
+             FROM keyboard_events JOIN keys USING (key_id) WHERE key_code = 'Ctrl+C'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        ).unwrap();
+        assert_eq!(ts, FIXTURE_START_MS + 150);
+        assert_eq!(offset, 8 * 3600);
+        // 按记录时的本地时间分组，与运行测试的时区无关
+        assert_eq!(hour, "09");
+
+        let (start, end): (i64, i64) = conn.query_row(
+            "SELECT start_ts, end_ts FROM activity_intervals",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap();
+        assert_eq!((start, end), (FIXTURE_START_MS, FIXTURE_START_MS + 5 * 60 * 1000));
+        let minute: i64 = conn.query_row("SELECT minute_ts FROM mouse_activity", [], |row| row.get(0)).unwrap();
+        assert_eq!(minute, FIXTURE_START_MS);
+        let click: i64 = conn.query_row("SELECT ts FROM mouse_clicks", [], |row| row.get(0)).unwrap();
+        assert_eq!(click, FIXTURE_START_MS + 2000);
+        let last_used: i64 = conn.query_row(
+            "SELECT last_used_ts FROM app_stats WHERE app_name = 'chrome.exe'",
+            [],
+            |row| row.get(0),
+        ).unwrap();
+        assert_eq!(last_used, FIXTURE_START_MS + 1000);
+
+        let mut stmt = conn.prepare(
+            "SELECT name FROM sqlite_master WHERE type = 'index' AND tbl_name = 'keyboard_events' ORDER BY name"
+        ).unwrap();
+        let indexes: Vec<String> = stmt.query_map([], |row| row.get(0)).unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(indexes, ["idx_keyboard_events_app_ts", "idx_keyboard_events_key_ts", "idx_keyboard_events_ts"]);
+    }
+
+    #[test]
+    fn drops_unparsable_timestamps_from_stats() {
+        let path = fixture_db("unparsable", FIXTURES[8].1, 9);
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "INSERT INTO keyboard_events (timestamp, key_code, app_name) VALUES
+                ('not a time', 'A', 'Code.exe'),
+                ('', 'F5', 'notepad.exe');
+             UPDATE key_stats SET count = count + 1 WHERE key_code = 'A';
+             INSERT INTO key_stats (key_code, count) VALUES ('F5', 1);
+             UPDATE app_stats SET key_count = key_count + 1 WHERE app_name = 'Code.exe';
+             INSERT INTO app_stats (app_name, key_count, last_used) VALUES ('notepad.exe', 1, '');"
+        ).unwrap();
+        drop(conn);
+
+        let conn = init_db(path.to_str().unwrap()).unwrap();
+        assert_eq!(count(&conn, "keyboard_events"), 3);
+        let key_total: i64 = conn.query_row("SELECT SUM(count) FROM key_stats", [], |row| row.get(0)).unwrap();
+        let app_total: i64 = conn.query_row("SELECT SUM(key_count) FROM app_stats", [], |row| row.get(0)).unwrap();
+        assert_eq!((key_total, app_total), (3, 3));
+        assert_eq!(count(&conn, "key_stats"), 3);
+        assert_eq!(count(&conn, "app_stats"), 2);
+    }
+
+    #[test]
+    fn interns_app_and_key_names() {
+        let path = fixture_db("intern", FIXTURES[8].1, 9);
+        let conn = init_db(path.to_str().unwrap()).unwrap();
+        assert_eq!(count(&conn, "apps"), 2);
+        assert_eq!(count(&conn, "keys"), 3);
+        for table in ["rollup_minute", "rollup_hour", "rollup_day"] {
+            assert_eq!(columns(&conn, table), ["bucket_ts", "utc_offset", "app_id", "key_id", "count"]);
+        }
+
+        // 已有的名称沿用原来的编号，新名称分配新编号
+        let code_id = crate::database::app_id(&conn, "Code.exe").unwrap();
+        insert_event(&conn, &KeyboardEventRecord {
+            timestamp: Local::now(),
+            key_code: "Enter".to_string(),
+            app_name: "Code.exe".to_string(),
+            hold_ms: None,
+            flight_ms: None,
+            raw_key: None,
+            scan_code: None,
+            repeat_count: 0,
+            device_id: None,
+            context_tag: None,
+        }).unwrap();
+        assert_eq!(crate::database::app_id(&conn, "Code.exe").unwrap(), code_id);
+        assert_eq!(count(&conn, "apps"), 2);
+        assert_eq!(count(&conn, "keys"), 4);
+        let events: u64 = conn.query_row(
+            "SELECT COUNT(*) FROM keyboard_events WHERE app_id = ?1",
+            [code_id],
+            |row| row.get(0),
+        ).unwrap();
+        assert_eq!(events, 3);
+    }
+
+    #[test]
+    fn hardens_masked_events() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn, None).unwrap();
+        let at = Local.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap() + chrono::Duration::milliseconds(42_317);
+        for key_code in ["[masked]", "A"] {
+            insert_event(&conn, &KeyboardEventRecord {
+                timestamp: at,
+                key_code: key_code.to_string(),
+                app_name: "keepass.exe".to_string(),
+                hold_ms: Some(90),
+                flight_ms: Some(120),
+                raw_key: None,
+                scan_code: None,
+                repeat_count: 0,
+                device_id: Some("usb-kbd".to_string()),
+                context_tag: Some("project:crate".to_string()),
+            }).unwrap();
+        }
+        conn.pragma_update(None, "user_version", 14).unwrap();
+        migrate(&mut conn, None).unwrap();
+
+        let events = query_events_by_time_range(&conn, at - chrono::Duration::minutes(1), at).unwrap();
+        let masked = events.iter().find(|event| event.key_code == "[masked]").unwrap();
+        assert_eq!(masked.timestamp, crate::privacy::truncate_to_minute(at));
+        assert_eq!((masked.hold_ms, masked.flight_ms), (None, None));
+        assert_eq!((masked.device_id.as_deref(), masked.context_tag.as_deref()), (None, None));
+        // 其他按键不受影响
+        let other = events.iter().find(|event| event.key_code == "A").unwrap();
+        assert_eq!((other.timestamp, other.hold_ms), (at, Some(90)));
+    }
+
+    #[test]
+    fn migrating_twice_is_a_no_op() {
+        let path = fixture_db("twice", FIXTURES[0].1, 1);
+        drop(init_db(path.to_str().unwrap()).unwrap());
+        let conn = init_db(path.to_str().unwrap()).unwrap();
+        assert_latest_schema(&conn);
+        assert_eq!(count(&conn, "keyboard_events"), 3);
+    }
+
+    #[test]
+    fn refuses_newer_database() {
+        let path = temp_db("newer");
+        let mut conn = Connection::open(&path).unwrap();
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1).unwrap();
+        assert!(migrate(&mut conn, Some(&path)).is_err());
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION + 1);
+    }
+}
@@ -0,0 +1,244 @@
+use crate::bus::{BusEvent, Subscriber};
+use crate::database::{MouseActivityRecord, MouseClickRecord};
+use crate::foreground::active_app_name;
+use crate::input::InputEvent;
+use crate::privacy::truncate_to_minute;
+use crate::writer::{EventWriter, WriteRecord};
+use chrono::{DateTime, Local};
+use rdev::{Button, EventType};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// 鼠标移动非常频繁，前台应用最多每隔该时长查询一次
+const APP_LOOKUP_INTERVAL: Duration = Duration::from_secs(1);
+// 移动距离累计后最多每隔该时长（毫秒）发布一次
+const MOVE_PUBLISH_MS: i64 = 1000;
+
+#[derive(Debug, Clone)]
+pub enum MouseEventKind {
+    Click(String),                        // 按下的鼠标按键
+    Move(f64),                            // 指针移动距离（像素）
+    Scroll { delta_x: i64, delta_y: i64 }, // 滚动格数
+}
+
+#[derive(Debug, Clone)]
+pub struct MouseEvent {
+    pub timestamp: DateTime<Local>,
+    pub app_name: String,
+    pub kind: MouseEventKind,
+}
+
+// 把原始鼠标事件转换为总线事件：计算移动距离并缓存前台应用
+#[derive(Default)]
+pub struct MouseCapture {
+    last_position: Option<(f64, f64)>,
+    app_cache: Option<(Instant, String)>,
+    pending_move: Option<MouseEvent>, // 尚未发布的累计移动
+}
+
+impl MouseCapture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 鼠标统计关闭期间的位置不再可靠
+    pub fn reset(&mut self) {
+        self.last_position = None;
+        self.app_cache = None;
+        self.pending_move = None;
+    }
+
+    pub fn process(&mut self, event: &InputEvent) -> Option<MouseEvent> {
+        let kind = match event.event_type {
+            EventType::ButtonPress(button) => MouseEventKind::Click(button_name(&button)),
+            EventType::MouseMove { x, y } => {
+                let previous = self.last_position.replace((x, y));
+                let (last_x, last_y) = previous?;
+                let distance = ((x - last_x).powi(2) + (y - last_y).powi(2)).sqrt();
+                if distance <= 0.0 {
+                    return None;
+                }
+                MouseEventKind::Move(distance)
+            }
+            EventType::Wheel { delta_x, delta_y } => MouseEventKind::Scroll { delta_x, delta_y },
+            _ => return None,
+        };
+        let app_name = match &event.app_name {
+            Some(app_name) => app_name.clone(),
+            None => self.current_app(),
+        };
+        Some(MouseEvent {
+            timestamp: event.timestamp,
+            app_name,
+            kind,
+        })
+    }
+
+    // 移动先按应用累计，超过 MOVE_PUBLISH_MS、切换应用或进入新的一分钟时才发布；
+    // 点击和滚动立即发布，发布前先交出之前累计的移动。返回需要发布的事件
+    pub fn coalesce(&mut self, event: MouseEvent) -> Vec<MouseEvent> {
+        let mut ready = Vec::new();
+        let MouseEventKind::Move(distance) = event.kind else {
+            ready.extend(self.pending_move.take());
+            ready.push(event);
+            return ready;
+        };
+        let same_bucket = self.pending_move.as_ref().is_some_and(|pending| {
+            pending.app_name == event.app_name
+                && truncate_to_minute(pending.timestamp) == truncate_to_minute(event.timestamp)
+        });
+        if !same_bucket {
+            ready.extend(self.pending_move.take());
+        }
+        let pending = self.pending_move.get_or_insert_with(|| MouseEvent {
+            timestamp: event.timestamp,
+            app_name: event.app_name.clone(),
+            kind: MouseEventKind::Move(0.0),
+        });
+        if let MouseEventKind::Move(total) = &mut pending.kind {
+            *total += distance;
+        }
+        if event.timestamp.signed_duration_since(pending.timestamp).num_milliseconds() >= MOVE_PUBLISH_MS {
+            ready.extend(self.pending_move.take());
+        }
+        ready
+    }
+
+    fn current_app(&mut self) -> String {
+        match &self.app_cache {
+            Some((looked_up, app_name)) if looked_up.elapsed() < APP_LOOKUP_INTERVAL => app_name.clone(),
+            _ => {
+                let app_name = active_app_name();
+                self.app_cache = Some((Instant::now(), app_name.clone()));
+                app_name
+            }
+        }
+    }
+}
+
+fn button_name(button: &Button) -> String {
+    format!("{:?}", button)
+}
+
+// 鼠标统计订阅者：点击逐条写入，移动和滚动按分钟、应用汇总后写入
+pub struct MouseSubscriber {
+    writer: EventWriter,
+    minute: Option<DateTime<Local>>,
+    activity: HashMap<String, MouseActivityRecord>,
+}
+
+impl MouseSubscriber {
+    pub fn new(writer: EventWriter) -> Self {
+        MouseSubscriber {
+            writer,
+            minute: None,
+            activity: HashMap::new(),
+        }
+    }
+
+    fn flush_activity(&mut self) {
+        for (_, record) in self.activity.drain() {
+            self.writer.submit_record(WriteRecord::MouseActivity(record));
+        }
+    }
+
+    fn add_activity(&mut self, event: &MouseEvent) {
+        let minute = truncate_to_minute(event.timestamp);
+        if self.minute != Some(minute) {
+            self.flush_activity();
+            self.minute = Some(minute);
+        }
+        let record = self.activity.entry(event.app_name.clone())
+            .or_insert_with(|| MouseActivityRecord {
+                minute,
+                app_name: event.app_name.clone(),
+                move_distance: 0.0,
+                scroll_x: 0,
+                scroll_y: 0,
+            });
+        match &event.kind {
+            MouseEventKind::Move(distance) => record.move_distance += distance,
+            MouseEventKind::Scroll { delta_x, delta_y } => {
+                record.scroll_x += delta_x.abs();
+                record.scroll_y += delta_y.abs();
+            }
+            MouseEventKind::Click(_) => {}
+        }
+    }
+}
+
+impl Subscriber for MouseSubscriber {
+    fn handle(&mut self, event: &BusEvent) {
+        match event {
+            BusEvent::Mouse(event) => match &event.kind {
+                MouseEventKind::Click(button) => {
+                    self.writer.submit_record(WriteRecord::MouseClick(MouseClickRecord {
+                        timestamp: event.timestamp,
+                        button: button.clone(),
+                        app_name: event.app_name.clone(),
+                    }));
+                }
+                _ => self.add_activity(event),
+            },
+            BusEvent::Flush => self.flush_activity(),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn event(millis: i64, app_name: &str, kind: MouseEventKind) -> MouseEvent {
+        MouseEvent {
+            timestamp: Local.with_ymd_and_hms(2024, 3, 1, 9, 0, 58).unwrap() + chrono::Duration::milliseconds(millis),
+            app_name: app_name.to_string(),
+            kind,
+        }
+    }
+
+    // 发布的事件简写为 (毫秒, 应用, 种类)
+    fn summary(events: Vec<MouseEvent>) -> Vec<(i64, String, String)> {
+        let start = Local.with_ymd_and_hms(2024, 3, 1, 9, 0, 58).unwrap();
+        events.into_iter()
+            .map(|e| (e.timestamp.signed_duration_since(start).num_milliseconds(), e.app_name, format!("{:?}", e.kind)))
+            .collect()
+    }
+
+    #[test]
+    fn coalesces_moves_between_publishes() {
+        let mut capture = MouseCapture::new();
+        let mut published = Vec::new();
+        let steps = [
+            event(0, "Code.exe", MouseEventKind::Move(3.0)),
+            event(400, "Code.exe", MouseEventKind::Move(4.0)),
+            // 累计超过 MOVE_PUBLISH_MS 后发布一次
+            event(1000, "Code.exe", MouseEventKind::Move(5.0)),
+            event(1100, "Code.exe", MouseEventKind::Move(1.0)),
+            // 切换应用时先发布之前的移动
+            event(1200, "chrome.exe", MouseEventKind::Move(2.0)),
+            // 点击立即发布，之前的移动先发布
+            event(1300, "chrome.exe", MouseEventKind::Click("Left".to_string())),
+            event(1500, "chrome.exe", MouseEventKind::Move(6.0)),
+            // 进入新的一分钟
+            event(2100, "chrome.exe", MouseEventKind::Move(7.0)),
+        ];
+        for step in steps {
+            published.extend(capture.coalesce(step));
+        }
+        assert_eq!(summary(published), [
+            (0, "Code.exe".to_string(), "Move(12.0)".to_string()),
+            (1100, "Code.exe".to_string(), "Move(1.0)".to_string()),
+            (1200, "chrome.exe".to_string(), "Move(2.0)".to_string()),
+            (1300, "chrome.exe".to_string(), "Click(\"Left\")".to_string()),
+            (1500, "chrome.exe".to_string(), "Move(6.0)".to_string()),
+        ]);
+
+        // 停用时丢弃尚未发布的移动
+        capture.reset();
+        let published = capture.coalesce(event(2200, "chrome.exe", MouseEventKind::Scroll { delta_x: 0, delta_y: 1 }));
+        assert_eq!(summary(published), [(2200, "chrome.exe".to_string(), "Scroll { delta_x: 0, delta_y: 1 }".to_string())]);
+    }
+}
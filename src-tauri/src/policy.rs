@@ -0,0 +1,107 @@
+use serde::{Serialize, Deserialize};
+
+// 按应用设置的录制策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingMode {
+    // 正常记录按键
+    #[default]
+    Full,
+    // 只按分钟记录按键次数，不保存按键内容、按键时间和节奏
+    CountsOnly,
+    // 完全忽略
+    Ignore,
+}
+
+// 一条应用策略，pattern 为进程名（如 "KeePassXC.exe"），支持 * 和 ? 通配符，不区分大小写
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppPolicy {
+    pub pattern: String,
+    pub mode: RecordingMode,
+}
+
+impl AppPolicy {
+    pub fn matches(&self, app_name: &str) -> bool {
+        matches_app_pattern(&self.pattern, app_name)
+    }
+}
+
+// 进程名匹配，支持 * 和 ? 通配符，不区分大小写
+pub fn matches_app_pattern(pattern: &str, app_name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = app_name.to_lowercase().chars().collect();
+    wildcard_match(&pattern, &name)
+}
+
+// 按顺序查找第一条匹配的策略，没有匹配时正常记录
+pub fn mode_for_app(policies: &[AppPolicy], app_name: &str) -> RecordingMode {
+    policies.iter()
+        .find(|policy| policy.matches(app_name))
+        .map(|policy| policy.mode)
+        .unwrap_or_default()
+}
+
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // 最近一个 * 的位置及其匹配到的名称位置，用于回溯
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_wildcard_patterns() {
+        let cases = [
+            ("完全相同", "KeePassXC.exe", "KeePassXC.exe", true),
+            ("不区分大小写", "keepassxc.EXE", "KeePassXC.exe", true),
+            ("名称不同", "KeePassXC.exe", "KeePass.exe", false),
+            ("前缀不完整", "KeePass", "KeePassXC.exe", false),
+            ("* 匹配任意后缀", "KeePass*", "KeePassXC.exe", true),
+            ("* 匹配任意前缀", "*.exe", "1Password.exe", true),
+            ("* 匹配空字符串", "Code*.exe", "Code.exe", true),
+            ("多个 * 需要回溯", "*pass*.exe", "KeePassXC.exe", true),
+            ("* 之后的部分不匹配", "*.exe", "firefox", false),
+            ("只有 * 匹配任意名称", "*", "firefox", true),
+            ("只有 * 匹配空名称", "*", "", true),
+            ("? 匹配一个字符", "Code?.exe", "Code2.exe", true),
+            ("? 不匹配空字符", "Code?.exe", "Code.exe", false),
+            ("? 不匹配多个字符", "Code?.exe", "Code12.exe", false),
+            ("? 和 * 组合", "?*.exe", "a.exe", true),
+            ("? 和 * 组合缺少字符", "?*.exe", ".exe", false),
+            ("中文名称", "微信*", "微信.exe", true),
+            ("空模式只匹配空名称", "", "Code.exe", false),
+        ];
+        for (name, pattern, app_name, expected) in cases {
+            assert_eq!(matches_app_pattern(pattern, app_name), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn uses_the_first_matching_policy() {
+        let policies = vec![
+            AppPolicy { pattern: "KeePass*".to_string(), mode: RecordingMode::Ignore },
+            AppPolicy { pattern: "*.exe".to_string(), mode: RecordingMode::CountsOnly },
+        ];
+        assert_eq!(mode_for_app(&policies, "KeePassXC.exe"), RecordingMode::Ignore);
+        assert_eq!(mode_for_app(&policies, "Code.exe"), RecordingMode::CountsOnly);
+        assert_eq!(mode_for_app(&policies, "code"), RecordingMode::Full);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::database::KeyboardEventRecord;
+use chrono::{DateTime, Local, Timelike};
+
+// 隐私模式：字符键（字母、数字、符号，含 Shift 组合）以及空格、回车、退格、Shift 只按分钟保存，
+// 同一分钟内的记录按按键排序后写入，无法还原输入顺序；其他组合键和功能键保持原样
+
+// 不输入字符，但能还原单词边界、修改位置的按键，与字符键一样处理
+const BOUNDARY_KEYS: &[&str] = &["Space", "Enter", "Backspace"];
+
+// 是否为可输入字符的按键，例如 "a"、"7"、"Shift+;"
+pub fn is_character_key(key_code: &str) -> bool {
+    let base = key_code.strip_prefix("Shift+").unwrap_or(key_code);
+    let mut chars = base.chars();
+    matches!((chars.next(), chars.next()), (Some(c), None) if c.is_ascii_graphic())
+}
+
+// 隐私模式下按分钟保存的按键：字符键、边界键（含 Shift 组合）和单独按下的 Shift
+pub fn is_private_key(key_code: &str) -> bool {
+    let base = key_code.strip_prefix("Shift+").unwrap_or(key_code);
+    is_character_key(key_code) || BOUNDARY_KEYS.contains(&base) || key_code == "Shift"
+}
+
+// 截断到所在分钟的开始
+pub fn truncate_to_minute(timestamp: DateTime<Local>) -> DateTime<Local> {
+    timestamp.with_second(0)
+        .and_then(|t| t.with_nanosecond(0))
+        .unwrap_or(timestamp)
+}
+
+// 降低记录精度：时间截断到分钟，去掉可用于推断输入顺序的按键节奏
+fn harden(mut record: KeyboardEventRecord) -> KeyboardEventRecord {
+    record.timestamp = truncate_to_minute(record.timestamp);
+    record.hold_ms = None;
+    record.flight_ms = None;
+    record
+}
+
+// 当前分钟内需要隐藏顺序的按键记录，进入下一分钟后整体释放
+#[derive(Default)]
+pub struct CharacterBucket {
+    minute: Option<DateTime<Local>>,
+    records: Vec<KeyboardEventRecord>,
+}
+
+impl CharacterBucket {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 加入一条记录，返回因分钟切换而释放的记录
+    pub fn add(&mut self, record: KeyboardEventRecord) -> Vec<KeyboardEventRecord> {
+        let record = harden(record);
+        let released = self.release_before(record.timestamp);
+        self.minute = self.minute.max(Some(record.timestamp));
+        self.records.push(record);
+        released
+    }
+
+    // 释放早于 now 所在分钟的记录
+    pub fn release_before(&mut self, now: DateTime<Local>) -> Vec<KeyboardEventRecord> {
+        match self.minute {
+            Some(minute) if minute < truncate_to_minute(now) => self.drain(),
+            _ => Vec::new(),
+        }
+    }
+
+    // 释放全部记录，按按键和应用排序以打乱原有顺序
+    pub fn drain(&mut self) -> Vec<KeyboardEventRecord> {
+        self.minute = None;
+        let mut records = std::mem::take(&mut self.records);
+        records.sort_by(|a, b| {
+            (a.timestamp, &a.app_name, &a.key_code).cmp(&(b.timestamp, &b.app_name, &b.key_code))
+        });
+        records
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{harden_character_events, insert_event, query_events_by_time_range};
+    use crate::store::{MemoryStore, SqliteStore, Store};
+    use chrono::{Duration, TimeZone};
+
+    // 按键名称、是否为字符键、隐私模式下是否按分钟保存
+    const KEY_CASES: &[(&str, bool, bool)] = &[
+        ("a", true, true),
+        ("7", true, true),
+        (";", true, true),
+        ("Shift+;", true, true),
+        ("Shift+A", true, true),
+        ("Space", false, true),
+        ("Enter", false, true),
+        ("Backspace", false, true),
+        ("Shift+Space", false, true),
+        ("Shift+Enter", false, true),
+        ("Shift", false, true),
+        ("Ctrl+C", false, false),
+        ("Ctrl+Shift", false, false),
+        ("Ctrl+Space", false, false),
+        ("Shift+Tab", false, false),
+        ("Tab", false, false),
+        ("F5", false, false),
+        ("Esc", false, false),
+        ("中", false, false),
+        ("", false, false),
+        ("[masked]", false, false),
+    ];
+
+    fn time(minute: u32, second: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 3, 1, 9, minute, second).unwrap()
+    }
+
+    fn record(key_code: &str, timestamp: DateTime<Local>) -> KeyboardEventRecord {
+        KeyboardEventRecord {
+            timestamp,
+            key_code: key_code.to_string(),
+            app_name: "Code.exe".to_string(),
+            hold_ms: Some(80),
+            flight_ms: Some(150),
+            raw_key: None,
+            scan_code: None,
+            repeat_count: 0,
+            device_id: None,
+            context_tag: None,
+        }
+    }
+
+    #[test]
+    fn classifies_private_keys() {
+        for (key_code, character, private) in KEY_CASES {
+            assert_eq!(is_character_key(key_code), *character, "字符键: {:?}", key_code);
+            assert_eq!(is_private_key(key_code), *private, "按分钟保存: {:?}", key_code);
+        }
+    }
+
+    #[test]
+    fn releases_a_minute_at_a_time() {
+        let mut bucket = CharacterBucket::new();
+        assert!(bucket.add(record("b", time(0, 5))).is_empty());
+        assert!(bucket.add(record("Space", time(0, 6))).is_empty());
+        assert!(bucket.add(record("a", time(0, 40))).is_empty());
+        assert!(bucket.release_before(time(0, 59)).is_empty());
+
+        let released = bucket.release_before(time(1, 0));
+        // 同一分钟内按按键排序，时间截断到分钟并去掉按键节奏
+        assert_eq!(released.iter().map(|r| r.key_code.as_str()).collect::<Vec<_>>(), ["Space", "a", "b"]);
+        assert!(released.iter().all(|r| r.timestamp == time(0, 0) && r.hold_ms.is_none() && r.flight_ms.is_none()));
+        assert!(bucket.release_before(time(5, 0)).is_empty());
+
+        // 加入下一分钟的记录时释放上一分钟的记录
+        assert!(bucket.add(record("c", time(1, 30))).is_empty());
+        let released = bucket.add(record("d", time(2, 1)));
+        assert_eq!(released.iter().map(|r| r.key_code.as_str()).collect::<Vec<_>>(), ["c"]);
+        let drained = bucket.drain();
+        assert_eq!(drained.iter().map(|r| r.key_code.as_str()).collect::<Vec<_>>(), ["d"]);
+        assert!(bucket.drain().is_empty());
+    }
+
+    #[test]
+    fn hardens_the_same_keys_in_sql() {
+        let store = MemoryStore::new().unwrap();
+        let mut conn = store.write().unwrap();
+        for (key_code, _, _) in KEY_CASES {
+            insert_event(&conn, &record(key_code, time(0, 30))).unwrap();
+        }
+
+        let count = harden_character_events(&mut conn).unwrap();
+        assert_eq!(count, KEY_CASES.iter().filter(|(_, _, private)| *private).count());
+
+        let events = query_events_by_time_range(&conn, time(0, 0), time(0, 0) + Duration::minutes(1)).unwrap();
+        assert_eq!(events.len(), KEY_CASES.len());
+        for event in events {
+            let private = is_private_key(&event.key_code);
+            assert_eq!(event.hold_ms.is_none(), private, "{:?}", event.key_code);
+            assert_eq!(event.flight_ms.is_none(), private, "{:?}", event.key_code);
+            assert_eq!(event.timestamp == time(0, 0), private, "{:?}", event.key_code);
+        }
+    }
+
+    #[test]
+    fn scrubs_the_wal_and_restores_secure_delete() {
+        let dir = std::env::temp_dir().join(format!("keyboard-statistics-privacy-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("keyboard_events.db");
+        let store = SqliteStore::open(&path).unwrap();
+        let mut conn = store.write().unwrap();
+        insert_event(&conn, &record("a", time(0, 30))).unwrap();
+
+        assert_eq!(harden_character_events(&mut conn).unwrap(), 1);
+        let secure_delete: i64 = conn.query_row("PRAGMA secure_delete", [], |row| row.get(0)).unwrap();
+        assert_eq!(secure_delete, 0);
+        // 检查点之后 WAL 中不再有原来的数据页
+        assert_eq!(std::fs::metadata(dir.join("keyboard_events.db-wal")).unwrap().len(), 0);
+        drop(conn);
+        drop(store);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
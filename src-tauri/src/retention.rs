@@ -0,0 +1,267 @@
+// 数据保留策略：超过保留期的原始按键记录并入汇总后删除，更细的汇总也可以按天数清理，
+// 较早的数据只能按小时或按天查看。天汇总始终保留
+use crate::database::{get_meta, raw_retained_from, set_meta, HOUR_RETAINED_FROM, MINUTE_RETAINED_FROM, RAW_RETAINED_FROM};
+use crate::rollup::{self, Granularity};
+use crate::store::Store;
+use chrono::{DateTime, Duration, Local};
+use rusqlite::{params, Connection, Result};
+use serde::{Serialize, Deserialize};
+
+// 后台维护的执行间隔
+pub const MAINTENANCE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(6 * 60 * 60);
+// 每个事务清理一天的原始记录，避免长时间占用写锁导致写入线程超时
+const CHUNK_MS: i64 = 24 * 60 * 60 * 1000;
+
+// 各类数据的保留天数，为空时永久保留
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionSettings {
+    #[serde(default)]
+    pub raw_days: Option<u32>, // 原始按键记录（按键时长、间隔、设备、上下文等统计依赖原始记录）
+    #[serde(default)]
+    pub minute_days: Option<u32>, // 分钟汇总
+    #[serde(default)]
+    pub hour_days: Option<u32>, // 小时汇总
+}
+
+impl RetentionSettings {
+    // 较细的数据不能比较粗的数据保留得更久，否则查询时会读到已清理的汇总
+    pub fn validate(&self) -> Result<(), String> {
+        let days = [("原始记录", self.raw_days), ("分钟汇总", self.minute_days), ("小时汇总", self.hour_days)];
+        for (name, value) in days {
+            if value == Some(0) {
+                return Err(format!("{}的保留天数至少为 1 天", name));
+            }
+        }
+        for pair in days.windows(2) {
+            let (finer, finer_days) = pair[0];
+            let (coarser, coarser_days) = pair[1];
+            let longer = match (finer_days, coarser_days) {
+                (None, Some(_)) => true,
+                (Some(finer_days), Some(coarser_days)) => finer_days > coarser_days,
+                _ => false,
+            };
+            if longer {
+                return Err(format!("{}的保留天数不能超过{}", finer, coarser));
+            }
+        }
+        Ok(())
+    }
+}
+
+// 一次维护清理的数据量
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetentionReport {
+    pub raw_events: usize,
+    pub minute_buckets: usize,
+    pub hour_buckets: usize,
+}
+
+// 保留期的起点，按当前时区对齐到当天零点
+fn cutoff(now: DateTime<Local>, days: u32) -> i64 {
+    rollup::floor_local((now - Duration::days(days as i64)).timestamp_millis(), Granularity::Day)
+}
+
+// 按保留策略清理旧数据
+pub fn apply_retention(store: &dyn Store, settings: &RetentionSettings, now: DateTime<Local>) -> Result<RetentionReport> {
+    let mut report = RetentionReport::default();
+    if let Some(days) = settings.raw_days {
+        report.raw_events = purge_raw_events(store, cutoff(now, days))?;
+    }
+    let conn = store.write()?;
+    if let Some(days) = settings.minute_days {
+        report.minute_buckets = purge_rollup(&conn, Granularity::Minute, cutoff(now, days))?;
+    }
+    if let Some(days) = settings.hour_days {
+        report.hour_buckets = purge_rollup(&conn, Granularity::Hour, cutoff(now, days))?;
+    }
+    Ok(report)
+}
+
+// 删除 cutoff_ms 之前的原始按键记录。上次清理之后的部分先按原始记录重新计算汇总，
+// 确保删除的记录都已计入汇总；按键统计和应用统计是累计值，不扣减
+fn purge_raw_events(store: &dyn Store, cutoff_ms: i64) -> Result<usize> {
+    let conn = store.write()?;
+    let retained_from = raw_retained_from(&conn)?;
+    // 上次清理的时间点之前的记录（例如系统时间被调回后写入的）已经累加到汇总中，直接删除
+    let mut purged = conn.execute(
+        "DELETE FROM keyboard_events WHERE ts < ?1",
+        params![retained_from.min(cutoff_ms)],
+    )?;
+    if cutoff_ms <= retained_from {
+        return Ok(purged);
+    }
+
+    let first_ts: Option<i64> = conn.query_row(
+        "SELECT MIN(ts) FROM keyboard_events WHERE ts >= ?1 AND ts < ?2",
+        params![retained_from, cutoff_ms],
+        |row| row.get(0),
+    )?;
+    let Some(first_ts) = first_ts else {
+        set_meta(&conn, RAW_RETAINED_FROM, cutoff_ms)?;
+        return Ok(purged);
+    };
+    drop(conn);
+
+    // 按天对齐，使每段都由完整的桶组成；每段结束后归还写连接，其间写入线程可以写入新的按键
+    let mut chunk_start = rollup::floor_local(first_ts, Granularity::Day).max(retained_from);
+    while chunk_start < cutoff_ms {
+        let chunk_end = (chunk_start + CHUNK_MS).min(cutoff_ms);
+        let mut conn = store.write()?;
+        let tx = conn.transaction()?;
+        rollup::rebuild_buckets(&tx, chunk_start, chunk_end)?;
+        purged += tx.execute(
+            "DELETE FROM keyboard_events WHERE ts >= ?1 AND ts < ?2",
+            params![chunk_start, chunk_end],
+        )?;
+        set_meta(&tx, RAW_RETAINED_FROM, chunk_end)?;
+        tx.commit()?;
+        chunk_start = chunk_end;
+    }
+    Ok(purged)
+}
+
+// 删除 cutoff_ms 之前的汇总并记录保留起点，查询时不再读取已清理的部分
+fn purge_rollup(conn: &Connection, granularity: Granularity, cutoff_ms: i64) -> Result<usize> {
+    let key = match granularity {
+        Granularity::Minute => MINUTE_RETAINED_FROM,
+        _ => HOUR_RETAINED_FROM,
+    };
+    let purged = conn.execute(
+        &format!("DELETE FROM {} WHERE bucket_ts < ?1", granularity.table().unwrap_or_default()),
+        params![cutoff_ms],
+    )?;
+    if get_meta(conn, key)?.unwrap_or(0) < cutoff_ms {
+        set_meta(conn, key, cutoff_ms)?;
+    }
+    Ok(purged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{delete_data_by_time_range, get_first_event_time};
+    use crate::rollup::{rebuild_rollups, source_sql};
+    use crate::store::MemoryStore;
+    use crate::test_support::{count_from, insert_sample_events, snapshot};
+
+    fn sample_store() -> (MemoryStore, DateTime<Local>) {
+        let store = MemoryStore::new().unwrap();
+        let base = insert_sample_events(&store.write().unwrap());
+        (store, base)
+    }
+
+    fn total(conn: &Connection, start: DateTime<Local>, end: DateTime<Local>) -> i64 {
+        count_from(conn, &source_sql(conn, &start, &end, Granularity::Day).unwrap())
+    }
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn validates_retention_order() {
+        assert!(RetentionSettings::default().validate().is_ok());
+        let settings = RetentionSettings { raw_days: Some(30), minute_days: Some(90), hour_days: None };
+        assert!(settings.validate().is_ok());
+        let settings = RetentionSettings { raw_days: Some(30), minute_days: None, hour_days: Some(365) };
+        assert!(settings.validate().is_err());
+        let settings = RetentionSettings { raw_days: Some(0), minute_days: None, hour_days: None };
+        assert!(settings.validate().is_err());
+        let settings = RetentionSettings { raw_days: Some(30), minute_days: Some(7), hour_days: None };
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn purged_events_stay_in_rollups() {
+        let (store, base) = sample_store();
+        let end = base + Duration::days(4);
+        let cutoff = base + Duration::days(2);
+        let old_events_sql = format!("SELECT COUNT(*) FROM keyboard_events WHERE ts < {}", cutoff.timestamp_millis());
+        let (old_events, hourly_before) = {
+            let conn = store.read().unwrap();
+            (count(&conn, &old_events_sql), snapshot(&conn, "rollup_hour"))
+        };
+        assert!(old_events > 0);
+
+        let settings = RetentionSettings { raw_days: Some(2), minute_days: None, hour_days: None };
+        let report = apply_retention(&store, &settings, end).unwrap();
+        assert_eq!(report.raw_events as i64, old_events);
+        {
+            let conn = store.read().unwrap();
+            assert_eq!(count(&conn, &old_events_sql), 0);
+            assert_eq!(total(&conn, base, end), 600);
+            assert_eq!(snapshot(&conn, "rollup_hour"), hourly_before);
+            assert_eq!(get_first_event_time(&conn).unwrap(), Some(base));
+        }
+
+        // 重建汇总和再次清理都不会丢失已清理时间段的汇总
+        rebuild_rollups(&mut store.write().unwrap()).unwrap();
+        assert_eq!(total(&store.read().unwrap(), base, end), 600);
+        assert_eq!(apply_retention(&store, &settings, end).unwrap().raw_events, 0);
+
+        // 删除已清理的时间段时按汇总扣减
+        let mut conn = store.write().unwrap();
+        assert_eq!(total(&conn, base, end), 600);
+        let first_day = total(&conn, base, base + Duration::days(1) - Duration::milliseconds(1));
+        let deleted = delete_data_by_time_range(&mut conn, base, base + Duration::days(1) - Duration::milliseconds(1)).unwrap();
+        assert_eq!(deleted as i64, first_day);
+        assert_eq!(total(&conn, base, end), 600 - first_day);
+        assert_eq!(count(&conn, "SELECT SUM(count) FROM key_stats"), 600 - first_day);
+    }
+
+    #[test]
+    fn deletes_planned_buckets_in_purged_periods() {
+        let (store, base) = sample_store();
+        let end = base + Duration::days(4);
+        let settings = RetentionSettings { raw_days: Some(2), minute_days: Some(2), hour_days: None };
+        apply_retention(&store, &settings, end).unwrap();
+
+        // 分钟汇总已清理，两端不足一小时的部分与统计一样按小时桶整桶删除
+        let mut conn = store.write().unwrap();
+        let start = base + Duration::hours(10) + Duration::minutes(30);
+        let stop = base + Duration::hours(14) + Duration::minutes(30);
+        let hour_sql = |hour: i64| format!(
+            "SELECT COALESCE(SUM(count), 0) FROM rollup_hour WHERE bucket_ts = {}",
+            (base + Duration::hours(hour)).timestamp_millis()
+        );
+        let tenth_hour = count(&conn, &hour_sql(10));
+        let in_range = total(&conn, start, stop);
+        let before = total(&conn, base, end);
+        assert!(in_range > 0);
+        let deleted = delete_data_by_time_range(&mut conn, start, stop).unwrap();
+        assert_eq!(deleted as i64, in_range);
+        assert_eq!(total(&conn, start, stop), 0);
+        assert_eq!(total(&conn, base, end), before - in_range);
+        assert_eq!(count(&conn, "SELECT SUM(count) FROM key_stats"), before - in_range);
+        assert_eq!(count(&conn, "SELECT SUM(key_count) FROM app_stats"), before - in_range);
+        assert_eq!(count(&conn, &hour_sql(10)), tenth_hour);
+        assert_eq!(count(&conn, &hour_sql(14)), 0);
+        // 各级汇总保持一致
+        assert_eq!(count(&conn, "SELECT SUM(count) FROM rollup_day"), before - in_range);
+        assert_eq!(count(&conn, "SELECT SUM(count) FROM rollup_hour"), before - in_range);
+    }
+
+    #[test]
+    fn purges_finer_rollups() {
+        let (store, base) = sample_store();
+        let end = base + Duration::days(4);
+        let settings = RetentionSettings { raw_days: Some(2), minute_days: Some(3), hour_days: None };
+        let report = apply_retention(&store, &settings, end).unwrap();
+        assert!(report.minute_buckets > 0);
+        let conn = store.read().unwrap();
+        assert_eq!(count(&conn, &format!("SELECT COUNT(*) FROM rollup_minute WHERE bucket_ts < {}", (base + Duration::days(1)).timestamp_millis())), 0);
+        // 整天的统计不受影响
+        assert_eq!(total(&conn, base, end - Duration::milliseconds(1)), 600);
+        // 分钟汇总已清理的时间段，两端不足一小时的部分对齐到小时汇总，开始时间在范围内的桶整桶计入
+        let hours_sql = format!(
+            "SELECT SUM(count) FROM rollup_hour WHERE bucket_ts BETWEEN {} AND {}",
+            (base + Duration::hours(1)).timestamp_millis(),
+            (base + Duration::hours(5)).timestamp_millis()
+        );
+        let start = base + Duration::minutes(37);
+        let end = base + Duration::hours(5) + Duration::minutes(10);
+        assert_eq!(total(&conn, start, end), count(&conn, &hours_sql));
+        let retained = rollup::Retained::load(&conn).unwrap();
+        assert!(rollup::plan(&start, &end, Granularity::Day, &retained).iter().all(|(granularity, _, _)| *granularity == Granularity::Hour));
+    }
+}
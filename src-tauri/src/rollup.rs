@@ -0,0 +1,362 @@
+// 按键汇总表：按分钟、小时、天统计每个应用每个按键的次数，写入按键时同步累加，
+// 仪表盘按时间范围统计时优先读汇总表，避免每次都扫描原始记录。
+//
+// 桶按记录时的本地时间对齐，bucket_ts 为桶开始的 UTC 毫秒，utc_offset 为记录时的时区偏移，
+// 因此按小时、日期分组的结果与直接读原始记录一致
+use crate::database::{
+    from_epoch_millis, get_meta, raw_retained_from, utc_offset_seconds, KeyboardEventRecord, HOUR_RETAINED_FROM,
+    MINUTE_RETAINED_FROM,
+};
+use chrono::{DateTime, Local};
+use rusqlite::{params, Connection, Result};
+
+const MINUTE_MS: i64 = 60_000;
+const HOUR_MS: i64 = 60 * MINUTE_MS;
+const DAY_MS: i64 = 24 * HOUR_MS;
+
+// 统计数据的粒度，Raw 表示原始记录
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Granularity {
+    Raw,
+    Minute,
+    Hour,
+    Day,
+}
+
+// 有汇总表的粒度，从细到粗
+pub const ROLLUP_GRANULARITIES: [Granularity; 3] = [Granularity::Minute, Granularity::Hour, Granularity::Day];
+
+impl Granularity {
+    pub fn table(&self) -> Option<&'static str> {
+        match self {
+            Granularity::Raw => None,
+            Granularity::Minute => Some("rollup_minute"),
+            Granularity::Hour => Some("rollup_hour"),
+            Granularity::Day => Some("rollup_day"),
+        }
+    }
+
+    pub fn bucket_ms(&self) -> i64 {
+        match self {
+            Granularity::Raw => 1,
+            Granularity::Minute => MINUTE_MS,
+            Granularity::Hour => HOUR_MS,
+            Granularity::Day => DAY_MS,
+        }
+    }
+
+    fn finer(&self) -> Granularity {
+        match self {
+            Granularity::Day => Granularity::Hour,
+            Granularity::Hour => Granularity::Minute,
+            Granularity::Minute | Granularity::Raw => Granularity::Raw,
+        }
+    }
+}
+
+// 各粒度数据的保留起点（UTC 毫秒），更早的部分已按保留策略清理。天汇总始终保留
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Retained {
+    pub raw: i64,
+    pub minute: i64,
+    pub hour: i64,
+}
+
+impl Retained {
+    pub fn load(conn: &Connection) -> Result<Self> {
+        Ok(Self {
+            raw: raw_retained_from(conn)?,
+            minute: get_meta(conn, MINUTE_RETAINED_FROM)?.unwrap_or(0),
+            hour: get_meta(conn, HOUR_RETAINED_FROM)?.unwrap_or(0),
+        })
+    }
+
+    // ms 所在时间点该粒度的数据是否仍然保留
+    fn keeps(&self, granularity: Granularity, ms: i64) -> bool {
+        match granularity {
+            Granularity::Raw => ms >= self.raw,
+            Granularity::Minute => ms >= self.minute,
+            Granularity::Hour => ms >= self.hour,
+            Granularity::Day => true,
+        }
+    }
+}
+
+// 桶开始时间（UTC 毫秒），与 bucket_sql 的计算一致
+pub fn bucket_start(ts: i64, utc_offset: i32, granularity: Granularity) -> i64 {
+    ts - (ts + utc_offset as i64 * 1000).rem_euclid(granularity.bucket_ms())
+}
+
+// 由原始记录的 ts、utc_offset 列计算桶开始时间
+fn bucket_sql(granularity: Granularity) -> String {
+    format!("(ts - (ts + utc_offset * 1000) % {})", granularity.bucket_ms())
+}
+
+// 写入一条按键记录时累加各粒度的计数，与 insert_event 在同一事务中执行
+pub fn record_event(conn: &Connection, event: &KeyboardEventRecord, app_id: i64, key_id: i64) -> Result<()> {
+    let ts = event.timestamp.timestamp_millis();
+    let utc_offset = utc_offset_seconds(&event.timestamp);
+    for granularity in ROLLUP_GRANULARITIES {
+        let table = granularity.table().unwrap_or_default();
+        conn.prepare_cached(&format!(
+            "INSERT INTO {} (bucket_ts, utc_offset, app_id, key_id, count)
+             VALUES (?1, ?2, ?3, ?4, 1)
+             ON CONFLICT(bucket_ts, app_id, key_id) DO UPDATE SET
+             count = count + 1",
+            table
+        ))?.execute(params![
+            bucket_start(ts, utc_offset, granularity),
+            utc_offset,
+            app_id,
+            key_id
+        ])?;
+    }
+    Ok(())
+}
+
+// 按原始记录重新计算开始时间在 [start_ms, end_ms) 内的桶
+pub fn rebuild_buckets(conn: &Connection, start_ms: i64, end_ms: i64) -> Result<()> {
+    for granularity in ROLLUP_GRANULARITIES {
+        let table = granularity.table().unwrap_or_default();
+        let bucket = bucket_sql(granularity);
+        conn.execute(
+            &format!("DELETE FROM {} WHERE bucket_ts >= ?1 AND bucket_ts < ?2", table),
+            params![start_ms, end_ms],
+        )?;
+        // 桶内的记录都在 [bucket_ts, bucket_ts + 桶长度) 之内，先按 ts 缩小范围以使用索引
+        conn.execute(
+            &format!(
+                "INSERT INTO {table} (bucket_ts, utc_offset, app_id, key_id, count)
+                 SELECT {bucket}, MAX(utc_offset), app_id, key_id, COUNT(*)
+                 FROM keyboard_events
+                 WHERE ts >= ?1 AND ts < ?2 + {size} AND {bucket} >= ?1 AND {bucket} < ?2
+                 GROUP BY {bucket}, app_id, key_id",
+                table = table,
+                bucket = bucket,
+                size = granularity.bucket_ms(),
+            ),
+            params![start_ms, end_ms],
+        )?;
+    }
+    Ok(())
+}
+
+// 按全部原始记录重新生成汇总，返回参与汇总的记录数。
+// 原始记录已按保留策略清理的时间段只剩汇总，保持不变
+pub fn rebuild_rollups(conn: &mut Connection) -> Result<i64> {
+    let tx = conn.transaction()?;
+    let retained_from = raw_retained_from(&tx)?;
+    for granularity in ROLLUP_GRANULARITIES {
+        let table = granularity.table().unwrap_or_default();
+        let bucket = bucket_sql(granularity);
+        tx.execute(&format!("DELETE FROM {} WHERE bucket_ts >= ?1", table), params![retained_from])?;
+        tx.execute(
+            &format!(
+                "INSERT INTO {table} (bucket_ts, utc_offset, app_id, key_id, count)
+                 SELECT {bucket}, MAX(utc_offset), app_id, key_id, COUNT(*)
+                 FROM keyboard_events
+                 WHERE ts >= ?1 AND {bucket} >= ?1
+                 GROUP BY {bucket}, app_id, key_id",
+                table = table,
+                bucket = bucket,
+            ),
+            params![retained_from],
+        )?;
+    }
+    let count = tx.query_row("SELECT COUNT(*) FROM keyboard_events WHERE ts >= ?1", params![retained_from], |row| row.get(0))?;
+    tx.commit()?;
+    Ok(count)
+}
+
+// 按当前时区对齐到桶的开始
+pub fn floor_local(ms: i64, granularity: Granularity) -> i64 {
+    bucket_start(ms, utc_offset_seconds(&from_epoch_millis(ms)), granularity)
+}
+
+fn ceil_local(ms: i64, granularity: Granularity) -> i64 {
+    let floor = floor_local(ms, granularity);
+    if floor == ms { ms } else { floor + granularity.bucket_ms() }
+}
+
+// 把 [start_ms, end_ms) 拆成若干段：中间整段的桶用尽量粗的粒度，两端剩余部分逐级用更细的粒度
+fn split(start_ms: i64, end_ms: i64, granularity: Granularity, retained: &Retained, parts: &mut Vec<(Granularity, i64, i64)>) {
+    if start_ms >= end_ms {
+        return;
+    }
+    if granularity == Granularity::Raw {
+        parts.push((granularity, start_ms, end_ms));
+        return;
+    }
+    let first = ceil_local(start_ms, granularity);
+    let last = floor_local(end_ms, granularity);
+    if first <= last {
+        split_edge(start_ms, first, granularity, retained, parts);
+        if first < last {
+            parts.push((granularity, first, last));
+        }
+        split_edge(last, end_ms, granularity, retained, parts);
+    } else {
+        split_edge(start_ms, end_ms, granularity, retained, parts);
+    }
+}
+
+// 不足一桶的部分：更细的数据仍保留时继续拆分，已清理时对齐到当前粒度的桶，
+// 开始时间在范围内的桶整桶计入。保留起点按天对齐，这一部分不会跨过保留起点
+fn split_edge(start_ms: i64, end_ms: i64, granularity: Granularity, retained: &Retained, parts: &mut Vec<(Granularity, i64, i64)>) {
+    if start_ms >= end_ms {
+        return;
+    }
+    let finer = granularity.finer();
+    if retained.keeps(finer, start_ms) {
+        split(start_ms, end_ms, finer, retained, parts);
+        return;
+    }
+    let first = ceil_local(start_ms, granularity);
+    if first < end_ms {
+        parts.push((granularity, first, ceil_local(end_ms, granularity)));
+    }
+}
+
+// 段的边界按当前时区对齐，其他时区下记录的桶如果正好跨过边界，会整桶计入相邻的一段
+pub fn plan(start_time: &DateTime<Local>, end_time: &DateTime<Local>, coarsest: Granularity, retained: &Retained) -> Vec<(Granularity, i64, i64)> {
+    let mut parts = Vec::new();
+    // 与 BETWEEN 一致，包含结束时间
+    split(start_time.timestamp_millis(), end_time.timestamp_millis() + 1, coarsest, retained, &mut parts);
+    parts
+}
+
+// 时间范围内按键次数的子查询，包含 ts、utc_offset、app_id、key_id、app_name、key_code、count 列，
+// 汇总表的 ts 为桶开始时间。按应用或按键分组时按编号分组，名称只在结果中连接一次。
+// coarsest 为允许使用的最粗粒度，例如按小时分组的统计最粗只能读小时汇总
+pub fn source_sql(conn: &Connection, start_time: &DateTime<Local>, end_time: &DateTime<Local>, coarsest: Granularity) -> Result<String> {
+    let retained = Retained::load(conn)?;
+    Ok(parts_sql(&plan(start_time, end_time, coarsest, &retained)))
+}
+
+// 一段内按键次数的子查询，包含 ts、utc_offset、app_id、key_id、count 列
+fn part_sql(granularity: Granularity, start_ms: i64, end_ms: i64) -> String {
+    match granularity.table() {
+        Some(table) => format!(
+            "SELECT bucket_ts AS ts, utc_offset, app_id, key_id, count FROM {}
+             WHERE bucket_ts >= {} AND bucket_ts < {}",
+            table, start_ms, end_ms
+        ),
+        None => format!(
+            "SELECT ts, utc_offset, app_id, key_id, 1 AS count FROM keyboard_events
+             WHERE ts >= {} AND ts < {}",
+            start_ms, end_ms
+        ),
+    }
+}
+
+// plan 返回的各段合起来的子查询，列与 source_sql 相同
+pub fn parts_sql(parts: &[(Granularity, i64, i64)]) -> String {
+    let mut parts: Vec<String> = parts.iter()
+        .map(|&(granularity, start_ms, end_ms)| part_sql(granularity, start_ms, end_ms))
+        .collect();
+    if parts.is_empty() {
+        parts.push("SELECT ts, utc_offset, app_id, key_id, 1 AS count FROM keyboard_events WHERE 0".to_string());
+    }
+    format!(
+        "SELECT ts, utc_offset, app_id, key_id, app_name, key_code, count
+         FROM ({}) JOIN apps USING (app_id) JOIN keys USING (key_id)",
+        parts.join(" UNION ALL ")
+    )
+}
+
+// 删除 plan 返回的各段中的按键，与 parts_sql 统计的是同一组桶：先从更粗的汇总中扣除该段的次数，
+// 再删除该段内的原始记录和同级、更细的汇总。原始记录已清理的时间段也能保持各汇总一致。
+// 删除原始记录时在同一事务中调用
+pub fn delete_parts(conn: &Connection, parts: &[(Granularity, i64, i64)]) -> Result<()> {
+    for &(granularity, start_ms, end_ms) in parts {
+        let source = part_sql(granularity, start_ms, end_ms);
+        for coarser in ROLLUP_GRANULARITIES.into_iter().filter(|g| g.bucket_ms() > granularity.bucket_ms()) {
+            let table = coarser.table().unwrap_or_default();
+            conn.execute(
+                &format!(
+                    "UPDATE {table} SET count = {table}.count - removed.count
+                     FROM (
+                         SELECT {bucket} AS bucket_ts, app_id, key_id, SUM(count) AS count
+                         FROM ({source})
+                         GROUP BY {bucket}, app_id, key_id
+                     ) AS removed
+                     WHERE {table}.bucket_ts = removed.bucket_ts
+                       AND {table}.app_id = removed.app_id
+                       AND {table}.key_id = removed.key_id",
+                    table = table,
+                    bucket = bucket_sql(coarser),
+                    source = source,
+                ),
+                [],
+            )?;
+            conn.execute(&format!("DELETE FROM {} WHERE count <= 0", table), [])?;
+        }
+        conn.execute("DELETE FROM keyboard_events WHERE ts >= ?1 AND ts < ?2", params![start_ms, end_ms])?;
+        for finer in ROLLUP_GRANULARITIES.into_iter().filter(|g| g.bucket_ms() <= granularity.bucket_ms()) {
+            conn.execute(
+                &format!("DELETE FROM {} WHERE bucket_ts >= ?1 AND bucket_ts < ?2", finer.table().unwrap_or_default()),
+                params![start_ms, end_ms],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::delete_data_by_time_range;
+    use crate::test_support::{count_from, insert_sample_events, snapshot};
+    use chrono::Duration;
+
+    fn sample_db() -> (Connection, DateTime<Local>) {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::migrations::migrate(&mut conn, None).unwrap();
+        let base = insert_sample_events(&conn);
+        (conn, base)
+    }
+
+    fn raw_count(conn: &Connection, start: &DateTime<Local>, end: &DateTime<Local>) -> i64 {
+        conn.query_row(
+            "SELECT COUNT(*) FROM keyboard_events WHERE ts BETWEEN ?1 AND ?2",
+            params![start.timestamp_millis(), end.timestamp_millis()],
+            |row| row.get(0),
+        ).unwrap()
+    }
+
+    #[test]
+    fn rollups_match_raw_events() {
+        let (conn, base) = sample_db();
+        let ranges = [
+            (base, base + Duration::days(3)),
+            (base + Duration::milliseconds(59_999), base + Duration::hours(25) + Duration::seconds(7)),
+            (base + Duration::minutes(90), base + Duration::minutes(150)),
+            (base + Duration::seconds(30), base + Duration::seconds(31)),
+            (base + Duration::days(1), base + Duration::days(2)),
+        ];
+        for (start, end) in ranges {
+            let expected = raw_count(&conn, &start, &end);
+            for coarsest in [Granularity::Raw, Granularity::Minute, Granularity::Hour, Granularity::Day] {
+                assert_eq!(count_from(&conn, &source_sql(&conn, &start, &end, coarsest).unwrap()), expected, "{:?} {} - {}", coarsest, start, end);
+            }
+        }
+
+        // 整天的范围只读天汇总
+        let parts = plan(&base, &(base + Duration::days(2) - Duration::milliseconds(1)), Granularity::Day, &Retained::default());
+        assert_eq!(parts, vec![(Granularity::Day, base.timestamp_millis(), (base + Duration::days(2)).timestamp_millis())]);
+    }
+
+    #[test]
+    fn deleting_events_updates_rollups() {
+        let (mut conn, base) = sample_db();
+        let start = base + Duration::minutes(200) + Duration::seconds(13);
+        let end = base + Duration::hours(30);
+        assert!(delete_data_by_time_range(&mut conn, start, end).unwrap() > 0);
+
+        let tables = ROLLUP_GRANULARITIES.map(|granularity| granularity.table().unwrap());
+        let incremental: Vec<_> = tables.iter().map(|table| snapshot(&conn, table)).collect();
+        rebuild_rollups(&mut conn).unwrap();
+        let rebuilt: Vec<_> = tables.iter().map(|table| snapshot(&conn, table)).collect();
+        assert_eq!(incremental, rebuilt);
+    }
+}
@@ -0,0 +1,172 @@
+// 数据库访问层：程序启动时打开一次数据库并完成升级，之后命令、写入线程和后台维护共用。
+// 数据库文件使用 WAL 模式，只有一个写连接，统计查询使用只读连接池，读取时不会阻塞按键写入
+use crate::database::init_db;
+use rusqlite::{Connection, OpenFlags, Result};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// 空闲的只读连接最多保留的数量，超出的在归还时关闭
+const READ_POOL_SIZE: usize = 4;
+// 其他连接正在写入时等待的最长时间
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 借出的连接，释放时归还
+pub type StoreConnection<'a> = Box<dyn DerefMut<Target = Connection> + 'a>;
+
+// 持有一个连接时不要再借出另一个，内存实现只有一个连接，会互相等待
+pub trait Store: Send + Sync {
+    // 用于统计查询的连接
+    fn read(&self) -> Result<StoreConnection<'_>>;
+    // 写连接，同一时间只有一个调用方持有，修改数据的事务都在这里执行
+    fn write(&self) -> Result<StoreConnection<'_>>;
+}
+
+// 数据库文件
+pub struct SqliteStore {
+    path: PathBuf,
+    writer: Mutex<Connection>,
+    readers: Mutex<Vec<Connection>>,
+}
+
+impl SqliteStore {
+    // 打开数据库并升级到当前版本，然后切换到 WAL 模式
+    pub fn open(path: &Path) -> Result<Self> {
+        let path_str = path.to_str().ok_or(rusqlite::Error::InvalidPath(path.to_path_buf()))?;
+        let conn = init_db(path_str)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA synchronous = NORMAL;"
+        )?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        Ok(SqliteStore {
+            path: path.to_path_buf(),
+            writer: Mutex::new(conn),
+            readers: Mutex::new(Vec::new()),
+        })
+    }
+
+    fn open_reader(&self) -> Result<Connection> {
+        let conn = Connection::open_with_flags(
+            &self.path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX | OpenFlags::SQLITE_OPEN_URI,
+        )?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        Ok(conn)
+    }
+}
+
+impl Store for SqliteStore {
+    fn read(&self) -> Result<StoreConnection<'_>> {
+        let idle = self.readers.lock().unwrap().pop();
+        let conn = match idle {
+            Some(conn) => conn,
+            None => self.open_reader()?,
+        };
+        Ok(Box::new(PooledReader { conn: Some(conn), pool: &self.readers }))
+    }
+
+    fn write(&self) -> Result<StoreConnection<'_>> {
+        Ok(Box::new(self.writer.lock().unwrap()))
+    }
+}
+
+// 从连接池借出的只读连接
+struct PooledReader<'a> {
+    conn: Option<Connection>,
+    pool: &'a Mutex<Vec<Connection>>,
+}
+
+impl Deref for PooledReader<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledReader<'_> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledReader<'_> {
+    fn drop(&mut self) {
+        let Some(conn) = self.conn.take() else {
+            return;
+        };
+        let mut pool = self.pool.lock().unwrap();
+        if pool.len() < READ_POOL_SIZE {
+            pool.push(conn);
+        }
+    }
+}
+
+// 内存数据库，读写共用一个连接，用于测试
+pub struct MemoryStore {
+    conn: Mutex<Connection>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Result<Self> {
+        let mut conn = Connection::open_in_memory()?;
+        crate::migrations::migrate(&mut conn, None)?;
+        Ok(MemoryStore { conn: Mutex::new(conn) })
+    }
+}
+
+impl Store for MemoryStore {
+    fn read(&self) -> Result<StoreConnection<'_>> {
+        Ok(Box::new(self.conn.lock().unwrap()))
+    }
+
+    fn write(&self) -> Result<StoreConnection<'_>> {
+        Ok(Box::new(self.conn.lock().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{get_total_key_count, insert_event};
+    use crate::test_support::event;
+    use chrono::Local;
+    use std::fs;
+
+    #[test]
+    fn readers_see_committed_writes() {
+        let dir = std::env::temp_dir().join(format!("keyboard-statistics-store-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("store.db");
+        for stale in ["store.db", "store.db-wal", "store.db-shm"] {
+            let _ = fs::remove_file(dir.join(stale));
+        }
+
+        let store = SqliteStore::open(&path).unwrap();
+        let mode: String = store.read().unwrap().query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+        assert_eq!(mode, "wal");
+
+        // 读取期间写连接仍可提交，读连接之后可以看到
+        let reader = store.read().unwrap();
+        insert_event(&store.write().unwrap(), &event(Local::now(), "A", "Code.exe")).unwrap();
+        assert_eq!(get_total_key_count(&reader).unwrap(), 1);
+        // 只读连接不能修改数据
+        assert!(insert_event(&reader, &event(Local::now(), "A", "Code.exe")).is_err());
+        drop(reader);
+
+        // 连接归还后重复使用
+        let readers: Vec<_> = (0..READ_POOL_SIZE + 2).map(|_| store.read().unwrap()).collect();
+        drop(readers);
+        assert_eq!(store.readers.lock().unwrap().len(), READ_POOL_SIZE);
+        assert_eq!(get_total_key_count(&store.read().unwrap()).unwrap(), 1);
+    }
+
+    #[test]
+    fn memory_store_shares_one_connection() {
+        let store = MemoryStore::new().unwrap();
+        insert_event(&store.write().unwrap(), &event(Local::now(), "A", "Code.exe")).unwrap();
+        assert_eq!(get_total_key_count(&store.read().unwrap()).unwrap(), 1);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::input::STOP_POLL_INTERVAL;
+use chrono::{DateTime, Local};
+use serde::{Serialize, Deserialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+// 状态变化时发送到前端的事件名
+pub const MONITOR_STATUS_EVENT: &str = "monitor-status";
+
+// 输入源出错后的重启间隔，从1秒开始逐次翻倍，最长1分钟
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// 输入源连续运行超过该时长后再出错，重新从最短间隔开始
+pub const STABLE_RUN: Duration = Duration::from_secs(60);
+
+// 监听器生命周期
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MonitorState {
+    Stopped,  // 未启动或已停止
+    Starting, // 正在启动输入源（包括出错后的重启）
+    Running,  // 正在记录
+    Paused,   // 输入源在运行，但不记录
+    Failed,   // 输入源出错，等待重启
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonitorStatus {
+    pub state: MonitorState,
+    pub backend: Option<String>, // 当前使用的输入源
+    pub error: Option<String>,   // 最近一次出错的原因，重启成功后保留以便排查
+    pub restarts: u32,           // 本次启动以来自动重启的次数
+    pub since: DateTime<Local>,  // 进入当前状态的时间
+    #[serde(default)]
+    pub resume_at: Option<DateTime<Local>>, // 临时暂停时自动恢复的时间
+}
+
+impl MonitorStatus {
+    fn new() -> Self {
+        MonitorStatus {
+            state: MonitorState::Stopped,
+            backend: None,
+            error: None,
+            restarts: 0,
+            since: Local::now(),
+            resume_at: None,
+        }
+    }
+}
+
+// 共享的监听器状态，每次变化都通过 monitor-status 事件通知前端和托盘
+#[derive(Clone)]
+pub struct StatusReporter {
+    status: Arc<Mutex<MonitorStatus>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+}
+
+impl StatusReporter {
+    pub fn new() -> Self {
+        StatusReporter {
+            status: Arc::new(Mutex::new(MonitorStatus::new())),
+            app_handle: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn set_app_handle(&self, app_handle: AppHandle) {
+        *self.app_handle.lock().unwrap() = Some(app_handle);
+    }
+
+    pub fn status(&self) -> MonitorStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    fn update<F: FnOnce(&mut MonitorStatus)>(&self, update_fn: F) {
+        let snapshot = {
+            let mut status = self.status.lock().unwrap();
+            let previous = status.state;
+            update_fn(&mut status);
+            if status.state != previous {
+                status.since = Local::now();
+            }
+            status.clone()
+        };
+        if let Some(app_handle) = self.app_handle.lock().unwrap().as_ref() {
+            let _ = app_handle.emit(MONITOR_STATUS_EVENT, snapshot);
+        }
+    }
+
+    // 手动启动：清除上一次的错误和重启次数
+    pub fn starting(&self) {
+        self.update(|status| {
+            status.state = MonitorState::Starting;
+            status.error = None;
+            status.restarts = 0;
+        });
+    }
+
+    // 出错后自动重启
+    pub fn restarting(&self) {
+        self.update(|status| {
+            status.state = MonitorState::Starting;
+            status.restarts += 1;
+        });
+    }
+
+    pub fn running(&self, backend: &str, paused: bool) {
+        self.update(|status| {
+            status.state = if paused { MonitorState::Paused } else { MonitorState::Running };
+            status.backend = Some(backend.to_string());
+        });
+    }
+
+    // 暂停或恢复，只在输入源运行时改变状态；resume_at 为临时暂停的结束时间
+    pub fn set_paused(&self, paused: bool, resume_at: Option<DateTime<Local>>) {
+        self.update(|status| {
+            status.state = match status.state {
+                MonitorState::Running | MonitorState::Paused if paused => MonitorState::Paused,
+                MonitorState::Running | MonitorState::Paused => MonitorState::Running,
+                state => state,
+            };
+            status.resume_at = resume_at;
+        });
+    }
+
+    pub fn failed(&self, error: String) {
+        self.update(|status| {
+            status.state = MonitorState::Failed;
+            status.error = Some(error);
+        });
+    }
+
+    pub fn stopped(&self) {
+        self.update(|status| status.state = MonitorState::Stopped);
+    }
+}
+
+impl Default for StatusReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// 指数退避的重启间隔
+pub struct Backoff {
+    next: Duration,
+}
+
+impl Backoff {
+    pub fn new() -> Self {
+        Backoff { next: INITIAL_BACKOFF }
+    }
+
+    pub fn reset(&mut self) {
+        self.next = INITIAL_BACKOFF;
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = (self.next * 2).min(MAX_BACKOFF);
+        delay
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// 等待指定时长，期间收到停止信号则提前返回 true
+pub fn sleep_unless_stopped(stop: &AtomicBool, duration: Duration) -> bool {
+    let deadline = Instant::now() + duration;
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            return true;
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return false;
+        }
+        thread::sleep(remaining.min(STOP_POLL_INTERVAL));
+    }
+}
@@ -0,0 +1,47 @@
+// 测试共用的示例数据和查询
+use crate::database::{insert_event, KeyboardEventRecord};
+use chrono::{DateTime, Duration, Local, TimeZone};
+use rusqlite::Connection;
+
+pub fn event(timestamp: DateTime<Local>, key_code: &str, app_name: &str) -> KeyboardEventRecord {
+    KeyboardEventRecord {
+        timestamp,
+        key_code: key_code.to_string(),
+        app_name: app_name.to_string(),
+        hold_ms: None,
+        flight_ms: None,
+        raw_key: None,
+        scan_code: None,
+        repeat_count: 0,
+        device_id: None,
+        context_tag: None,
+    }
+}
+
+// 写入三天内不规则分布的 600 次按键，返回第一天零点
+pub fn insert_sample_events(conn: &Connection) -> DateTime<Local> {
+    let base = Local.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+    let keys = ["A", "Backspace", "Ctrl+C"];
+    let apps = ["Code.exe", "chrome.exe"];
+    for i in 0..600i64 {
+        let at = base + Duration::milliseconds(i * i * 713 + i * 37);
+        insert_event(conn, &event(at, keys[i as usize % 3], apps[i as usize % 2])).unwrap();
+    }
+    base
+}
+
+// 按键次数子查询（见 rollup::source_sql）的总次数
+pub fn count_from(conn: &Connection, source: &str) -> i64 {
+    conn.query_row(&format!("SELECT COALESCE(SUM(count), 0) FROM ({})", source), [], |row| row.get(0)).unwrap()
+}
+
+// 汇总表的全部内容，按桶、应用、按键排序
+pub fn snapshot(conn: &Connection, table: &str) -> Vec<(i64, String, String, i64)> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT bucket_ts, app_name, key_code, count FROM {} JOIN apps USING (app_id) JOIN keys USING (key_id)
+         ORDER BY bucket_ts, app_name, key_code",
+        table
+    )).unwrap();
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))).unwrap();
+    rows.collect::<rusqlite::Result<_>>().unwrap()
+}
@@ -1,19 +1,39 @@
 use tauri::{
     menu::{Menu, MenuItem},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
-    App, Manager,
+    App, Listener, Manager,
 };
 use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+use crate::supervisor::{MonitorState, MonitorStatus, MONITOR_STATUS_EVENT};
+
+// 托盘上显示的监听状态，出错时带上原因
+fn status_text(status: &MonitorStatus) -> String {
+    match status.state {
+        MonitorState::Stopped => "已停止记录".to_string(),
+        MonitorState::Starting => "正在启动监听…".to_string(),
+        MonitorState::Running => "正在记录".to_string(),
+        MonitorState::Paused => match status.resume_at {
+            Some(resume_at) => format!("临时暂停，{} 恢复", resume_at.format("%H:%M")),
+            None => "已暂停记录".to_string(),
+        },
+        MonitorState::Failed => format!(
+            "监听失败: {}",
+            status.error.as_deref().unwrap_or("未知错误")
+        ),
+    }
+}
 
 pub fn setup_tray(app: &App) -> Result<(), Box<dyn std::error::Error>> {
+    let initial_status = app.state::<crate::AppState>().keyboard_monitor.lock().unwrap().status();
+    let status = MenuItem::with_id(app, "status", status_text(&initial_status), false, None::<&str>)?;
     let show = MenuItem::with_id(app, "show", "显示窗口", true, None::<&str>)?;
     let about = MenuItem::with_id(app, "about", "关于", true, None::<&str>)?;
     let quit = MenuItem::with_id(app, "quit", "退出程序", true, None::<&str>)?;
-    let menu = Menu::with_items(app, &[&show, &about, &quit])?;
+    let menu = Menu::with_items(app, &[&status, &show, &about, &quit])?;
 
-    TrayIconBuilder::new()
+    let tray = TrayIconBuilder::new()
         .icon(app.default_window_icon().unwrap().clone())
-        .tooltip("右键显示菜单")
+        .tooltip(status_text(&initial_status))
         .menu(&menu)
         .menu_on_left_click(false)
         .on_menu_event(|app, event| match event.id.as_ref() {
@@ -49,5 +69,14 @@ pub fn setup_tray(app: &App) -> Result<(), Box<dyn std::error::Error>> {
         })
         .build(app)?;
 
+    // 监听器状态变化时更新托盘，出错时不再默默地什么都不记录
+    app.listen(MONITOR_STATUS_EVENT, move |event| {
+        if let Ok(monitor_status) = serde_json::from_str::<MonitorStatus>(event.payload()) {
+            let text = status_text(&monitor_status);
+            let _ = status.set_text(&text);
+            let _ = tray.set_tooltip(Some(&text));
+        }
+    });
+
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,224 @@
+use crate::database::{
+    insert_activity_interval, insert_event, insert_mouse_activity, insert_mouse_click, upsert_input_device,
+    ActivityInterval, InputDeviceRecord, KeyboardEventRecord, MouseActivityRecord, MouseClickRecord,
+};
+use crate::store::Store;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, sync_channel, RecvTimeoutError, Sender, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// 队列容量，写入线程跟不上时超出的事件会被丢弃而不是阻塞键盘钩子
+const QUEUE_CAPACITY: usize = 10_000;
+// 攒够该数量的事件立即写入
+const BATCH_SIZE: usize = 200;
+// 最长写入间隔
+const FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+
+// 写入线程处理的记录
+pub enum WriteRecord {
+    Key(KeyboardEventRecord),
+    Interval(ActivityInterval),
+    MouseClick(MouseClickRecord),
+    MouseActivity(MouseActivityRecord),
+    Device(InputDeviceRecord),
+}
+
+enum WriterMessage {
+    Record(WriteRecord),
+    Flush(Sender<()>),
+}
+
+#[derive(Default)]
+struct WriterCounters {
+    queued: AtomicU64,
+    written: AtomicU64,
+    dropped: AtomicU64,
+    failed: AtomicU64,
+}
+
+// 写入线程的计数快照
+#[derive(Debug, Clone, Serialize)]
+pub struct WriterStats {
+    pub queued: u64,  // 队列中等待写入的记录数
+    pub written: u64, // 已写入数据库的记录数
+    pub dropped: u64, // 队列已满被丢弃的记录数
+    pub failed: u64,  // 写入数据库失败的记录数
+}
+
+// 事件写入器：键盘钩子只负责入队，由独立线程按批次在单个事务中写入数据库
+#[derive(Clone)]
+pub struct EventWriter {
+    tx: SyncSender<WriterMessage>,
+    counters: Arc<WriterCounters>,
+}
+
+impl EventWriter {
+    // 启动写入线程，所有 EventWriter 副本被释放后线程写完剩余事件并退出。
+    // 每批记录写入时才借用 store 的写连接，其间删除数据等命令可以使用写连接
+    pub fn spawn(store: Arc<dyn Store>) -> Self {
+        let (tx, rx) = sync_channel(QUEUE_CAPACITY);
+        let counters = Arc::new(WriterCounters::default());
+        let thread_counters = counters.clone();
+
+        thread::spawn(move || {
+            let mut batch: Vec<WriteRecord> = Vec::with_capacity(BATCH_SIZE);
+            let mut last_flush = Instant::now();
+            loop {
+                let timeout = FLUSH_INTERVAL.saturating_sub(last_flush.elapsed());
+                match rx.recv_timeout(timeout) {
+                    Ok(WriterMessage::Record(record)) => {
+                        thread_counters.queued.fetch_sub(1, Ordering::Relaxed);
+                        batch.push(record);
+                        if batch.len() >= BATCH_SIZE {
+                            write_batch(store.as_ref(), &mut batch, &thread_counters);
+                            last_flush = Instant::now();
+                        }
+                    }
+                    Ok(WriterMessage::Flush(done)) => {
+                        write_batch(store.as_ref(), &mut batch, &thread_counters);
+                        last_flush = Instant::now();
+                        let _ = done.send(());
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        write_batch(store.as_ref(), &mut batch, &thread_counters);
+                        last_flush = Instant::now();
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        write_batch(store.as_ref(), &mut batch, &thread_counters);
+                        break;
+                    }
+                }
+            }
+        });
+
+        EventWriter { tx, counters }
+    }
+
+    // 提交按键事件，不会阻塞；队列已满时丢弃并计数
+    pub fn submit(&self, record: KeyboardEventRecord) {
+        self.submit_record(WriteRecord::Key(record));
+    }
+
+    // 提交一段活跃或空闲时间
+    pub fn submit_interval(&self, interval: ActivityInterval) {
+        self.submit_record(WriteRecord::Interval(interval));
+    }
+
+    pub fn submit_record(&self, record: WriteRecord) {
+        self.counters.queued.fetch_add(1, Ordering::Relaxed);
+        match self.tx.try_send(WriterMessage::Record(record)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                self.counters.queued.fetch_sub(1, Ordering::Relaxed);
+                self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // 立即写入队列中的所有事件并等待完成，用于退出前和读取最新数据前
+    pub fn flush(&self) {
+        let (done_tx, done_rx) = channel();
+        if self.tx.send(WriterMessage::Flush(done_tx)).is_ok() {
+            let _ = done_rx.recv();
+        }
+    }
+
+    pub fn stats(&self) -> WriterStats {
+        WriterStats {
+            queued: self.counters.queued.load(Ordering::Relaxed),
+            written: self.counters.written.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+            failed: self.counters.failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// 在单个事务中写入一批记录，任一条失败则整批回滚
+fn insert_batch(conn: &mut Connection, batch: &[WriteRecord]) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for record in batch {
+        match record {
+            WriteRecord::Key(event) => insert_event(&tx, event)?,
+            WriteRecord::Interval(interval) => insert_activity_interval(&tx, interval)?,
+            WriteRecord::MouseClick(click) => insert_mouse_click(&tx, click)?,
+            WriteRecord::MouseActivity(activity) => insert_mouse_activity(&tx, activity)?,
+            WriteRecord::Device(device) => upsert_input_device(&tx, device)?,
+        }
+    }
+    tx.commit()
+}
+
+// 整批写入失败时先重试一次，仍然失败则逐条写入，只丢弃本身无法写入的记录
+fn write_batch(store: &dyn Store, batch: &mut Vec<WriteRecord>, counters: &WriterCounters) {
+    if batch.is_empty() {
+        return;
+    }
+    let count = batch.len() as u64;
+    let mut conn = match store.write() {
+        Ok(conn) => conn,
+        Err(e) => {
+            counters.failed.fetch_add(count, Ordering::Relaxed);
+            println!("批量写入数据库失败: {:?}", e);
+            batch.clear();
+            return;
+        }
+    };
+    let result = insert_batch(&mut conn, batch).or_else(|_| insert_batch(&mut conn, batch));
+    match result {
+        Ok(()) => {
+            counters.written.fetch_add(count, Ordering::Relaxed);
+        }
+        Err(e) => {
+            println!("批量写入数据库失败，改为逐条写入: {:?}", e);
+            for record in batch.iter() {
+                match insert_batch(&mut conn, std::slice::from_ref(record)) {
+                    Ok(()) => {
+                        counters.written.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(e) => {
+                        counters.failed.fetch_add(1, Ordering::Relaxed);
+                        println!("写入记录失败: {:?}", e);
+                    }
+                }
+            }
+        }
+    }
+    batch.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::get_total_key_count;
+    use crate::store::MemoryStore;
+    use crate::test_support::event;
+    use chrono::Local;
+
+    #[test]
+    fn keeps_the_rest_of_a_batch_when_one_record_fails() {
+        let store = MemoryStore::new().unwrap();
+        // 鼠标点击表不存在时，点击记录每次都写入失败
+        store.write().unwrap().execute_batch("DROP TABLE mouse_clicks").unwrap();
+        let counters = WriterCounters::default();
+        let mut batch = vec![
+            WriteRecord::Key(event(Local::now(), "A", "Code.exe")),
+            WriteRecord::MouseClick(MouseClickRecord {
+                timestamp: Local::now(),
+                button: "Left".to_string(),
+                app_name: "Code.exe".to_string(),
+            }),
+            WriteRecord::Key(event(Local::now(), "A", "Code.exe")),
+        ];
+
+        write_batch(&store, &mut batch, &counters);
+
+        assert!(batch.is_empty());
+        assert_eq!(counters.written.load(Ordering::Relaxed), 2);
+        assert_eq!(counters.failed.load(Ordering::Relaxed), 1);
+        assert_eq!(get_total_key_count(&store.read().unwrap()).unwrap(), 2);
+    }
+}
@@ -0,0 +1,141 @@
+-- 版本 14 的数据库结构（平台按键码 scan_code），与版本 14 的迁移创建的表一致
+
+CREATE TABLE apps (
+    app_id INTEGER PRIMARY KEY,
+    app_name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE keys (
+    key_id INTEGER PRIMARY KEY,
+    key_code TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE keyboard_events (
+    id INTEGER PRIMARY KEY,
+    ts INTEGER NOT NULL,
+    utc_offset INTEGER NOT NULL,
+    key_id INTEGER NOT NULL REFERENCES keys(key_id),
+    app_id INTEGER NOT NULL REFERENCES apps(app_id),
+    hold_ms INTEGER,
+    flight_ms INTEGER,
+    raw_key TEXT,
+    repeat_count INTEGER NOT NULL DEFAULT 0,
+    device_id TEXT,
+    context_tag TEXT,
+    scan_code INTEGER
+);
+
+CREATE INDEX idx_keyboard_events_ts ON keyboard_events(ts);
+CREATE INDEX idx_keyboard_events_app_ts ON keyboard_events(app_id, ts);
+CREATE INDEX idx_keyboard_events_key_ts ON keyboard_events(key_id, ts);
+
+CREATE TABLE app_stats (
+    id INTEGER PRIMARY KEY,
+    app_name TEXT UNIQUE NOT NULL,
+    key_count INTEGER NOT NULL DEFAULT 0,
+    last_used_ts INTEGER NOT NULL
+);
+
+CREATE TABLE key_stats (
+    id INTEGER PRIMARY KEY,
+    key_code TEXT UNIQUE NOT NULL,
+    count INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE activity_intervals (
+    id INTEGER PRIMARY KEY,
+    start_ts INTEGER NOT NULL,
+    end_ts INTEGER NOT NULL,
+    utc_offset INTEGER NOT NULL,
+    state TEXT NOT NULL
+);
+CREATE INDEX idx_activity_intervals_start_ts ON activity_intervals(start_ts);
+
+CREATE TABLE mouse_clicks (
+    id INTEGER PRIMARY KEY,
+    ts INTEGER NOT NULL,
+    utc_offset INTEGER NOT NULL,
+    button TEXT NOT NULL,
+    app_name TEXT NOT NULL
+);
+CREATE INDEX idx_mouse_clicks_ts ON mouse_clicks(ts);
+CREATE TABLE mouse_activity (
+    id INTEGER PRIMARY KEY,
+    minute_ts INTEGER NOT NULL,
+    utc_offset INTEGER NOT NULL,
+    app_name TEXT NOT NULL,
+    move_distance REAL NOT NULL DEFAULT 0,
+    scroll_x INTEGER NOT NULL DEFAULT 0,
+    scroll_y INTEGER NOT NULL DEFAULT 0,
+    UNIQUE(minute_ts, app_name)
+);
+
+CREATE TABLE input_devices (
+    device_id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    vendor_id INTEGER NOT NULL,
+    product_id INTEGER NOT NULL,
+    last_seen_ts INTEGER NOT NULL
+);
+
+CREATE TABLE rollup_minute (
+    bucket_ts INTEGER NOT NULL,
+    utc_offset INTEGER NOT NULL,
+    app_id INTEGER NOT NULL REFERENCES apps(app_id),
+    key_id INTEGER NOT NULL REFERENCES keys(key_id),
+    count INTEGER NOT NULL,
+    PRIMARY KEY (bucket_ts, app_id, key_id)
+) WITHOUT ROWID;
+
+CREATE TABLE rollup_hour (
+    bucket_ts INTEGER NOT NULL,
+    utc_offset INTEGER NOT NULL,
+    app_id INTEGER NOT NULL REFERENCES apps(app_id),
+    key_id INTEGER NOT NULL REFERENCES keys(key_id),
+    count INTEGER NOT NULL,
+    PRIMARY KEY (bucket_ts, app_id, key_id)
+) WITHOUT ROWID;
+
+CREATE TABLE rollup_day (
+    bucket_ts INTEGER NOT NULL,
+    utc_offset INTEGER NOT NULL,
+    app_id INTEGER NOT NULL REFERENCES apps(app_id),
+    key_id INTEGER NOT NULL REFERENCES keys(key_id),
+    count INTEGER NOT NULL,
+    PRIMARY KEY (bucket_ts, app_id, key_id)
+) WITHOUT ROWID;
+
+CREATE TABLE db_meta (
+    key TEXT PRIMARY KEY,
+    value INTEGER NOT NULL
+);
+
+INSERT INTO apps (app_id, app_name) VALUES (1, 'Code.exe'), (2, 'chrome.exe');
+INSERT INTO keys (key_id, key_code) VALUES (1, 'A'), (2, 'Backspace'), (3, 'Ctrl+C');
+
+INSERT INTO keyboard_events (ts, utc_offset, key_id, app_id, hold_ms, flight_ms, raw_key, repeat_count, device_id, context_tag, scan_code) VALUES
+    (1709254800000, 28800, 1, 1, 85, NULL, 'KeyA', 0, NULL, 'project:crate', NULL),
+    (1709254800150, 28800, 3, 1, 120, 150, 'KeyC', 0, '046d:c31c:USB Keyboard', 'project:crate', NULL),
+    (1709254801000, 28800, 2, 2, NULL, 850, 'Backspace', 3, NULL, NULL, NULL);
+
+INSERT INTO app_stats (app_name, key_count, last_used_ts) VALUES
+    ('Code.exe', 2, 1709254800150),
+    ('chrome.exe', 1, 1709254801000);
+
+INSERT INTO key_stats (key_code, count) VALUES ('A', 1), ('Ctrl+C', 1), ('Backspace', 1);
+
+INSERT INTO activity_intervals (start_ts, end_ts, utc_offset, state) VALUES
+    (1709254800000, 1709255100000, 28800, 'active');
+
+INSERT INTO mouse_clicks (ts, utc_offset, button, app_name) VALUES (1709254802000, 28800, 'Left', 'chrome.exe');
+INSERT INTO mouse_activity (minute_ts, utc_offset, app_name, move_distance, scroll_x, scroll_y) VALUES (1709254800000, 28800, 'chrome.exe', 1520.5, 0, 12);
+
+INSERT INTO input_devices (device_id, name, vendor_id, product_id, last_seen_ts) VALUES
+    ('046d:c31c:USB Keyboard', 'USB Keyboard', 1133, 49948, 1709254800150);
+
+INSERT INTO rollup_minute (bucket_ts, utc_offset, app_id, key_id, count) VALUES
+    (1709254800000, 28800, 1, 1, 1), (1709254800000, 28800, 1, 3, 1), (1709254800000, 28800, 2, 2, 1);
+INSERT INTO rollup_hour (bucket_ts, utc_offset, app_id, key_id, count) VALUES
+    (1709254800000, 28800, 1, 1, 1), (1709254800000, 28800, 1, 3, 1), (1709254800000, 28800, 2, 2, 1);
+INSERT INTO rollup_day (bucket_ts, utc_offset, app_id, key_id, count) VALUES
+    (1709222400000, 28800, 1, 1, 1), (1709222400000, 28800, 1, 3, 1), (1709222400000, 28800, 2, 2, 1);
@@ -0,0 +1,35 @@
+-- 版本 4 的数据库结构（物理按键标识 raw_key），与当时的 init_db 创建的表一致
+
+CREATE TABLE keyboard_events (
+    id INTEGER PRIMARY KEY,
+    timestamp TEXT NOT NULL,
+    key_code TEXT NOT NULL,
+    app_name TEXT NOT NULL,
+    hold_ms INTEGER,
+    flight_ms INTEGER,
+    raw_key TEXT
+);
+
+CREATE TABLE app_stats (
+    id INTEGER PRIMARY KEY,
+    app_name TEXT UNIQUE NOT NULL,
+    key_count INTEGER NOT NULL DEFAULT 0,
+    last_used TEXT NOT NULL
+);
+
+CREATE TABLE key_stats (
+    id INTEGER PRIMARY KEY,
+    key_code TEXT UNIQUE NOT NULL,
+    count INTEGER NOT NULL DEFAULT 0
+);
+
+INSERT INTO keyboard_events (timestamp, key_code, app_name, hold_ms, flight_ms, raw_key) VALUES
+    ('2024-03-01T09:00:00+08:00', 'A', 'Code.exe', 85, NULL, 'KeyA'),
+    ('2024-03-01T09:00:00.150+08:00', 'Ctrl+C', 'Code.exe', 120, 150, 'KeyC'),
+    ('2024-03-01T09:00:01+08:00', 'Backspace', 'chrome.exe', NULL, 850, 'Backspace');
+
+INSERT INTO app_stats (app_name, key_count, last_used) VALUES
+    ('Code.exe', 2, '2024-03-01T09:00:00.150+08:00'),
+    ('chrome.exe', 1, '2024-03-01T09:00:01+08:00');
+
+INSERT INTO key_stats (key_code, count) VALUES ('A', 1), ('Ctrl+C', 1), ('Backspace', 1);
@@ -0,0 +1,65 @@
+-- 版本 7 的数据库结构（鼠标统计表 mouse_clicks、mouse_activity），与当时的 init_db 创建的表一致
+
+CREATE TABLE keyboard_events (
+    id INTEGER PRIMARY KEY,
+    timestamp TEXT NOT NULL,
+    key_code TEXT NOT NULL,
+    app_name TEXT NOT NULL,
+    hold_ms INTEGER,
+    flight_ms INTEGER,
+    raw_key TEXT,
+    repeat_count INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE app_stats (
+    id INTEGER PRIMARY KEY,
+    app_name TEXT UNIQUE NOT NULL,
+    key_count INTEGER NOT NULL DEFAULT 0,
+    last_used TEXT NOT NULL
+);
+
+CREATE TABLE key_stats (
+    id INTEGER PRIMARY KEY,
+    key_code TEXT UNIQUE NOT NULL,
+    count INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE activity_intervals (
+    id INTEGER PRIMARY KEY,
+    start_time TEXT NOT NULL,
+    end_time TEXT NOT NULL,
+    state TEXT NOT NULL
+);
+
+CREATE TABLE mouse_clicks (
+    id INTEGER PRIMARY KEY,
+    timestamp TEXT NOT NULL,
+    button TEXT NOT NULL,
+    app_name TEXT NOT NULL
+);
+CREATE TABLE mouse_activity (
+    id INTEGER PRIMARY KEY,
+    minute TEXT NOT NULL,
+    app_name TEXT NOT NULL,
+    move_distance REAL NOT NULL DEFAULT 0,
+    scroll_x INTEGER NOT NULL DEFAULT 0,
+    scroll_y INTEGER NOT NULL DEFAULT 0,
+    UNIQUE(minute, app_name)
+);
+
+INSERT INTO keyboard_events (timestamp, key_code, app_name, hold_ms, flight_ms, raw_key, repeat_count) VALUES
+    ('2024-03-01T09:00:00+08:00', 'A', 'Code.exe', 85, NULL, 'KeyA', 0),
+    ('2024-03-01T09:00:00.150+08:00', 'Ctrl+C', 'Code.exe', 120, 150, 'KeyC', 0),
+    ('2024-03-01T09:00:01+08:00', 'Backspace', 'chrome.exe', NULL, 850, 'Backspace', 3);
+
+INSERT INTO app_stats (app_name, key_count, last_used) VALUES
+    ('Code.exe', 2, '2024-03-01T09:00:00.150+08:00'),
+    ('chrome.exe', 1, '2024-03-01T09:00:01+08:00');
+
+INSERT INTO key_stats (key_code, count) VALUES ('A', 1), ('Ctrl+C', 1), ('Backspace', 1);
+
+INSERT INTO activity_intervals (start_time, end_time, state) VALUES
+    ('2024-03-01T09:00:00+08:00', '2024-03-01T09:05:00+08:00', 'active');
+
+INSERT INTO mouse_clicks (timestamp, button, app_name) VALUES ('2024-03-01T09:00:02+08:00', 'Left', 'chrome.exe');
+INSERT INTO mouse_activity (minute, app_name, move_distance, scroll_x, scroll_y) VALUES ('2024-03-01T09:00:00+08:00', 'chrome.exe', 1520.5, 0, 12);
@@ -58,6 +58,13 @@ window.addEventListener("DOMContentLoaded", async() => {
     // 初始化自启动设置
     await initAutostartSetting();
 
+    // 初始化鼠标统计设置
+    await initMouseSetting();
+    await initInputBackendSetting();
+    await initHotkeySetting();
+    await initRetentionSetting();
+    await initBackupSetting();
+
     // 创建退出确认模态框
     createExitConfirmModal();
 
@@ -68,6 +75,16 @@ window.addEventListener("DOMContentLoaded", async() => {
         showModal('exit-confirm-modal');
     });
 
+    // 监听器状态变化（启动、暂停、出错后自动重启）
+    await listen('monitor-status', (event) => {
+        updateMonitorStatus(event.payload);
+    });
+    try {
+        updateMonitorStatus(await invoke('get_monitor_status'));
+    } catch (error) {
+        console.error('获取监听状态失败:', error);
+    }
+
     // 加载数据（从后端获取）
     await loadData();
 
@@ -326,6 +343,171 @@ async function initAutostartSetting() {
     }
 }
 
+// 初始化鼠标统计设置
+async function initMouseSetting() {
+    try {
+        const mouseEnabled = await invoke('get_mouse_enabled');
+        const mouseToggle = document.getElementById('mouse-toggle');
+        if (mouseToggle) {
+            mouseToggle.checked = mouseEnabled;
+        }
+    } catch (error) {
+        console.error('获取鼠标统计设置失败:', error);
+    }
+}
+
+// 更新鼠标统计设置
+async function updateMouseSetting(event) {
+    const isEnabled = event.target.checked;
+
+    try {
+        await invoke('set_mouse_enabled', { enabled: isEnabled });
+        await updateMouseStats();
+    } catch (error) {
+        console.error('设置鼠标统计失败:', error);
+        alert('设置失败，请重试。');
+
+        // 恢复原状态
+        event.target.checked = !isEnabled;
+    }
+}
+
+// 初始化输入源设置
+async function initInputBackendSetting() {
+    try {
+        const backend = await invoke('get_input_backend');
+        const backendSelect = document.getElementById('input-backend-select');
+        if (backendSelect) {
+            backendSelect.value = backend;
+        }
+    } catch (error) {
+        console.error('获取输入源设置失败:', error);
+    }
+}
+
+// 更新输入源设置，重启后生效
+async function updateInputBackendSetting(event) {
+    const backendSelect = event.target;
+    const previous = await invoke('get_input_backend');
+
+    try {
+        await invoke('set_input_backend', { backend: backendSelect.value });
+        alert('输入源已更改，重启应用后生效。');
+    } catch (error) {
+        console.error('设置输入源失败:', error);
+        alert(`设置失败: ${error}`);
+
+        // 恢复原选项
+        backendSelect.value = previous;
+    }
+}
+
+// 初始化快捷键设置
+async function initHotkeySetting() {
+    try {
+        const hotkeys = await invoke('get_hotkeys');
+        document.getElementById('pause-hotkey-input').value = hotkeys.pause || '';
+        document.getElementById('incognito-hotkey-input').value = hotkeys.incognito || '';
+        document.getElementById('incognito-minutes-input').value = hotkeys.incognito_minutes;
+    } catch (error) {
+        console.error('获取快捷键设置失败:', error);
+    }
+}
+
+// 保存快捷键设置，立即生效
+async function saveHotkeySetting() {
+    const pause = document.getElementById('pause-hotkey-input').value.trim();
+    const incognito = document.getElementById('incognito-hotkey-input').value.trim();
+    const minutes = parseInt(document.getElementById('incognito-minutes-input').value, 10);
+
+    try {
+        await invoke('set_hotkeys', {
+            settings: {
+                pause: pause || null,
+                incognito: incognito || null,
+                incognito_minutes: Number.isNaN(minutes) ? 0 : minutes
+            }
+        });
+        alert('快捷键已保存。');
+    } catch (error) {
+        console.error('设置快捷键失败:', error);
+        alert(`设置失败: ${error}`);
+        await initHotkeySetting();
+    }
+}
+
+// 初始化数据保留设置
+async function initRetentionSetting() {
+    try {
+        const retention = await invoke('get_retention');
+        document.getElementById('retention-raw-input').value = retention.raw_days ?? '';
+        document.getElementById('retention-minute-input').value = retention.minute_days ?? '';
+        document.getElementById('retention-hour-input').value = retention.hour_days ?? '';
+    } catch (error) {
+        console.error('获取数据保留设置失败:', error);
+    }
+}
+
+// 保存数据保留设置，保存后立即清理过期数据
+async function saveRetentionSetting() {
+    const readDays = (id) => {
+        const days = parseInt(document.getElementById(id).value, 10);
+        return Number.isNaN(days) ? null : days;
+    };
+    const settings = {
+        raw_days: readDays('retention-raw-input'),
+        minute_days: readDays('retention-minute-input'),
+        hour_days: readDays('retention-hour-input')
+    };
+    if (settings.raw_days !== null && !confirm(`超过 ${settings.raw_days} 天的原始按键记录将被删除，只保留汇总统计，确定继续吗？`)) {
+        await initRetentionSetting();
+        return;
+    }
+
+    const saveBtn = document.getElementById('save-retention');
+    saveBtn.disabled = true;
+    try {
+        const result = await invoke('set_retention', { settings });
+        alert(`数据保留设置已保存。${result}`);
+        loadData();
+    } catch (error) {
+        console.error('设置数据保留失败:', error);
+        alert(`设置失败: ${error}`);
+        await initRetentionSetting();
+    } finally {
+        saveBtn.disabled = false;
+    }
+}
+
+// 更新仪表盘上的鼠标统计，未开启鼠标统计时隐藏
+async function updateMouseStats() {
+    const container = document.getElementById('mouse-stats-cards');
+    if (!container) return;
+
+    try {
+        const mouseEnabled = await invoke('get_mouse_enabled');
+        if (!mouseEnabled) {
+            container.style.display = 'none';
+            return;
+        }
+        const stats = await invoke('get_mouse_stats', { timeRange: currentTimeFilter });
+        container.style.display = '';
+
+        const left = stats.clicks_by_button['Left'] || 0;
+        const right = stats.clicks_by_button['Right'] || 0;
+        document.getElementById('mouse-clicks-value').textContent = stats.total_clicks.toLocaleString();
+        document.getElementById('mouse-clicks-detail').textContent = `左键 ${left.toLocaleString()} / 右键 ${right.toLocaleString()}`;
+        document.getElementById('mouse-distance-value').textContent = Math.round(stats.move_distance).toLocaleString();
+        document.getElementById('mouse-scroll-value').textContent = stats.scroll_distance.toLocaleString();
+
+        const topApp = stats.app_clicks[0];
+        document.getElementById('mouse-top-app-value').textContent = topApp ? topApp[0] : '-';
+        document.getElementById('mouse-top-app-detail').textContent = `${topApp ? topApp[1].toLocaleString() : 0} 次点击`;
+    } catch (error) {
+        console.error('获取鼠标统计失败:', error);
+    }
+}
+
 // 更新退出确认设置
 async function updateExitConfirmSetting(event) {
     const showConfirm = event.target.checked;
@@ -401,6 +583,46 @@ async function initRecordingStatus() {
     }
 }
 
+// 根据监听器状态更新侧边栏的记录状态，监听出错时显示原因
+function updateMonitorStatus(status) {
+    const statusIndicator = document.querySelector('.status-indicator');
+    const statusText = statusIndicator.nextElementSibling;
+    const toggleBtn = document.getElementById('toggle-recording');
+
+    // 快捷键也会暂停或恢复录制，同步按钮状态
+    if (status.state === 'running' || status.state === 'paused') {
+        isRecording = status.state === 'running';
+        toggleBtn.textContent = isRecording ? '暂停' : '继续';
+    }
+
+    statusIndicator.classList.toggle('active', status.state === 'running');
+    statusIndicator.classList.toggle('failed', status.state === 'failed');
+    statusText.title = status.error || '';
+
+    switch (status.state) {
+        case 'running':
+            statusText.textContent = '正在记录';
+            break;
+        case 'starting':
+            statusText.textContent = status.restarts > 0 ? '正在重启' : '正在启动';
+            break;
+        case 'failed':
+            statusText.textContent = '监听失败';
+            break;
+        case 'paused':
+            if (status.resume_at) {
+                const resumeAt = new Date(status.resume_at);
+                const time = resumeAt.toLocaleTimeString('zh-CN', { hour: '2-digit', minute: '2-digit' });
+                statusText.textContent = `临时暂停，${time} 恢复`;
+                break;
+            }
+            statusText.textContent = '已暂停';
+            break;
+        default:
+            statusText.textContent = '已暂停';
+    }
+}
+
 // 切换页面
 function switchPage(pageId) {
     // 隐藏所有页面
@@ -525,6 +747,42 @@ function initButtonEvents() {
         autostartToggle.addEventListener('change', updateAutostartSetting);
     }
 
+    // 鼠标统计开关
+    const mouseToggle = document.getElementById('mouse-toggle');
+    if (mouseToggle) {
+        mouseToggle.addEventListener('change', updateMouseSetting);
+    }
+
+    // 输入源选择
+    const inputBackendSelect = document.getElementById('input-backend-select');
+    if (inputBackendSelect) {
+        inputBackendSelect.addEventListener('change', updateInputBackendSetting);
+    }
+
+    // 保存快捷键
+    const saveHotkeysBtn = document.getElementById('save-hotkeys');
+    if (saveHotkeysBtn) {
+        saveHotkeysBtn.addEventListener('click', saveHotkeySetting);
+    }
+
+    // 保存数据保留设置
+    const saveRetentionBtn = document.getElementById('save-retention');
+    if (saveRetentionBtn) {
+        saveRetentionBtn.addEventListener('click', saveRetentionSetting);
+    }
+
+    // 保存自动备份设置
+    const saveBackupBtn = document.getElementById('save-backup-settings');
+    if (saveBackupBtn) {
+        saveBackupBtn.addEventListener('click', saveBackupSetting);
+    }
+
+    // 立即备份按钮
+    const createBackupBtn = document.getElementById('create-backup');
+    if (createBackupBtn) {
+        createBackupBtn.addEventListener('click', createBackup);
+    }
+
     // 打开数据文件夹按钮
     const openDataFolderBtn = document.getElementById('open-data-folder');
     if (openDataFolderBtn) {
@@ -541,6 +799,12 @@ function initButtonEvents() {
         });
     }
 
+    // 重建统计汇总按钮
+    const rebuildRollupsBtn = document.getElementById('rebuild-rollups');
+    if (rebuildRollupsBtn) {
+        rebuildRollupsBtn.addEventListener('click', rebuildRollups);
+    }
+
     // 健康评估按钮
     const assessHealthBtn = document.getElementById('assess-health');
     if (assessHealthBtn) {
@@ -920,6 +1184,11 @@ function generateInsights(metrics, riskFlags, userProfile) {
         insights.push(`您的平均连续打字时长为${Math.round(metrics.avg_session_duration_seconds/60)}分钟，建议每20-30分钟短暂休息。`);
     }
 
+    // 添加按键按住时长相关洞察
+    if (metrics.long_hold_ratio > 5) {
+        insights.push(`您有${metrics.long_hold_ratio.toFixed(1)}%的按键按住超过0.5秒（平均按住${Math.round(metrics.avg_hold_ms)}毫秒），长时间按压按键会增加手指肌腱的负担。`);
+    }
+
     // 根据用户配置添加洞察
     if (userProfile.dailyHours === '10+' && !userProfile.hasBreaks) {
         insights.push('长时间使用电脑而不定期休息，会显著增加颈部、肩部和手腕疲劳的风险。');
@@ -1096,6 +1365,7 @@ async function updateDataByTimeRange(timeRange) {
             // 使用实际数据更新UI
             updateStatsCards(stats);
             updateCharts(stats);
+            updateMouseStats();
 
             // 更新KPM显示
             if (kpmValueElement) {
@@ -1227,6 +1497,9 @@ function updateCharts(stats) {
         // 应用使用统计 - 恢复应用按键数量排行图表的更新
         updateAppKeysChart(stats);
 
+        // 应用类别按键分布
+        updateAppCategoryChart(stats);
+
         // 活动热力图
         updateActivityHeatmap(stats);
 
@@ -2206,6 +2479,7 @@ async function loadData() {
             // 使用实际数据更新UI
             updateStatsCards(stats);
             updateCharts(stats);
+            updateMouseStats();
 
             // 更新KPM显示
             if (kpmValueElement) {
@@ -2855,6 +3129,68 @@ function updateWeeklyDistributionChart(stats) {
 }
 
 // 应用按键数量排行图表
+// 更新应用类别按键分布图表
+function updateAppCategoryChart(stats) {
+    const chartElement = document.getElementById('app-category-chart');
+    if (!chartElement) return;
+
+    // 清除旧的图表
+    chartElement.innerHTML = '';
+
+    // 按按键数量排序
+    const appCategories = stats.app_categories || {};
+    const categories = Object.keys(appCategories)
+        .sort((a, b) => appCategories[b] - appCategories[a]);
+
+    // 如果没有数据，显示提示信息
+    if (categories.length === 0) {
+        const noDataMessage = document.createElement('div');
+        noDataMessage.className = 'no-data-message';
+        noDataMessage.textContent = '暂无应用类别数据';
+        noDataMessage.style.display = 'flex';
+        noDataMessage.style.justifyContent = 'center';
+        noDataMessage.style.alignItems = 'center';
+        noDataMessage.style.height = '100%';
+        noDataMessage.style.fontSize = '18px';
+        noDataMessage.style.color = '#aaa';
+        chartElement.appendChild(noDataMessage);
+        return;
+    }
+
+    const canvas = document.createElement('canvas');
+    chartElement.appendChild(canvas);
+
+    new Chart(canvas, {
+        type: 'doughnut',
+        data: {
+            labels: categories,
+            datasets: [{
+                data: categories.map(category => appCategories[category]),
+                backgroundColor: [
+                    '#4a6cf7', '#6c8cff', '#94a3ff', '#b6bcff',
+                    '#d8d5ff', '#f092ff', '#ff71a3', '#ff6b6b'
+                ],
+            }]
+        },
+        options: {
+            responsive: true,
+            maintainAspectRatio: false,
+            plugins: {
+                legend: {
+                    position: 'right',
+                },
+                tooltip: {
+                    callbacks: {
+                        label: function(context) {
+                            return `${context.label}: ${context.raw} 次按键`;
+                        }
+                    }
+                }
+            }
+        }
+    });
+}
+
 function updateAppKeysChart(stats) {
     const chartElement = document.getElementById('app-keys-chart');
     if (!chartElement) return;
@@ -3091,6 +3427,135 @@ async function exportData() {
     }
 }
 
+// 初始化自动备份设置和备份列表
+async function initBackupSetting() {
+    try {
+        const settings = await invoke('get_backup_settings');
+        document.getElementById('backup-toggle').checked = settings.enabled;
+        document.getElementById('backup-interval-input').value = settings.interval_hours;
+        document.getElementById('backup-keep-input').value = settings.keep;
+    } catch (error) {
+        console.error('获取自动备份设置失败:', error);
+    }
+    await loadBackupList();
+}
+
+// 保存自动备份设置
+async function saveBackupSetting() {
+    const readNumber = (id) => {
+        const value = parseInt(document.getElementById(id).value, 10);
+        return Number.isNaN(value) ? 0 : value;
+    };
+    try {
+        await invoke('set_backup_settings', {
+            settings: {
+                enabled: document.getElementById('backup-toggle').checked,
+                interval_hours: readNumber('backup-interval-input'),
+                keep: readNumber('backup-keep-input')
+            }
+        });
+        alert('自动备份设置已保存。');
+    } catch (error) {
+        console.error('设置自动备份失败:', error);
+        alert(`设置失败: ${error}`);
+        await initBackupSetting();
+    }
+}
+
+// 显示已有的备份，每项可以恢复
+async function loadBackupList() {
+    const list = document.getElementById('backup-list');
+    if (!list) return;
+
+    const kindNames = { auto: '自动备份', manual: '手动备份', before_restore: '恢复前的数据' };
+    list.innerHTML = '';
+    try {
+        const backups = await invoke('list_backups');
+        if (backups.length === 0) {
+            list.textContent = '暂无备份';
+            return;
+        }
+        backups.forEach(backup => {
+            const item = document.createElement('div');
+            item.className = 'backup-item';
+
+            const detail = document.createElement('span');
+            detail.className = 'backup-detail';
+            const size = (backup.size_bytes / 1024 / 1024).toFixed(1);
+            const count = backup.event_count === null ? '无法读取' : `${backup.event_count.toLocaleString()} 次按键`;
+            detail.textContent = `${new Date(backup.created_at).toLocaleString()} · ${kindNames[backup.kind] || backup.kind} · ${size} MB · ${count}`;
+            detail.title = backup.path;
+
+            const restoreBtn = document.createElement('button');
+            restoreBtn.className = 'small-btn';
+            restoreBtn.textContent = '恢复';
+            restoreBtn.addEventListener('click', () => restoreBackup(backup));
+
+            item.appendChild(detail);
+            item.appendChild(restoreBtn);
+            list.appendChild(item);
+        });
+    } catch (error) {
+        console.error('获取备份列表失败:', error);
+        list.textContent = `获取备份列表失败: ${error}`;
+    }
+}
+
+// 立即备份数据库
+async function createBackup() {
+    const backupBtn = document.getElementById('create-backup');
+    const originalText = backupBtn.textContent;
+    backupBtn.textContent = '备份中...';
+    backupBtn.disabled = true;
+    try {
+        const backup = await invoke('create_backup');
+        alert(`已备份到 ${backup.path}`);
+        await loadBackupList();
+    } catch (error) {
+        console.error('备份数据库失败:', error);
+        alert(`备份数据库失败: ${error}`);
+    } finally {
+        backupBtn.textContent = originalText;
+        backupBtn.disabled = false;
+    }
+}
+
+// 用所选备份替换当前数据
+async function restoreBackup(backup) {
+    const time = new Date(backup.created_at).toLocaleString();
+    if (!confirm(`当前数据将被替换为 ${time} 的备份，恢复前的数据会另存为一份备份。确定继续吗？`)) {
+        return;
+    }
+    try {
+        const result = await invoke('restore_backup', { fileName: backup.file_name });
+        alert(result);
+        loadData();
+    } catch (error) {
+        console.error('恢复备份失败:', error);
+        alert(`恢复备份失败: ${error}`);
+    }
+    await loadBackupList();
+}
+
+// 重建统计汇总
+async function rebuildRollups() {
+    const rebuildBtn = document.getElementById('rebuild-rollups');
+    const originalText = rebuildBtn.textContent;
+    rebuildBtn.textContent = '重建中...';
+    rebuildBtn.disabled = true;
+    try {
+        const result = await invoke('rebuild_rollups');
+        alert(result);
+        loadData();
+    } catch (error) {
+        console.error('重建统计汇总失败:', error);
+        alert(`重建统计汇总失败: ${error}`);
+    } finally {
+        rebuildBtn.textContent = originalText;
+        rebuildBtn.disabled = false;
+    }
+}
+
 // 删除数据函数
 async function deleteData() {
     const range = document.getElementById('delete-range').value;